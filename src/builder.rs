@@ -0,0 +1,297 @@
+//! Fluent construction of a [`VoxScene`] from code.
+//!
+//! Building a [`VoxScene`] by hand requires filling the palette, keeping track
+//! of model indices, and composing [`SceneNode`]s. The [`SceneBuilder`] takes
+//! care of this bookkeeping, and only produces valid scenes. (Or a
+//! [`BuildError`] otherwise)
+//!
+//! # Example: Place a model twice
+//!
+//! ```
+//! use vox_parser::builder::{NodeBuilder, SceneBuilder};
+//! use vox_parser::data::spec::MatRowCols;
+//!
+//! let mut builder = SceneBuilder::new( );
+//! builder.color( 1, (255, 0, 0, 255) );
+//!
+//! // A red 2x2x2 cube
+//! let cube =
+//!   builder.add_voxels(
+//!     (0..8).map( |i| ( i & 1, ( i >> 1 ) & 1, ( i >> 2 ) & 1, 1 ) )
+//!   );
+//!
+//! builder
+//!   .add( NodeBuilder::shape( cube ).layer( "Cubes" ) )
+//!   .add(
+//!     NodeBuilder::shape( cube )
+//!       .translation( (10, 0, 0) )
+//!       .rotation( MatRowCols::TwoOneThree( true, false, false ) )
+//!       .layer( "Cubes" )
+//!   );
+//!
+//! let scene = builder.build( ).unwrap( );
+//! assert_eq!( scene.models.len( ), 1 );
+//! assert_eq!( scene.layers.len( ), 1 );
+//! ```
+
+
+// Local imports
+use crate::data::spec::{DEFAULT_PALETTE, MatRowCols};
+use crate::data::custom::{Layer, Material, MaterialType, Model, NodeType,
+  SceneNode, VoxScene};
+
+
+/// Incrementally builds a [`VoxScene`].
+///
+/// The palette starts out as the default palette
+/// ([`DEFAULT_PALETTE`](crate::data::spec::DEFAULT_PALETTE)) with diffuse
+/// materials. Nodes added with [`SceneBuilder::add`] become the children of
+/// the root group in the scene graph.
+pub struct SceneBuilder {
+  palette : [Material; 255],
+  models  : Vec< Model >,
+  layers  : Vec< Layer >,
+  nodes   : Vec< NodeBuilder >,
+  /// The first invalid call, which is reported by [`SceneBuilder::build`]
+  error   : Option< BuildError >
+}
+
+/// An error while building a scene. (See [`SceneBuilder::build`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildError {
+  /// A color or material was set for palette index 0, which does not exist.
+  ZeroPaletteIndex,
+  /// A shape node references a model that was not added to the builder.
+  UnknownModel( u32 )
+}
+
+impl SceneBuilder {
+  /// Constructs an empty scene builder with the default palette.
+  pub fn new( ) -> SceneBuilder {
+    let palette =
+      std::array::from_fn( |i| Material::new( DEFAULT_PALETTE[ i ], MaterialType::Diffuse ) );
+
+    SceneBuilder {
+      palette,
+      models: Vec::new( ),
+      layers: Vec::new( ),
+      nodes:  Vec::new( ),
+      error:  None
+    }
+  }
+
+  /// Sets the color of the given palette index. The material type is
+  /// unchanged.
+  ///
+  /// Palette index 0 does not exist, so setting it makes
+  /// [`SceneBuilder::build`] fail.
+  pub fn color( &mut self, index: u8, rgba: (u8,u8,u8,u8) ) -> &mut SceneBuilder {
+    match index {
+      0 => { self.error.get_or_insert( BuildError::ZeroPaletteIndex ); },
+      _ => self.palette[ index as usize - 1 ].rgba = rgba
+    }
+    self
+  }
+
+  /// Sets the material (including its color) of the given palette index.
+  ///
+  /// Palette index 0 does not exist, so setting it makes
+  /// [`SceneBuilder::build`] fail.
+  pub fn material( &mut self, index: u8, material: Material ) -> &mut SceneBuilder {
+    match index {
+      0 => { self.error.get_or_insert( BuildError::ZeroPaletteIndex ); },
+      _ => self.palette[ index as usize - 1 ] = material
+    }
+    self
+  }
+
+  /// Adds a model to the scene, and returns its model index. That index is
+  /// used to place the model with [`NodeBuilder::shape`].
+  ///
+  /// Note that the model is _not_ yet placed in the scene graph.
+  pub fn add_model( &mut self, model: Model ) -> u32 {
+    self.models.push( model );
+    ( self.models.len( ) - 1 ) as u32
+  }
+
+  /// Adds a model from its `(x,y,z,palette_index)` voxels, and returns its
+  /// model index. (See [`SceneBuilder::add_model`])
+  ///
+  /// The size of the model is the smallest size that contains all voxels.
+  /// Voxels with palette index 0 represent empty space, and are omitted.
+  pub fn add_voxels< I >( &mut self, voxels: I ) -> u32
+      where I : IntoIterator< Item = (u8,u8,u8,u8) > {
+    let xyzi: Vec< (u8,u8,u8,u8) > =
+      voxels.into_iter( ).filter( |v| v.3 != 0 ).collect( );
+
+    // A model has at least size 1 in every dimension
+    let mut size = (1, 1, 1);
+    for (x,y,z,_) in &xyzi {
+      size.0 = size.0.max( *x as u32 + 1 );
+      size.1 = size.1.max( *y as u32 + 1 );
+      size.2 = size.2.max( *z as u32 + 1 );
+    }
+
+    self.add_model( Model { size, xyzi } )
+  }
+
+  /// Returns the id of the layer with the given name. If no such layer exists,
+  /// it is created.
+  pub fn layer( &mut self, name: &str ) -> u32 {
+    if let Some( i ) = self.layers.iter( ).position( |l| l.name == name ) {
+      i as u32
+    } else {
+      self.layers.push( Layer { name: name.to_string( ), is_hidden: false } );
+      ( self.layers.len( ) - 1 ) as u32
+    }
+  }
+
+  /// Hides or shows the layer with the given name. If no such layer exists, it
+  /// is created.
+  pub fn hide_layer( &mut self, name: &str, is_hidden: bool ) -> &mut SceneBuilder {
+    let layer_id = self.layer( name );
+    self.layers[ layer_id as usize ].is_hidden = is_hidden;
+    self
+  }
+
+  /// Adds the node to the root group of the scene graph.
+  pub fn add( &mut self, node: NodeBuilder ) -> &mut SceneBuilder {
+    self.nodes.push( node );
+    self
+  }
+
+  /// Constructs the scene.
+  ///
+  /// Layers referenced by name in the nodes are created when they do not yet
+  /// exist. Fails when palette index 0 was set, or when a shape node
+  /// references a model that was not added to this builder.
+  pub fn build( mut self ) -> Result< VoxScene, BuildError > {
+    if let Some( err ) = self.error {
+      return Err( err );
+    }
+    let nodes = std::mem::take( &mut self.nodes );
+    let children =
+      nodes.into_iter( ).map( |n| self.build_node( n ) ).collect::< Result< _, _ > >( )?;
+
+    let graph =
+      SceneNode {
+        name:        None,
+        rotation:    MatRowCols::identity( ),
+        translation: (0,0,0),
+        layer_id:    None,
+        node_type:   NodeType::Group( children )
+      };
+
+    Ok(
+      VoxScene {
+        palette: self.palette,
+        models:  self.models,
+        graph,
+        layers:  self.layers
+      }
+    )
+  }
+
+  /// Converts the node description into an actual scene node. Layers are
+  /// resolved by name.
+  fn build_node( &mut self, n: NodeBuilder ) -> Result< SceneNode, BuildError > {
+    let layer_id = n.layer.map( |name| self.layer( &name ) );
+
+    let node_type =
+      match n.kind {
+        NodeKind::Shape( model_id ) => {
+          if model_id as usize >= self.models.len( ) {
+            return Err( BuildError::UnknownModel( model_id ) );
+          }
+          NodeType::Shape( model_id )
+        },
+        NodeKind::Group( children ) =>
+          NodeType::Group(
+            children.into_iter( ).map( |c| self.build_node( c ) ).collect::< Result< _, _ > >( )?
+          )
+      };
+
+    Ok(
+      SceneNode {
+        name:        n.name,
+        rotation:    n.rotation,
+        translation: n.translation,
+        layer_id,
+        node_type
+      }
+    )
+  }
+}
+
+impl Default for SceneBuilder {
+  fn default( ) -> SceneBuilder {
+    SceneBuilder::new( )
+  }
+}
+
+/// Description of a node in the scene graph. (Used by [`SceneBuilder`])
+///
+/// By default, a node has no name, no rotation, no translation, and belongs to
+/// no layer.
+pub struct NodeBuilder {
+  name        : Option< String >,
+  rotation    : MatRowCols,
+  translation : (i32,i32,i32),
+  layer       : Option< String >,
+  kind        : NodeKind
+}
+
+/// Internal. The contents of a [`NodeBuilder`].
+enum NodeKind {
+  Shape( u32 ),
+  Group( Vec< NodeBuilder > )
+}
+
+impl NodeBuilder {
+  /// Constructs a node which places the model with the given index. (See
+  /// [`SceneBuilder::add_model`])
+  pub fn shape( model_id: u32 ) -> NodeBuilder {
+    NodeBuilder::with_kind( NodeKind::Shape( model_id ) )
+  }
+
+  /// Constructs a node which groups the given nodes. Transformations of the
+  /// group apply to all of its children.
+  pub fn group< I >( children: I ) -> NodeBuilder
+      where I : IntoIterator< Item = NodeBuilder > {
+    NodeBuilder::with_kind( NodeKind::Group( children.into_iter( ).collect( ) ) )
+  }
+
+  /// Sets the name of the node.
+  pub fn name( mut self, name: &str ) -> NodeBuilder {
+    self.name = Some( name.to_string( ) );
+    self
+  }
+
+  /// Sets the translation of the node.
+  pub fn translation( mut self, translation: (i32,i32,i32) ) -> NodeBuilder {
+    self.translation = translation;
+    self
+  }
+
+  /// Sets the rotation (or mirroring) of the node.
+  pub fn rotation( mut self, rotation: MatRowCols ) -> NodeBuilder {
+    self.rotation = rotation;
+    self
+  }
+
+  /// Places the node on the layer with the given name.
+  pub fn layer( mut self, name: &str ) -> NodeBuilder {
+    self.layer = Some( name.to_string( ) );
+    self
+  }
+
+  fn with_kind( kind: NodeKind ) -> NodeBuilder {
+    NodeBuilder {
+      name:        None,
+      rotation:    MatRowCols::identity( ),
+      translation: (0,0,0),
+      layer:       None,
+      kind
+    }
+  }
+}
@@ -28,6 +28,11 @@ static DEFAULT_WEIGHT: f32 = 0.0;
 static DEFAULT_IOR:   f32 = 0.0;
 static DEFAULT_EMIT:  f32 = 0.0;
 static DEFAULT_LDR:   f32 = 0.0;
+static DEFAULT_SPEC:    f32 = 0.0;
+static DEFAULT_ATT:     f32 = 0.0;
+static DEFAULT_DENSITY: f32 = 0.0;
+static DEFAULT_G:       f32 = 0.0;
+static DEFAULT_MEDIA_TYPE: custom::MediaType = custom::MediaType::Absorb;
 
 /// Internal. Enum over the types of nodes in the scene graph.
 #[derive(Debug)]
@@ -37,16 +42,6 @@ enum ParsedNode< 'a > {
   Shape( spec::ShapeNode< 'a > )
 }
 
-/// Internal. Default diffuse material within the palette.
-/// 
-/// The color is surely overwritten by either values in the RGBA chunk, or the
-/// corresponding color in the default palette.
-static DEFAULT_MATERIAL: custom::Material =
-  custom::Material {
-    rgba: (0,0,0,0),
-    mat_type: custom::MaterialType::Diffuse
-  };
-
 /// Parses and converts [`RawChunk`]s into a [`VoxScene`].
 /// 
 /// As not all chunks are fully specified, this function ensures unknown chunks
@@ -54,10 +49,8 @@ static DEFAULT_MATERIAL: custom::Material =
 pub fn to_custom< 'a >( chunks: &'a [RawChunk] ) -> Result< VoxScene, VoxErrorKind > {
   // Keep a parsing state and update it while traversing the chunks.
 
-  let mut palette: [custom::Material; 255] = [DEFAULT_MATERIAL; 255];
-  for i in 0..255 {
-    palette[ i ].rgba = DEFAULT_PALETTE[ i ];
-  }
+  let mut palette: [custom::Material; 255] =
+    std::array::from_fn( |i| custom::Material::new( DEFAULT_PALETTE[ i ], custom::MaterialType::Diffuse ) );
 
   let mut models: Vec< custom::Model > = Vec::new( );
   let mut latest_size: Option<(u32,u32,u32)> = None;
@@ -109,7 +102,9 @@ pub fn to_custom< 'a >( chunks: &'a [RawChunk] ) -> Result< VoxScene, VoxErrorKi
         } else {
           // Note that palette array index 0 is index 1 in the actual palette.
           // (Index 0 represents the "null"-material, which is not stored)
-          palette[ m.id as usize - 1 ].mat_type = matl2material( &m );
+          let mat_type = matl2material( &m );
+          palette[ m.id as usize - 1 ].unknown_props = unheld_props( &m, &mat_type );
+          palette[ m.id as usize - 1 ].mat_type = mat_type;
         },
       Ok( ( _, spec::Chunk::LAYR( layr ) ) ) => {
         let uid = layr.id as usize;
@@ -158,6 +153,7 @@ pub fn to_custom< 'a >( chunks: &'a [RawChunk] ) -> Result< VoxScene, VoxErrorKi
       // single model. Introduce a simple scene for this kind of file.
 
       custom::SceneNode {
+        name: None,
         rotation: spec::MatRowCols::identity( ),
         translation: (0,0,0),
         layer_id: Some( 0 ),
@@ -222,7 +218,7 @@ pub fn from_custom< 'a >( s: &'a VoxScene ) -> Vec< spec::Chunk< 'a > > {
   // Materials
   for i in 0..255 {
     chunks.push(
-      spec::Chunk::MATL( material2matl( i + 1, &s.palette[ i as usize ].mat_type ) )
+      spec::Chunk::MATL( material2matl( i + 1, &s.palette[ i as usize ] ) )
     )
   }
 
@@ -277,6 +273,7 @@ fn build_scene< 'a >(
 
       Some(
         custom::SceneNode {
+          name:        n.name.clone( ),
           rotation:    n.rotation,
           translation: n.translation,
           layer_id:    n.layer_id,
@@ -299,7 +296,7 @@ fn export_scene< 'a >( dst: &mut Vec< spec::Chunk< 'a > >, i: &mut u32, scene: &
   let transform_node =
     spec::TransformNode {
       node_id:       node_id,
-      name:          None,
+      name:          scene.name.clone( ),
       is_hidden:     false,
       child_node_id: node_id + 1,
       layer_id:      scene.layer_id,
@@ -354,22 +351,29 @@ fn export_scene< 'a >( dst: &mut Vec< spec::Chunk< 'a > >, i: &mut u32, scene: &
 /// Converts the `MATL` chunk to a material type in the custom structure
 /// ([`custom::Material`]).
 fn matl2material( m: &spec::Matl ) -> custom::MaterialType {
+  // Older versions store the specular value as `_spec`
+  let prop_spec = to_val( DEFAULT_SPEC, m.prop_sp.or( m.prop_spec ) );
+
   match m.prop_type {
     spec::MatlType::Diffuse => custom::MaterialType::Diffuse,
     spec::MatlType::Metal =>
       custom::MaterialType::Metal(
         custom::MetalMaterial {
-          prop_rough: to_val( DEFAULT_ROUGH, m.prop_rough ),
-          prop_ior:   to_val( DEFAULT_IOR,   m.prop_ior ),
-          prop_metal: to_val( DEFAULT_METAL, m.prop_metal )
+          prop_rough:   to_val( DEFAULT_ROUGH, m.prop_rough ),
+          prop_ior:     to_val( DEFAULT_IOR,   m.prop_ior ),
+          prop_metal:   to_val( DEFAULT_METAL, m.prop_metal ),
+          prop_spec,
+          prop_plastic: m.prop_plastic
         }
       ),
     spec::MatlType::Glass =>
       custom::MaterialType::Glass(
         custom::GlassMaterial {
-          prop_rough:  to_val( DEFAULT_ROUGH,  m.prop_rough ),
-          prop_ior:    to_val( DEFAULT_IOR,    m.prop_ior ),
-          prop_weight: to_val( DEFAULT_WEIGHT, m.prop_weight )
+          prop_rough:   to_val( DEFAULT_ROUGH,   m.prop_rough ),
+          prop_ior:     to_val( DEFAULT_IOR,     m.prop_ior ),
+          prop_weight:  to_val( DEFAULT_WEIGHT,  m.prop_weight ),
+          prop_att:     to_val( DEFAULT_ATT,     m.prop_att ),
+          prop_density: to_val( DEFAULT_DENSITY, m.prop_density )
         }
       ),
     spec::MatlType::Emit =>
@@ -383,14 +387,83 @@ fn matl2material( m: &spec::Matl ) -> custom::MaterialType {
     spec::MatlType::Blend =>
       custom::MaterialType::Blend(
         custom::BlendMaterial {
-          prop_rough: to_val( DEFAULT_ROUGH, m.prop_rough ),
-          prop_metal: to_val( DEFAULT_METAL, m.prop_metal ),
-          prop_ior:   to_val( DEFAULT_IOR,   m.prop_ior ),
-          prop_alpha: to_val( DEFAULT_ALPHA, m.prop_alpha )
+          prop_rough:   to_val( DEFAULT_ROUGH,   m.prop_rough ),
+          prop_metal:   to_val( DEFAULT_METAL,   m.prop_metal ),
+          prop_ior:     to_val( DEFAULT_IOR,     m.prop_ior ),
+          prop_alpha:   to_val( DEFAULT_ALPHA,   m.prop_alpha ),
+          prop_spec,
+          prop_plastic: m.prop_plastic,
+          prop_att:     to_val( DEFAULT_ATT,     m.prop_att ),
+          prop_density: to_val( DEFAULT_DENSITY, m.prop_density )
         }
       ),
-    spec::MatlType::Media => custom::MaterialType::Media
+    spec::MatlType::Media =>
+      custom::MaterialType::Media(
+        custom::MediaMaterial {
+          prop_media_type: m.prop_media_type.map_or( DEFAULT_MEDIA_TYPE, media_type ),
+          prop_density:    to_val( DEFAULT_DENSITY, m.prop_density ),
+          prop_g:          to_val( DEFAULT_G,       m.prop_g ),
+          prop_ior:        to_val( DEFAULT_IOR,     m.prop_ior ),
+          prop_rough:      to_val( DEFAULT_ROUGH,   m.prop_rough )
+        }
+      )
+  }
+}
+
+/// Returns the properties of the `MATL` chunk which the material type does not
+/// hold, by their keys. Besides the unknown properties, these are the
+/// properties of other material types. (e.g., `_rough` of a diffuse material)
+///
+/// `_spec` is included unless `_sp` supersedes it. When `_sp` is absent, the
+/// material type holds the same value, but the key tells [`material2matl`] to
+/// write it back as `_spec`.
+fn unheld_props( m: &spec::Matl, mat_type: &custom::MaterialType ) -> HashMap< String, String > {
+  use custom::MaterialType as T;
+  let metal = matches!( mat_type, T::Metal( _ ) );
+  let glass = matches!( mat_type, T::Glass( _ ) );
+  let emit  = matches!( mat_type, T::Emit( _ ) );
+  let blend = matches!( mat_type, T::Blend( _ ) );
+  let media = matches!( mat_type, T::Media( _ ) );
+
+  // Every property, and whether the material type holds it
+  let props =
+    [ ( "_weight", m.prop_weight,  glass )
+    , ( "_rough",  m.prop_rough,   metal || glass || blend || media )
+    , ( "_spec",   m.prop_spec,    ( metal || blend ) && m.prop_sp.is_some( ) )
+    , ( "_ior",    m.prop_ior,     metal || glass || blend || media )
+    , ( "_att",    m.prop_att,     glass || blend )
+    , ( "_d",      m.prop_density, glass || blend || media )
+    , ( "_alpha",  m.prop_alpha,   blend )
+    , ( "_emit",   m.prop_emit,    emit )
+    , ( "_ldr",    m.prop_ldr,     emit )
+    , ( "_metal",  m.prop_metal,   metal || blend )
+    , ( "_sp",     m.prop_sp,      metal || blend )
+    , ( "_g",      m.prop_g,       media )
+    ];
+
+  let mut out = m.unknown_props.clone( );
+  for (key, v, is_held) in &props {
+    if let (Some( v ), false) = (v, is_held) {
+      out.insert( key.to_string( ), v.to_string( ) );
+    }
+  }
+  if let (Some( flux ), false) = (m.prop_flux, emit) {
+    out.insert( "_flux".to_string( ), flux.to_string( ) );
+  }
+  if m.prop_plastic && !( metal || blend ) {
+    out.insert( "_plastic".to_string( ), "1".to_string( ) );
+  }
+  if let (Some( t ), false) = (m.prop_media_type, media) {
+    let t =
+      match t {
+        spec::MatlMediaType::Absorb  => "_absorb",
+        spec::MatlMediaType::Scatter => "_scatter",
+        spec::MatlMediaType::Emit    => "_emit",
+        spec::MatlMediaType::Sss     => "_sss"
+      };
+    out.insert( "_media_type".to_string( ), t.to_string( ) );
   }
+  out
 }
 
 /// Converts the `MATT` chunk to a material type in the custom structure
@@ -401,17 +474,21 @@ fn matt2material( m: &spec::Matt ) -> custom::MaterialType {
     spec::MattType::Metal( w ) =>
       custom::MaterialType::Metal(
         custom::MetalMaterial {
-          prop_rough: to_val( DEFAULT_ROUGH, m.prop_roughness ),
-          prop_ior:   to_val( DEFAULT_IOR,   m.prop_ior ),
-          prop_metal: w,
+          prop_rough:   to_val( DEFAULT_ROUGH, m.prop_roughness ),
+          prop_ior:     to_val( DEFAULT_IOR,   m.prop_ior ),
+          prop_metal:   w,
+          prop_spec:    to_val( DEFAULT_SPEC,  m.prop_specular ),
+          prop_plastic: m.prop_plastic.is_some_and( |p| p > 0.0 ),
         }
       ),
     spec::MattType::Glass( w ) =>
       custom::MaterialType::Glass(
         custom::GlassMaterial {
-          prop_rough:  to_val( DEFAULT_ROUGH, m.prop_roughness ),
-          prop_ior:    to_val( DEFAULT_IOR,   m.prop_ior ),
-          prop_weight: w,
+          prop_rough:   to_val( DEFAULT_ROUGH, m.prop_roughness ),
+          prop_ior:     to_val( DEFAULT_IOR,   m.prop_ior ),
+          prop_weight:  w,
+          prop_att:     to_val( DEFAULT_ATT,   m.prop_attenuation ),
+          prop_density: DEFAULT_DENSITY,
         }
       ),
     spec::MattType::Emissive( w ) =>
@@ -429,41 +506,99 @@ fn matt2material( m: &spec::Matt ) -> custom::MaterialType {
 }
 
 /// Converts the material back to the `MATL` chunk.
-fn material2matl( id: u8, t: &custom::MaterialType ) -> spec::Matl {
+fn material2matl( id: u8, material: &custom::Material ) -> spec::Matl {
+  let mut out =
+    match &material.mat_type {
+      custom::MaterialType::Diffuse =>
+        spec::Matl::new( id, spec::MatlType::Diffuse ),
+      custom::MaterialType::Metal( m ) => {
+        let mut out = spec::Matl::new( id, spec::MatlType::Metal );
+        out.prop_rough   = from_val( DEFAULT_ROUGH, m.prop_rough );
+        out.prop_ior     = from_val( DEFAULT_IOR, m.prop_ior );
+        out.prop_metal   = from_val( DEFAULT_METAL, m.prop_metal );
+        out.prop_plastic = m.prop_plastic;
+        out
+      },
+      custom::MaterialType::Glass( m ) => {
+        let mut out = spec::Matl::new( id, spec::MatlType::Glass );
+        out.prop_rough   = from_val( DEFAULT_ROUGH, m.prop_rough );
+        out.prop_ior     = from_val( DEFAULT_IOR, m.prop_ior );
+        out.prop_weight  = from_val( DEFAULT_WEIGHT, m.prop_weight );
+        out.prop_att     = from_val( DEFAULT_ATT, m.prop_att );
+        out.prop_density = from_val( DEFAULT_DENSITY, m.prop_density );
+        out
+      },
+      custom::MaterialType::Emit( m ) => {
+        let mut out = spec::Matl::new( id, spec::MatlType::Emit );
+        out.prop_emit = from_val( DEFAULT_EMIT, m.prop_emit );
+        out.prop_flux = from_val( DEFAULT_FLUX, m.prop_flux );
+        out.prop_ldr  = from_val( DEFAULT_LDR, m.prop_ldr );
+        out
+      },
+      custom::MaterialType::Blend( m ) => {
+        let mut out = spec::Matl::new( id, spec::MatlType::Blend );
+        out.prop_rough   = from_val( DEFAULT_ROUGH, m.prop_rough );
+        out.prop_metal   = from_val( DEFAULT_METAL, m.prop_metal );
+        out.prop_ior     = from_val( DEFAULT_IOR, m.prop_ior );
+        out.prop_alpha   = from_val( DEFAULT_ALPHA, m.prop_alpha );
+        out.prop_plastic = m.prop_plastic;
+        out.prop_att     = from_val( DEFAULT_ATT, m.prop_att );
+        out.prop_density = from_val( DEFAULT_DENSITY, m.prop_density );
+        out
+      },
+      custom::MaterialType::Media( m ) => {
+        let mut out = spec::Matl::new( id, spec::MatlType::Media );
+        out.prop_media_type =
+          from_val( DEFAULT_MEDIA_TYPE, m.prop_media_type ).map( matl_media_type );
+        out.prop_density = from_val( DEFAULT_DENSITY, m.prop_density );
+        out.prop_g       = from_val( DEFAULT_G, m.prop_g );
+        out.prop_ior     = from_val( DEFAULT_IOR, m.prop_ior );
+        out.prop_rough   = from_val( DEFAULT_ROUGH, m.prop_rough );
+        out
+      }
+    };
+  // The chunk always contains `_plastic`, so other material types write the
+  // value that was read
+  if !matches!( material.mat_type, custom::MaterialType::Metal( _ ) | custom::MaterialType::Blend( _ ) ) {
+    out.prop_plastic = material.unknown_props.get( "_plastic" ).is_some_and( |v| v == "1" );
+  }
+  out.unknown_props = material.unknown_props.clone( );
+  match &material.mat_type {
+    custom::MaterialType::Metal( m ) => set_sp_prop( &mut out, m.prop_spec ),
+    custom::MaterialType::Blend( m ) => set_sp_prop( &mut out, m.prop_spec ),
+    _ => { }
+  }
+  out
+}
+
+/// Sets the `_sp` property of the specular value. Files of older versions keep
+/// their `_spec` key when it holds the same value. Otherwise, `_sp` replaces
+/// the (stale) `_spec` key.
+fn set_sp_prop( out: &mut spec::Matl, prop_spec: f32 ) {
+  let legacy = out.unknown_props.get( "_spec" ).and_then( |v| v.parse::< f32 >( ).ok( ) );
+  if legacy != Some( prop_spec ) {
+    out.unknown_props.remove( "_spec" );
+    out.prop_sp = from_val( DEFAULT_SPEC, prop_spec );
+  }
+}
+
+/// Converts the media type of the `MATL` chunk to the custom structure.
+fn media_type( t: spec::MatlMediaType ) -> custom::MediaType {
   match t {
-    custom::MaterialType::Diffuse =>
-      spec::Matl::new( id, spec::MatlType::Diffuse ),
-    custom::MaterialType::Metal( m ) => {
-      let mut out = spec::Matl::new( id, spec::MatlType::Metal );
-      out.prop_rough = from_val( DEFAULT_ROUGH, m.prop_rough );
-      out.prop_ior   = from_val( DEFAULT_IOR, m.prop_ior );
-      out.prop_metal = from_val( DEFAULT_METAL, m.prop_metal );
-      out
-    },
-    custom::MaterialType::Glass( m ) => {
-      let mut out = spec::Matl::new( id, spec::MatlType::Glass );
-      out.prop_rough  = from_val( DEFAULT_ROUGH, m.prop_rough );
-      out.prop_ior    = from_val( DEFAULT_IOR, m.prop_ior );
-      out.prop_weight = from_val( DEFAULT_ALPHA, m.prop_weight );
-      out
-    },
-    custom::MaterialType::Emit( m ) => {
-      let mut out = spec::Matl::new( id, spec::MatlType::Emit );
-      out.prop_emit = from_val( DEFAULT_EMIT, m.prop_emit );
-      out.prop_flux = from_val( DEFAULT_FLUX, m.prop_flux );
-      out.prop_ldr  = from_val( DEFAULT_LDR, m.prop_ldr );
-      out
-    },
-    custom::MaterialType::Blend( m ) => {
-      let mut out = spec::Matl::new( id, spec::MatlType::Blend );
-      out.prop_rough = from_val( DEFAULT_ROUGH, m.prop_rough );
-      out.prop_metal = from_val( DEFAULT_METAL, m.prop_metal );
-      out.prop_ior   = from_val( DEFAULT_IOR, m.prop_ior );
-      out.prop_alpha = from_val( DEFAULT_ALPHA, m.prop_alpha );
-      out
-    },
-    custom::MaterialType::Media =>
-      spec::Matl::new( id, spec::MatlType::Media )
+    spec::MatlMediaType::Absorb  => custom::MediaType::Absorb,
+    spec::MatlMediaType::Scatter => custom::MediaType::Scatter,
+    spec::MatlMediaType::Emit    => custom::MediaType::Emit,
+    spec::MatlMediaType::Sss     => custom::MediaType::Sss
+  }
+}
+
+/// Converts the media type back to the `MATL` chunk.
+fn matl_media_type( t: custom::MediaType ) -> spec::MatlMediaType {
+  match t {
+    custom::MediaType::Absorb  => spec::MatlMediaType::Absorb,
+    custom::MediaType::Scatter => spec::MatlMediaType::Scatter,
+    custom::MediaType::Emit    => spec::MatlMediaType::Emit,
+    custom::MediaType::Sss     => spec::MatlMediaType::Sss
   }
 }
 
@@ -10,10 +10,14 @@
 //! scene graph, and layers.
 
 
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
 use crate::data::spec::MatRowCols;
 
 
 /// Represents a scene described by a `.vox` file.
+#[derive(Debug,Clone)]
 pub struct VoxScene {
   /// The material palette. Every voxel in the scene has an index referencing
   /// into this palette.
@@ -23,7 +27,7 @@ pub struct VoxScene {
   /// index 0 corresponds to palette index 1. This means:
   /// ```
   /// let (x,y,z,i) = model.xyzi[ 42 ];
-  /// let color = scene.palette[ i - 1 ];
+  /// let material = &scene.palette[ i - 1 ];
   /// ```
   pub palette : [Material; 255],
 
@@ -44,6 +48,7 @@ pub struct VoxScene {
 }
 
 /// A voxel model
+#[derive(Debug,Clone)]
 pub struct Model {
   /// Size of the model `(x_size, y_size, z_size)`. _z_ is the gravity
   /// direction.
@@ -53,7 +58,7 @@ pub struct Model {
   /// `VoxFile#palette`, with a negative offset of 1:
   /// ```
   /// let (x,y,z,i) = model.xyzi[ 42 ];
-  /// let color = vox_file.palette[ i - 1 ];
+  /// let material = &vox_file.palette[ i - 1 ];
   /// ```
   /// A color index of 0 is invalid.
   pub xyzi : Vec< (u8, u8, u8, u8) >
@@ -62,6 +67,7 @@ pub struct Model {
 /// A layer in the scene.
 /// 
 /// MagicaVoxel supports exactly 8 layers.
+#[derive(Debug,Clone)]
 pub struct Layer {
   pub name      : String,
   pub is_hidden : bool
@@ -70,7 +76,11 @@ pub struct Layer {
 /// A node in the voxel scene graph.
 /// 
 /// This condenses the nTRN and nSHP/nGRP nodes together.
+#[derive(Debug,Clone)]
 pub struct SceneNode {
+  /// The name of the node, as shown in MagicaVoxel's outliner. (Stored as the
+  /// `_name` attribute of the nTRN chunk)
+  pub name        : Option< String >,
   pub rotation    : MatRowCols,
   pub translation : (i32,i32,i32),
   pub layer_id    : Option< u32 >,
@@ -79,6 +89,7 @@ pub struct SceneNode {
 
 /// An enum for the different types of nodes in the scene graph. (Used by
 /// [`SceneNode`])
+#[derive(Debug,Clone)]
 pub enum NodeType {
   /// A group of nodes in the scene graph.
   Group( Vec< SceneNode > ),
@@ -88,56 +99,83 @@ pub enum NodeType {
   Shape( u32 )
 }
 
+/// A model placed in the world by the scene graph. (See
+/// [`VoxScene::instances`])
+/// 
+/// The transformations of all [`SceneNode`]s on the path from the root to the
+/// shape node are composed into a single rotation and translation. A point
+/// `p` in the model maps to the world point `rotation * (p - pivot) +
+/// translation`, where the pivot is the center of the model (see
+/// [`Model::pivot`]).
+#[derive(Debug,Copy,Clone)]
+pub struct Instance {
+  /// The index of the placed model in [`VoxScene::models`]
+  pub model_id    : u32,
+  pub rotation    : MatRowCols,
+  pub translation : (i32,i32,i32),
+  /// The layer of the innermost node on the path that has a layer
+  pub layer_id    : Option< u32 >
+}
+
 /// A single material in the palette.
 /// 
 /// This representation roughly abstracts over both the `MATT` and `MATL` chunks.
-#[derive(Debug,Copy,Clone)]
+/// Construct it with [`Material::new`].
+#[derive(Debug,Clone,PartialEq)]
 pub struct Material {
-  pub rgba     : (u8, u8, u8, u8),
-  pub mat_type : MaterialType
+  pub rgba          : (u8, u8, u8, u8),
+  pub mat_type      : MaterialType,
+  /// The `MATL` properties that are not represented by the material type, by
+  /// their keys. (See [`Material::unknown_props`])
+  pub(crate) unknown_props : HashMap< String, String >
 }
 
 /// An enum for the different types of materials. (Used by [`Material`])
 /// 
 /// Note that every material still has a color (which is contained in
 /// `Material`).
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub enum MaterialType {
   Diffuse,
   Metal( MetalMaterial ),
   Glass( GlassMaterial ),
   Emit( EmitMaterial ),
   Blend( BlendMaterial ),
-  /// Unsure about these. In files, stores: _d _ior _rough
-  Media
+  Media( MediaMaterial )
 }
 
 /// A metallic material
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct MetalMaterial {
-  pub prop_rough  : f32,
+  pub prop_rough   : f32,
   /// Index-of-Refraction.
   /// 
   /// WARNING: The offset from 1.0 is stored. So, when the value is 0.14, the
   ///   actual IOR is 1.14.
-  pub prop_ior    : f32,
-  pub prop_metal  : f32
+  pub prop_ior     : f32,
+  pub prop_metal   : f32,
+  /// Specular. (Stored as `_sp`, or as `_spec` by older versions)
+  pub prop_spec    : f32,
+  pub prop_plastic : bool
 }
 
 /// A semi-transparent material.
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct GlassMaterial {
-  pub prop_rough  : f32,
+  pub prop_rough   : f32,
   /// Index-of-Refraction.
   /// 
   /// WARNING: The offset from 1.0 is stored. So, when the value is 0.14, the
   ///   actual IOR is 1.14.
-  pub prop_ior    : f32,
-  pub prop_weight : f32
+  pub prop_ior     : f32,
+  pub prop_weight  : f32,
+  /// Attenuation
+  pub prop_att     : f32,
+  pub prop_density : f32
 }
 
 /// An illuminative material.
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct EmitMaterial {
   pub prop_emit : f32,
   pub prop_flux : u32, // power slider
@@ -145,14 +183,189 @@ pub struct EmitMaterial {
 }
 
 /// A material that blends between metallic and transparent.
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct BlendMaterial {
-  pub prop_rough : f32,
-  pub prop_metal : f32,
+  pub prop_rough   : f32,
+  pub prop_metal   : f32,
+  /// Index-of-Refraction.
+  /// 
+  /// WARNING: The offset from 1.0 is stored. So, when the value is 0.14, the
+  ///   actual IOR is 1.14.
+  pub prop_ior     : f32,
+  pub prop_alpha   : f32,
+  /// Specular. (Stored as `_sp`, or as `_spec` by older versions)
+  pub prop_spec    : f32,
+  pub prop_plastic : bool,
+  /// Attenuation
+  pub prop_att     : f32,
+  pub prop_density : f32
+}
+
+/// A participating medium, such as a cloud. The voxels of media are rendered
+/// as a volume, instead of as solid cubes.
+#[derive(Debug,Copy,Clone,PartialEq)]
+pub struct MediaMaterial {
+  pub prop_media_type : MediaType,
+  /// How dense the medium is. Denser media absorb or scatter more light.
+  pub prop_density    : f32,
+  /// The phase; The anisotropy of the scattering, from -1.0 (backward) to 1.0
+  /// (forward). At 0.0, light scatters equally in all directions.
+  pub prop_g          : f32,
   /// Index-of-Refraction.
   /// 
   /// WARNING: The offset from 1.0 is stored. So, when the value is 0.14, the
   ///   actual IOR is 1.14.
-  pub prop_ior   : f32,
-  pub prop_alpha : f32
+  pub prop_ior        : f32,
+  pub prop_rough      : f32
+}
+
+/// The way a medium interacts with light. (Used by [`MediaMaterial`])
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum MediaType {
+  /// Absorbs light, like smoke.
+  Absorb,
+  /// Scatters light, like clouds.
+  Scatter,
+  /// Emits light, like fire.
+  Emit,
+  /// Subsurface scattering, like wax or skin.
+  Sss
+}
+
+
+impl VoxScene {
+  /// Flattens the scene graph into the models it places in the world. The
+  /// instances are ordered depth-first, as they occur in the scene graph.
+  pub fn instances( &self ) -> Vec< Instance > {
+    let mut dst = Vec::new( );
+    let root =
+      Instance {
+        model_id:    0,
+        rotation:    MatRowCols::identity( ),
+        translation: (0,0,0),
+        layer_id:    None
+      };
+    collect_instances( &mut dst, root, &self.graph );
+    dst
+  }
+
+  /// Returns `true` iff the instance is on a hidden layer.
+  pub fn is_hidden( &self, instance: &Instance ) -> bool {
+    instance.layer_id
+      .and_then( |l| self.layers.get( l as usize ) )
+      .is_some_and( |l| l.is_hidden )
+  }
+}
+
+impl Model {
+  /// Returns the center of the model, around which the scene graph rotates
+  /// the model. For odd sizes, the center voxel is chosen. For even sizes, the
+  /// voxel just above the center is chosen.
+  pub fn pivot( &self ) -> (i32,i32,i32) {
+    let (x_size, y_size, z_size) = self.size;
+    ( ( x_size / 2 ) as i32, ( y_size / 2 ) as i32, ( z_size / 2 ) as i32 )
+  }
+}
+
+impl Instance {
+  /// Maps a point in the model to its world position.
+  pub fn point_to_world( &self, model: &Model, (x,y,z): (f32,f32,f32) ) -> (f32,f32,f32) {
+    let (px, py, pz) = model.pivot( );
+    let (tx, ty, tz) = self.translation;
+    let (rx, ry, rz) =
+      self.rotation.apply_to_f32( (x - px as f32, y - py as f32, z - pz as f32) );
+    (rx + tx as f32, ry + ty as f32, rz + tz as f32)
+  }
+
+  /// Maps a world position to its point in the model. This is the inverse of
+  /// [`Instance::point_to_world`].
+  pub fn point_to_local( &self, model: &Model, (x,y,z): (f32,f32,f32) ) -> (f32,f32,f32) {
+    let (px, py, pz) = model.pivot( );
+    let (tx, ty, tz) = self.translation;
+    let (rx, ry, rz) =
+      self.rotation.inverse( ).apply_to_f32( (x - tx as f32, y - ty as f32, z - tz as f32) );
+    (rx + px as f32, ry + py as f32, rz + pz as f32)
+  }
+
+  /// Maps the voxel `(x,y,z)` in the model to the voxel it occupies in the
+  /// world.
+  /// 
+  /// Voxel `(x,y,z)` is the unit cube with its lowest corner at `(x,y,z)`.
+  /// When the rotation mirrors an axis, the lowest corner of the cube in the
+  /// world is thus the image of a _different_ corner of the cube in the model.
+  pub fn voxel_to_world( &self, model: &Model, (x,y,z): (i32,i32,i32) ) -> (i32,i32,i32) {
+    let (px, py, pz) = model.pivot( );
+    let (tx, ty, tz) = self.translation;
+    let (rx, ry, rz) = self.rotation.apply_to( (x - px, y - py, z - pz) );
+    let (nx, ny, nz) = mirror_offset( self.rotation );
+    (rx + tx + nx, ry + ty + ny, rz + tz + nz)
+  }
+
+  /// Maps a voxel in the world to its voxel in the model. This is the inverse
+  /// of [`Instance::voxel_to_world`]. Note that the resulting voxel may be
+  /// outside the bounds of the model.
+  pub fn voxel_to_local( &self, model: &Model, (x,y,z): (i32,i32,i32) ) -> (i32,i32,i32) {
+    let (px, py, pz) = model.pivot( );
+    let (tx, ty, tz) = self.translation;
+    let (nx, ny, nz) = mirror_offset( self.rotation );
+    let (rx, ry, rz) =
+      self.rotation.inverse( ).apply_to( (x - tx - nx, y - ty - ny, z - tz - nz) );
+    (rx + px, ry + py, rz + pz)
+  }
+}
+
+impl Material {
+  /// Constructs a material of the given color and type, without other
+  /// properties.
+  pub fn new( rgba: (u8, u8, u8, u8), mat_type: MaterialType ) -> Material {
+    Material { rgba, mat_type, unknown_props: HashMap::new( ) }
+  }
+
+  /// Returns the `MATL` properties that are not represented by the material
+  /// type, by their keys. Besides unknown properties, these include the
+  /// properties of other material types, and the older `_spec` key (unless
+  /// `_sp` supersedes it). These are written back unchanged, so materials of
+  /// newer MagicaVoxel versions survive a round trip.
+  pub fn unknown_props( &self ) -> &HashMap< String, String > {
+    &self.unknown_props
+  }
+
+  /// Returns the `MATL` properties that are not represented by the material
+  /// type, for modification. (See [`Material::unknown_props`])
+  pub fn unknown_props_mut( &mut self ) -> &mut HashMap< String, String > {
+    &mut self.unknown_props
+  }
+}
+
+/// Traverses the scene graph downward, and appends the encountered shape
+/// nodes to `dst`. `parent` contains the composed transformation of the
+/// ancestors of `node`.
+fn collect_instances( dst: &mut Vec< Instance >, parent: Instance, node: &SceneNode ) {
+  let (tx, ty, tz) = parent.rotation.apply_to( node.translation );
+  let (px, py, pz) = parent.translation;
+
+  let current =
+    Instance {
+      model_id:    parent.model_id,
+      rotation:    parent.rotation.compose( &node.rotation ),
+      translation: (px + tx, py + ty, pz + tz),
+      layer_id:    node.layer_id.or( parent.layer_id )
+    };
+
+  match &node.node_type {
+    NodeType::Group( children ) =>
+      for c in children {
+        collect_instances( dst, current, c );
+      },
+    NodeType::Shape( model_id ) =>
+      dst.push( Instance { model_id: *model_id, ..current } )
+  }
+}
+
+/// Returns the offset of the lowest corner of a voxel cube after rotation.
+/// Mirrored axes shift the lowest corner by `-1`; e.g., the cube `[0,1]` maps to
+/// `[-1,0]`.
+pub(crate) fn mirror_offset( rotation: MatRowCols ) -> (i32,i32,i32) {
+  let (x, y, z) = rotation.apply_to( (1,1,1) );
+  ( x.min( 0 ), y.min( 0 ), z.min( 0 ) )
 }
@@ -239,6 +239,11 @@ pub struct ShapeNode< 'a > {
 ///         (_metal   : float)
 ///         (_plastic)
 /// ```
+///
+/// MagicaVoxel writes some properties which the specification lacks, such as
+/// `_sp` (the specular value, which replaced `_spec`), and `_media_type` and
+/// `_g` (the phase) of media. Properties that are not known at all are kept in
+/// [`Matl::unknown_props`], so they are written back unchanged.
 #[derive(Debug)]
 pub struct Matl {
   pub id           : u8,
@@ -254,7 +259,12 @@ pub struct Matl {
   pub prop_emit    : Option< f32 >,
   pub prop_ldr     : Option< f32 >,
   pub prop_metal   : Option< f32 >,
-  pub prop_plastic : bool
+  pub prop_plastic : bool,
+  pub prop_sp      : Option< f32 >,
+  pub prop_g       : Option< f32 >,
+  pub prop_media_type : Option< MatlMediaType >,
+  /// The remaining properties, by their keys
+  pub unknown_props : HashMap< String, String >
 }
 
 impl Matl {
@@ -275,7 +285,11 @@ impl Matl {
       prop_emit: None,
       prop_ldr: None,
       prop_metal: None,
-      prop_plastic: false
+      prop_plastic: false,
+      prop_sp: None,
+      prop_g: None,
+      prop_media_type: None,
+      unknown_props: HashMap::new( )
     }
   }
 }
@@ -288,7 +302,18 @@ pub enum MatlType {
   Glass,
   Emit,
   Blend, // blends between glass and metal
-  Media // clouds
+  Media // clouds, smoke, and other volumes
+}
+
+/// The kind of medium of a `MATL` chunk with type [`MatlType::Media`]. (See
+/// [`Matl`])
+#[derive(Debug,Copy,Clone,PartialEq,Eq)]
+pub enum MatlMediaType {
+  Absorb,
+  Scatter,
+  Emit,
+  /// Subsurface scattering
+  Sss
 }
 
 
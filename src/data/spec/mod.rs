@@ -9,7 +9,7 @@ mod chunks;
 mod default_palette;
 mod special;
 
-pub use self::special::{IsNeg, MatRowCols};
+pub use self::special::{Axis, IsNeg, MatRowCols};
 pub use self::default_palette::DEFAULT_PALETTE;
 pub use self::chunks::{Chunk, RawChunk, Matt, MattType, TransformNode,
-  GroupNode, ShapeNode, Matl, MatlType, Layr};
+  GroupNode, ShapeNode, Matl, MatlType, MatlMediaType, Layr};
@@ -1,6 +1,14 @@
 //! Data structures for the special (non-chunk) binary structures.
 
 
+/// A coordinate axis. (Used by [`MatRowCols`])
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Axis {
+  X,
+  Y,
+  Z
+}
+
 /// Boolean alias used by [`MatRowCols`]. True iff an entry is `-1`; `1`
 /// otherwise.
 pub type IsNeg = bool;
@@ -25,7 +33,12 @@ pub type IsNeg = bool;
 ///  0  0 -1   # by Three and true
 ///  1  0  0   # by One and false
 /// ```
-#[derive(Debug, Clone, Copy)]
+/// 
+/// The transformations form a group of 48 elements (see [`MatRowCols::all`]):
+/// 24 proper rotations and 24 mirrored rotations. Transformations are
+/// combined with [`MatRowCols::compose`] and undone with
+/// [`MatRowCols::inverse`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum MatRowCols {
   /// `[x,0,0] [0,x,0] [0,0,x]`
   OneTwoThree( IsNeg, IsNeg, IsNeg ),
@@ -71,6 +84,156 @@ impl MatRowCols {
     )
   }
 
+  /// Constructs the transformation from a row-major matrix.
+  /// 
+  /// Returns `None` if the matrix is not a rotation/mirror matrix; i.e., every
+  /// row and every column must contain exactly one non-zero entry, which is
+  /// either `1` or `-1`.
+  /// 
+  /// E.g., `[0,1,0, 0,0,-1, 1,0,0]` becomes `TwoThreeOne(false, true, false)`.
+  pub fn from_matrix( m: [i32; 9] ) -> Option< MatRowCols > {
+    let mut cols = [0; 3];
+    let mut is_neg = [false; 3];
+
+    for row in 0..3 {
+      let entries = &m[ row * 3 .. row * 3 + 3 ];
+      if entries.iter( ).filter( |v| **v != 0 ).count( ) != 1 {
+        return None;
+      }
+      let col = entries.iter( ).position( |v| *v != 0 )?;
+      match entries[ col ] {
+        1  => { },
+        -1 => { is_neg[ row ] = true; },
+        _  => { return None; }
+      }
+      cols[ row ] = col;
+    }
+
+    let (a, b, c) = (is_neg[ 0 ], is_neg[ 1 ], is_neg[ 2 ]);
+    match cols {
+      [0, 1, 2] => Some( MatRowCols::OneTwoThree( a, b, c ) ),
+      [0, 2, 1] => Some( MatRowCols::OneThreeTwo( a, b, c ) ),
+      [1, 0, 2] => Some( MatRowCols::TwoOneThree( a, b, c ) ),
+      [1, 2, 0] => Some( MatRowCols::TwoThreeOne( a, b, c ) ),
+      [2, 0, 1] => Some( MatRowCols::ThreeOneTwo( a, b, c ) ),
+      [2, 1, 0] => Some( MatRowCols::ThreeTwoOne( a, b, c ) ),
+      _ => None // Two rows share the same column
+    }
+  }
+
+  /// Composes the transformations, where `other` is applied _first_. So,
+  /// `a.compose( &b ).apply_to( v ) == a.apply_to( b.apply_to( v ) )`.
+  pub fn compose( &self, other: &MatRowCols ) -> MatRowCols {
+    let a = self.matrix( );
+    let b = other.matrix( );
+    let mut out = [0; 9];
+
+    for row in 0..3 {
+      for col in 0..3 {
+        out[ row * 3 + col ] =
+          (0..3).map( |k| a[ row * 3 + k ] * b[ k * 3 + col ] ).sum( );
+      }
+    }
+
+    // The product of two rotation/mirror matrices is again such a matrix
+    MatRowCols::from_matrix( out ).unwrap( )
+  }
+
+  /// Constructs the rotation by the given number of quarter turns (90 degrees)
+  /// around the axis. Positive turns rotate counter-clockwise when looking
+  /// from the positive side of the axis towards the origin (i.e., by the
+  /// right-hand rule). Negative turns rotate clockwise.
+  /// 
+  /// E.g., a single quarter turn around _z_ maps the _x_ axis onto the _y_
+  /// axis.
+  pub fn from_axis_turns( axis: Axis, quarter_turns: i32 ) -> MatRowCols {
+    let turn =
+      match axis {
+        Axis::X => MatRowCols::OneThreeTwo( false, true, false ),
+        Axis::Y => MatRowCols::ThreeTwoOne( false, false, true ),
+        Axis::Z => MatRowCols::TwoOneThree( true, false, false )
+      };
+
+    let mut out = MatRowCols::identity( );
+    for _i in 0..quarter_turns.rem_euclid( 4 ) {
+      out = turn.compose( &out );
+    }
+    out
+  }
+
+  /// Constructs the transformation which mirrors the given axis. (i.e., it
+  /// negates the coordinate along that axis)
+  pub fn mirror( axis: Axis ) -> MatRowCols {
+    match axis {
+      Axis::X => MatRowCols::OneTwoThree( true, false, false ),
+      Axis::Y => MatRowCols::OneTwoThree( false, true, false ),
+      Axis::Z => MatRowCols::OneTwoThree( false, false, true )
+    }
+  }
+
+  /// Returns all 48 rotation/mirror transformations.
+  /// 
+  /// The 24 proper rotations (see [`MatRowCols::is_rotation`]) are the
+  /// orientations of a cube. The other 24 transformations additionally mirror.
+  pub fn all( ) -> [MatRowCols; 48] {
+    let mut out = [MatRowCols::identity( ); 48];
+
+    for (i, m) in out.iter_mut( ).enumerate( ) {
+      let (a, b, c) = ( i & 1 != 0, i & 2 != 0, i & 4 != 0 );
+      *m =
+        match i / 8 {
+          0 => MatRowCols::OneTwoThree( a, b, c ),
+          1 => MatRowCols::OneThreeTwo( a, b, c ),
+          2 => MatRowCols::TwoOneThree( a, b, c ),
+          3 => MatRowCols::TwoThreeOne( a, b, c ),
+          4 => MatRowCols::ThreeOneTwo( a, b, c ),
+          _ => MatRowCols::ThreeTwoOne( a, b, c )
+        };
+    }
+
+    out
+  }
+
+  /// Returns the determinant of the matrix, which is either `1` or `-1`.
+  pub fn determinant( &self ) -> i32 {
+    let m = self.matrix( );
+    m[0] * ( m[4] * m[8] - m[5] * m[7] )
+      - m[1] * ( m[3] * m[8] - m[5] * m[6] )
+      + m[2] * ( m[3] * m[7] - m[4] * m[6] )
+  }
+
+  /// Returns `true` iff the transformation is a proper rotation; i.e., it
+  /// preserves handedness (its determinant is `1`).
+  pub fn is_rotation( &self ) -> bool {
+    self.determinant( ) == 1
+  }
+
+  /// Returns `true` iff the transformation mirrors; i.e., it reverses
+  /// handedness (its determinant is `-1`). Mirroring transformations turn
+  /// the winding order of faces around.
+  pub fn is_mirror( &self ) -> bool {
+    self.determinant( ) == -1
+  }
+
+  /// Returns the inverse transformation.
+  /// 
+  /// As rotation/mirror matrices are orthogonal, the inverse is the transpose.
+  pub fn inverse( &self ) -> MatRowCols {
+    let m = self.matrix( );
+    MatRowCols::from_matrix( [m[0], m[3], m[6], m[1], m[4], m[7], m[2], m[5], m[8]] ).unwrap( )
+  }
+
+  /// Applies the transformation to the given (non-integer) point.
+  pub fn apply_to_f32( &self, (x,y,z): (f32,f32,f32) ) -> (f32,f32,f32) {
+    let mat = self.matrix( );
+    let m = |i: usize| mat[ i ] as f32;
+
+    ( m(0)*x + m(1)*y + m(2)*z
+    , m(3)*x + m(4)*y + m(5)*z
+    , m(6)*x + m(7)*y + m(8)*z
+    )
+  }
+
   /// Returns `true` iff the transformation is the identity transformation.
   /// 
   /// The identity transformation maps any 3d vector to itself.
@@ -0,0 +1,496 @@
+//! Export to binary glTF 2.0 (`.glb`) files.
+//!
+//! The scene graph is preserved: every [`SceneNode`] becomes a glTF node, with
+//! its name, and with its rotation and translation as the node's matrix. Every
+//! placed [`Model`] becomes a single glTF mesh, which is shared by all nodes
+//! that place it. Shape nodes on hidden layers are omitted.
+//!
+//! Every used palette index becomes a separate material, with the properties
+//! of its [`MaterialType`] mapped onto the metallic-roughness model and its
+//! extensions:
+//!
+//! * `Metal` - `metallicFactor`, `roughnessFactor`, and `KHR_materials_ior`.
+//! * `Glass` - `KHR_materials_transmission`, `roughnessFactor`, and
+//!   `KHR_materials_ior`.
+//! * `Emit` - `emissiveFactor`, and `KHR_materials_emissive_strength`.
+//! * `Blend` - As metal, with transmission.
+//!
+//! # Example: Export a scene
+//!
+//! ```no_run
+//! use vox_parser::formats::gltf::{self, GltfOptions};
+//!
+//! let content = std::fs::read( "input.vox" ).unwrap( );
+//! let scene = vox_parser::parse::file_custom( &content ).unwrap( );
+//!
+//! let glb = gltf::export( &scene, &GltfOptions::default( ) );
+//! std::fs::write( "output.glb", glb ).unwrap( );
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::{Material, MaterialType, Model, NodeType, SceneNode, VoxScene};
+use crate::formats::{Pivot, pivot_point, to_output_axes, visible_instances};
+use crate::mesh::{self, Mesh};
+
+
+/// Options for [`export`].
+#[derive(Debug, Clone)]
+pub struct GltfOptions {
+  /// The size of a single voxel in meters.
+  pub scale          : f32,
+  /// The point of the scene which is placed at the origin.
+  pub pivot          : Pivot,
+  /// When set, the _y_ axis points up in the output (as required by the glTF
+  /// specification). Otherwise, the _z_ axis points up (as in `.vox` files).
+  pub y_up           : bool,
+  /// When set, faces covered by the voxels of other instances are omitted.
+  /// (See [`greedy_instances`](crate::mesh::greedy_instances)) Every shape
+  /// node then has its own mesh, as the meshes can no longer be shared.
+  pub cull_instances : bool
+}
+
+impl Default for GltfOptions {
+  fn default( ) -> GltfOptions {
+    GltfOptions { scale: 1.0, pivot: Pivot::Origin, y_up: true, cull_instances: false }
+  }
+}
+
+/// Internal. The glTF document under construction.
+#[derive(Default)]
+struct Document {
+  /// The JSON objects of the respective arrays
+  nodes        : Vec< String >,
+  meshes       : Vec< String >,
+  materials    : Vec< String >,
+  accessors    : Vec< String >,
+  buffer_views : Vec< String >,
+  /// The binary buffer, which is stored in the `BIN` chunk
+  buffer       : Vec< u8 >,
+  /// The index of the glTF mesh of every model. `None` if the model has no
+  /// faces.
+  model_meshes : HashMap< u32, Option< usize > >,
+  /// The mesh of every instance, when culling between instances. Otherwise,
+  /// this is empty.
+  inst_meshes  : Vec< Mesh >,
+  /// The index in [`VoxScene::instances`] of the next shape node
+  next_inst    : usize,
+  /// The index of the glTF material of every palette index
+  material_ids : HashMap< u8, usize >,
+  extensions   : Vec< &'static str >
+}
+
+/// Exports the scene as a binary glTF (`.glb`) file.
+pub fn export( scene: &VoxScene, options: &GltfOptions ) -> Vec< u8 > {
+  let instances = visible_instances( scene );
+  let pivot = pivot_point( scene, &instances, options.pivot );
+
+  let mut doc = Document::default( );
+  if options.cull_instances {
+    doc.inst_meshes = mesh::greedy_instances( scene );
+  }
+
+  // The root node converts from the `.vox` coordinate system to the output,
+  // as `scale * A * (p - pivot)`. Its column-major matrix has the axes as
+  // columns.
+  let s = options.scale;
+  let [ax, ay, az] =
+    [ [1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0] ]
+      .map( |a| to_output_axes( a, options.y_up ).map( |c| c * s ) );
+  let t = [0, 1, 2].map( |i| -( ax[ i ] * pivot[ 0 ] + ay[ i ] * pivot[ 1 ] + az[ i ] * pivot[ 2 ] ) );
+  let root_matrix =
+    [ ax[ 0 ], ax[ 1 ], ax[ 2 ], 0.0
+    , ay[ 0 ], ay[ 1 ], ay[ 2 ], 0.0
+    , az[ 0 ], az[ 1 ], az[ 2 ], 0.0
+    , t[ 0 ],  t[ 1 ],  t[ 2 ],  1.0
+    ];
+
+  doc.nodes.push( String::new( ) );
+  let graph_root = add_node( &mut doc, scene, &scene.graph, None );
+  doc.nodes[ 0 ] =
+    format!(
+      "{{\"name\":\"root\",\"matrix\":{},\"children\":[{}]}}",
+      json_floats( &root_matrix ), graph_root
+    );
+
+  to_glb( &doc )
+}
+
+/// Appends the node (and its descendants) to the document, and returns its
+/// node index. `parent_layer` is the layer inherited from the ancestors.
+fn add_node(
+    doc: &mut Document,
+    scene: &VoxScene,
+    node: &SceneNode,
+    parent_layer: Option< u32 > ) -> usize {
+
+  let node_id = doc.nodes.len( );
+  doc.nodes.push( String::new( ) );
+
+  let layer = node.layer_id.or( parent_layer );
+  let mut json = String::from( "{" );
+
+  if let Some( name ) = &node.name {
+    json.push_str( &format!( "\"name\":{},", json_string( name ) ) );
+  }
+
+  if !node.rotation.is_identity( ) || node.translation != (0,0,0) {
+    let m = node.rotation.matrix( );
+    let (x, y, z) = node.translation;
+    let column_major =
+      [ m[ 0 ], m[ 3 ], m[ 6 ], 0
+      , m[ 1 ], m[ 4 ], m[ 7 ], 0
+      , m[ 2 ], m[ 5 ], m[ 8 ], 0
+      , x,      y,      z,      1
+      ].map( |v| v as f32 );
+    json.push_str( &format!( "\"matrix\":{},", json_floats( &column_major ) ) );
+  }
+
+  match &node.node_type {
+    NodeType::Group( children ) => {
+      let child_ids: Vec< String > =
+        children.iter( ).map( |c| add_node( doc, scene, c, layer ).to_string( ) ).collect( );
+      if !child_ids.is_empty( ) {
+        json.push_str( &format!( "\"children\":[{}],", child_ids.join( "," ) ) );
+      }
+    },
+    NodeType::Shape( model_id ) => {
+      let is_hidden =
+        layer.and_then( |l| scene.layers.get( l as usize ) ).is_some_and( |l| l.is_hidden );
+      let instance_id = doc.next_inst;
+      doc.next_inst += 1;
+
+      if !is_hidden {
+        if let Some( mesh_id ) = shape_mesh( doc, scene, *model_id, instance_id ) {
+          json.push_str( &format!( "\"mesh\":{},", mesh_id ) );
+        }
+      }
+    }
+  }
+
+  // Remove the trailing comma (if any)
+  if json.ends_with( ',' ) {
+    json.pop( );
+  }
+  json.push( '}' );
+  doc.nodes[ node_id ] = json;
+  node_id
+}
+
+/// Returns the glTF mesh of the shape node, which places the model as the
+/// given instance. Returns `None` if the mesh has no faces, as glTF meshes
+/// cannot be empty.
+///
+/// Without culling between instances, the mesh of the model is shared, and
+/// added upon first use.
+fn shape_mesh( doc: &mut Document, scene: &VoxScene, model_id: u32, instance_id: usize ) -> Option< usize > {
+  let model = &scene.models[ model_id as usize ];
+
+  if !doc.inst_meshes.is_empty( ) {
+    let mesh = std::mem::take( &mut doc.inst_meshes[ instance_id ] );
+    return
+      if mesh.indices.is_empty( ) {
+        None
+      } else {
+        Some( add_mesh( doc, scene, model, &mesh, model_id ) )
+      };
+  }
+
+  if let Some( mesh_id ) = doc.model_meshes.get( &model_id ) {
+    return *mesh_id;
+  }
+
+  let mesh = mesh::greedy( model );
+
+  let mesh_id =
+    if mesh.indices.is_empty( ) {
+      None
+    } else {
+      Some( add_mesh( doc, scene, model, &mesh, model_id ) )
+    };
+  doc.model_meshes.insert( model_id, mesh_id );
+  mesh_id
+}
+
+/// Appends the mesh of the model to the document, with a primitive for every
+/// palette index. Returns its mesh index.
+fn add_mesh( doc: &mut Document, scene: &VoxScene, model: &Model, mesh: &Mesh, model_id: u32 ) -> usize {
+  // The scene graph rotates models around their pivot
+  let (px, py, pz) = model.pivot( );
+  let positions: Vec< [f32; 3] > =
+    mesh.positions.iter( )
+      .map( |p| [p[ 0 ] - px as f32, p[ 1 ] - py as f32, p[ 2 ] - pz as f32] )
+      .collect( );
+
+  let mut min = [f32::INFINITY; 3];
+  let mut max = [f32::NEG_INFINITY; 3];
+  for p in &positions {
+    for axis in 0..3 {
+      min[ axis ] = min[ axis ].min( p[ axis ] );
+      max[ axis ] = max[ axis ].max( p[ axis ] );
+    }
+  }
+
+  let position_accessor =
+    add_accessor(
+      doc, &floats_to_bytes( positions.iter( ).flatten( ) ), 34962,
+      &format!(
+        "\"componentType\":5126,\"count\":{},\"type\":\"VEC3\",\"min\":{},\"max\":{}",
+        positions.len( ), json_floats( &min ), json_floats( &max )
+      )
+    );
+  let normal_accessor =
+    add_accessor(
+      doc, &floats_to_bytes( mesh.normals.iter( ).flatten( ) ), 34962,
+      &format!( "\"componentType\":5126,\"count\":{},\"type\":\"VEC3\"", mesh.normals.len( ) )
+    );
+
+  // Every triangle belongs to a single palette index
+  let mut triangles: HashMap< u8, Vec< u32 > > = HashMap::new( );
+  for t in mesh.indices.chunks( 3 ) {
+    triangles.entry( mesh.palette_indices[ t[ 0 ] as usize ] ).or_default( ).extend( t );
+  }
+  let mut palette_indices: Vec< u8 > = triangles.keys( ).copied( ).collect( );
+  palette_indices.sort( );
+
+  let mut primitives = Vec::new( );
+  for palette_index in palette_indices {
+    let indices = &triangles[ &palette_index ];
+    let bytes: Vec< u8 > = indices.iter( ).flat_map( |i| i.to_le_bytes( ) ).collect( );
+    let index_accessor =
+      add_accessor(
+        doc, &bytes, 34963,
+        &format!( "\"componentType\":5125,\"count\":{},\"type\":\"SCALAR\"", indices.len( ) )
+      );
+    let material_id = palette_material( doc, scene, palette_index );
+
+    primitives.push(
+      format!(
+        "{{\"attributes\":{{\"POSITION\":{},\"NORMAL\":{}}},\"indices\":{},\"material\":{}}}",
+        position_accessor, normal_accessor, index_accessor, material_id
+      )
+    );
+  }
+
+  doc.meshes.push(
+    format!( "{{\"name\":\"model_{}\",\"primitives\":[{}]}}", model_id, primitives.join( "," ) )
+  );
+  doc.meshes.len( ) - 1
+}
+
+/// Appends the data to the buffer, with its own buffer view and accessor.
+/// Returns the accessor index. `properties` contains the JSON properties of
+/// the accessor, other than its buffer view.
+fn add_accessor( doc: &mut Document, data: &[u8], target: u32, properties: &str ) -> usize {
+  let offset = doc.buffer.len( );
+  doc.buffer.extend( data );
+  // Keep all views 4-byte aligned
+  while doc.buffer.len( ) % 4 != 0 {
+    doc.buffer.push( 0 );
+  }
+
+  doc.buffer_views.push(
+    format!(
+      "{{\"buffer\":0,\"byteOffset\":{},\"byteLength\":{},\"target\":{}}}",
+      offset, data.len( ), target
+    )
+  );
+  doc.accessors.push( format!( "{{\"bufferView\":{},{}}}", doc.buffer_views.len( ) - 1, properties ) );
+  doc.accessors.len( ) - 1
+}
+
+/// Returns the glTF material of the palette index, which is added upon first
+/// use.
+fn palette_material( doc: &mut Document, scene: &VoxScene, palette_index: u8 ) -> usize {
+  if let Some( id ) = doc.material_ids.get( &palette_index ) {
+    return *id;
+  }
+
+  let m = &scene.palette[ palette_index as usize - 1 ];
+  let json = material_json( doc, palette_index, m );
+  doc.materials.push( json );
+  doc.material_ids.insert( palette_index, doc.materials.len( ) - 1 );
+  doc.materials.len( ) - 1
+}
+
+/// Returns the JSON of the glTF material for the palette material. The used
+/// extensions are registered with the document.
+fn material_json( doc: &mut Document, palette_index: u8, m: &Material ) -> String {
+  let (r, g, b, a) = m.rgba;
+  // Palette colors are in sRGB, whereas glTF factors are linear
+  let [r, g, b] = [r, g, b].map( srgb_to_linear );
+  let alpha = a as f32 / 255.0;
+
+  let (metallic, roughness) =
+    match m.mat_type {
+      MaterialType::Metal( p ) => (p.prop_metal, p.prop_rough),
+      MaterialType::Glass( p ) => (0.0, p.prop_rough),
+      MaterialType::Blend( p ) => (p.prop_metal, p.prop_rough),
+      _                        => (0.0, 1.0)
+    };
+
+  let mut json =
+    format!(
+      "{{\"name\":\"palette_{}\",\"pbrMetallicRoughness\":{{\"baseColorFactor\":{},\"metallicFactor\":{},\"roughnessFactor\":{}}}",
+      palette_index, json_floats( &[r, g, b, alpha] ), json_float( metallic ), json_float( roughness )
+    );
+  if a < 255 {
+    json.push_str( ",\"alphaMode\":\"BLEND\"" );
+  }
+
+  let mut extensions = Vec::new( );
+  match m.mat_type {
+    MaterialType::Metal( p ) => {
+      extensions.push( ior_extension( doc, p.prop_ior ) );
+    },
+    MaterialType::Glass( p ) => {
+      extensions.push( transmission_extension( doc, p.prop_weight ) );
+      extensions.push( ior_extension( doc, p.prop_ior ) );
+    },
+    MaterialType::Blend( p ) => {
+      extensions.push( transmission_extension( doc, p.prop_alpha ) );
+      extensions.push( ior_extension( doc, p.prop_ior ) );
+    },
+    MaterialType::Emit( p ) => {
+      json.push_str( &format!( ",\"emissiveFactor\":{}", json_floats( &[r, g, b] ) ) );
+      // The power slider scales the emission further
+      let strength = p.prop_emit * ( 1 + p.prop_flux ) as f32;
+      use_extension( doc, "KHR_materials_emissive_strength" );
+      extensions.push(
+        format!( "\"KHR_materials_emissive_strength\":{{\"emissiveStrength\":{}}}", json_float( strength ) )
+      );
+    },
+    MaterialType::Diffuse | MaterialType::Media( _ ) => { }
+  }
+
+  if !extensions.is_empty( ) {
+    json.push_str( &format!( ",\"extensions\":{{{}}}", extensions.join( "," ) ) );
+  }
+  json.push( '}' );
+  json
+}
+
+/// Returns the `KHR_materials_ior` extension object. (See [`MetalMaterial`]
+/// for the offset of `prop_ior`)
+///
+/// [`MetalMaterial`]: crate::data::custom::MetalMaterial
+fn ior_extension( doc: &mut Document, prop_ior: f32 ) -> String {
+  use_extension( doc, "KHR_materials_ior" );
+  format!( "\"KHR_materials_ior\":{{\"ior\":{}}}", json_float( 1.0 + prop_ior ) )
+}
+
+/// Returns the `KHR_materials_transmission` extension object.
+fn transmission_extension( doc: &mut Document, transmission: f32 ) -> String {
+  use_extension( doc, "KHR_materials_transmission" );
+  format!( "\"KHR_materials_transmission\":{{\"transmissionFactor\":{}}}", json_float( transmission ) )
+}
+
+/// Registers the extension in `extensionsUsed`.
+fn use_extension( doc: &mut Document, name: &'static str ) {
+  if !doc.extensions.contains( &name ) {
+    doc.extensions.push( name );
+  }
+}
+
+/// Serializes the document into the binary glTF container.
+fn to_glb( doc: &Document ) -> Vec< u8 > {
+  let mut json =
+    format!(
+      "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"vox_parser\"}},\"scene\":0,\"scenes\":[{{\"nodes\":[0]}}],\"nodes\":[{}]",
+      doc.nodes.join( "," )
+    );
+  for (name, items) in
+    &[ ("meshes", &doc.meshes), ("materials", &doc.materials)
+     , ("accessors", &doc.accessors), ("bufferViews", &doc.buffer_views)
+     ] {
+    if !items.is_empty( ) {
+      json.push_str( &format!( ",\"{}\":[{}]", name, items.join( "," ) ) );
+    }
+  }
+  if !doc.buffer.is_empty( ) {
+    json.push_str( &format!( ",\"buffers\":[{{\"byteLength\":{}}}]", doc.buffer.len( ) ) );
+  }
+  if !doc.extensions.is_empty( ) {
+    let names: Vec< String > = doc.extensions.iter( ).map( |e| format!( "\"{}\"", e ) ).collect( );
+    json.push_str( &format!( ",\"extensionsUsed\":[{}]", names.join( "," ) ) );
+  }
+  json.push( '}' );
+
+  // Chunks are 4-byte aligned. JSON is padded with spaces.
+  let mut json = json.into_bytes( );
+  while json.len( ) % 4 != 0 {
+    json.push( b' ' );
+  }
+
+  let mut total_len = 12 + 8 + json.len( );
+  if !doc.buffer.is_empty( ) {
+    total_len += 8 + doc.buffer.len( );
+  }
+
+  let mut out = Vec::with_capacity( total_len );
+  out.extend( b"glTF" );
+  out.extend( &2u32.to_le_bytes( ) );
+  out.extend( &( total_len as u32 ).to_le_bytes( ) );
+
+  out.extend( &( json.len( ) as u32 ).to_le_bytes( ) );
+  out.extend( b"JSON" );
+  out.extend( &json );
+
+  if !doc.buffer.is_empty( ) {
+    out.extend( &( doc.buffer.len( ) as u32 ).to_le_bytes( ) );
+    out.extend( b"BIN\0" );
+    out.extend( &doc.buffer );
+  }
+
+  out
+}
+
+/// Converts an sRGB color channel to a linear intensity in `0.0..=1.0`.
+fn srgb_to_linear( c: u8 ) -> f32 {
+  let c = c as f32 / 255.0;
+  if c <= 0.04045 {
+    c / 12.92
+  } else {
+    ( ( c + 0.055 ) / 1.055 ).powf( 2.4 )
+  }
+}
+
+/// Encodes the floats as little-endian bytes.
+fn floats_to_bytes< 'a, I: Iterator< Item = &'a f32 > >( values: I ) -> Vec< u8 > {
+  values.flat_map( |v| v.to_le_bytes( ) ).collect( )
+}
+
+/// Formats the float as a JSON number. JSON cannot represent non-finite
+/// numbers, so NaN becomes `0`, and infinities are clamped to the largest
+/// finite floats.
+fn json_float( v: f32 ) -> String {
+  if v.is_nan( ) {
+    "0".to_string( )
+  } else {
+    v.clamp( f32::MIN, f32::MAX ).to_string( )
+  }
+}
+
+/// Formats the floats as a JSON array. (See [`json_float`])
+fn json_floats( values: &[f32] ) -> String {
+  let values: Vec< String > = values.iter( ).map( |v| json_float( *v ) ).collect( );
+  format!( "[{}]", values.join( "," ) )
+}
+
+/// Formats the text as a JSON string, with quotes and escapes.
+fn json_string( s: &str ) -> String {
+  let mut out = String::with_capacity( s.len( ) + 2 );
+  out.push( '"' );
+  for c in s.chars( ) {
+    match c {
+      '"'  => out.push_str( "\\\"" ),
+      '\\' => out.push_str( "\\\\" ),
+      c if ( c as u32 ) < 0x20 => out.push_str( &format!( "\\u{:04x}", c as u32 ) ),
+      c => out.push( c )
+    }
+  }
+  out.push( '"' );
+  out
+}
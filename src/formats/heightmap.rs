@@ -0,0 +1,140 @@
+//! Conversion from heightmaps to voxel terrain.
+//!
+//! Every pixel of the heightmap becomes a column of voxels, whose height is
+//! the brightness of the pixel. Seen from above, the top row of the image is
+//! the far end (the positive _y_ axis). Transparent pixels have no column.
+//!
+//! The columns take their colors from an optional color map, which is aligned
+//! with the heightmap. Without one (or where it is transparent), the colors
+//! follow [`HeightmapOptions::height_colors`] instead.
+//!
+//! # Example: Create a terrain from images
+//!
+//! ```no_run
+//! use vox_parser::formats::heightmap::{self, HeightmapOptions};
+//! use vox_parser::formats::image::Image;
+//!
+//! let heights = Image::from_png( &std::fs::read( "heights.png" ).unwrap( ) ).unwrap( );
+//! let colors = Image::from_png( &std::fs::read( "colors.png" ).unwrap( ) ).unwrap( );
+//!
+//! let options = HeightmapOptions { max_height: 100, ..HeightmapOptions::default( ) };
+//! let scene = heightmap::import( &heights, Some( &colors ), &options );
+//! std::fs::write( "terrain.vox", vox_parser::unparse::file_custom( &scene ) ).unwrap( );
+//! ```
+
+
+// Local imports
+use crate::builder::SceneBuilder;
+use crate::data::custom::VoxScene;
+use crate::formats::image::Image;
+use crate::formats::{build_scene, place_voxels};
+use crate::palette::{self, Rgba};
+
+
+/// Options for [`import`].
+#[derive(Debug, Clone)]
+pub struct HeightmapOptions {
+  /// The height (in voxels) of a white pixel, above the bottom layer. A black
+  /// pixel is a single voxel.
+  pub max_height    : u32,
+  /// When set, every column is filled down to the bottom. Otherwise, columns
+  /// only extend down to the tops of their neighbours, which leaves the
+  /// terrain hollow (but without gaps in its surface).
+  pub solid         : bool,
+  /// The colors of equally-sized height bands, from the bottom to the top.
+  /// These color the columns which have no color in the color map.
+  pub height_colors : Vec< Rgba >
+}
+
+impl Default for HeightmapOptions {
+  fn default( ) -> HeightmapOptions {
+    HeightmapOptions {
+      max_height:    64,
+      solid:         true,
+      // Sand, grass, forest, rock, and snow
+      height_colors: vec![
+        (219, 207, 163, 255), (95, 159, 53, 255), (60, 110, 30, 255),
+        (125, 125, 125, 255), (249, 254, 254, 255)
+      ]
+    }
+  }
+}
+
+/// Converts the heightmap (with an optional color map) into a terrain scene.
+/// The brightness of a pixel is its luminance, so any image can serve as a
+/// heightmap.
+///
+/// The terrain's lowest corner is at the origin. Terrains beyond 256 voxels
+/// along any axis are split into multiple models. The colors are quantized
+/// into the palette. (See [`palette::quantize`])
+pub fn import( heightmap: &Image, colors: Option< &Image >, options: &HeightmapOptions ) -> VoxScene {
+  let (width, height) = ( heightmap.width, heightmap.height );
+
+  // The top of every column, if any
+  let top = |x: i64, y: i64| -> Option< u32 > {
+    if x < 0 || y < 0 {
+      return None;
+    }
+    let (r, g, b, a) = heightmap.pixel( x as u32, y as u32 );
+    if a == 0 {
+      return None;
+    }
+    let luminance = 0.299 * r as f32 + 0.587 * g as f32 + 0.114 * b as f32;
+    Some( ( luminance / 255.0 * options.max_height as f32 ).round( ) as u32 )
+  };
+
+  let band_color = |z: u32| -> Rgba {
+    let n = options.height_colors.len( ) as u32;
+    let band = ( z as u64 * n as u64 / ( options.max_height as u64 + 1 ) ) as usize;
+    options.height_colors.get( band.min( n.saturating_sub( 1 ) as usize ) ).copied( ).unwrap_or( (128, 128, 128, 255) )
+  };
+
+  let mut columns = Vec::new( );
+  for y in 0..height {
+    for x in 0..width {
+      let (xi, yi) = ( x as i64, y as i64 );
+      if let Some( z ) = top( xi, yi ) {
+        let bottom =
+          if options.solid {
+            0
+          } else {
+            // Missing neighbours expose the whole side of the column
+            [(xi - 1, yi), (xi + 1, yi), (xi, yi - 1), (xi, yi + 1)].iter( )
+              .map( |(nx, ny)| top( *nx, *ny ).map_or( 0, |t| t + 1 ) )
+              .min( ).unwrap_or( 0 ).min( z )
+          };
+
+        let color =
+          colors.map( |c| c.pixel( x, y ) )
+            .filter( |c| c.3 != 0 )
+            .map_or_else( || band_color( z ), |(r, g, b, _)| (r, g, b, 255) );
+
+        // Image rows go from the far end to the near end
+        columns.push( ((x, height - 1 - y), bottom, z, color) );
+      }
+    }
+  }
+
+  let q =
+    palette::quantize(
+      columns.iter( ).flat_map( |(_, bottom, z, c)| ( *bottom..=*z ).map( move |_| *c ) )
+    );
+
+  let mut builder = SceneBuilder::new( );
+  for (i, c) in &q.entries {
+    builder.color( *i, *c );
+  }
+
+  let mut voxels = Vec::new( );
+  for ((x, y), bottom, z, c) in &columns {
+    let i = q.index_of( *c ).unwrap_or( 1 );
+    voxels.extend( ( *bottom..=*z ).map( |z| ((*x, *y, z), i) ) );
+  }
+
+  if !voxels.is_empty( ) {
+    let node = place_voxels( &mut builder, (0, 0, 0), &voxels );
+    builder.add( node );
+  }
+
+  build_scene( builder )
+}
@@ -0,0 +1,49 @@
+//! RGBA images, which are the input of the image-based importers.
+//!
+//! Images are decoded from PNG files, or constructed from raw pixels.
+
+
+// Local imports
+use crate::formats::{ExportError, ImportError, png};
+use crate::palette::Rgba;
+
+
+/// An image with 8-bit RGBA pixels.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Image {
+  pub width  : u32,
+  pub height : u32,
+  /// The rows from top to bottom, with 4 bytes per pixel.
+  pub pixels : Vec< u8 >
+}
+
+impl Image {
+  /// Decodes a PNG file. All color types and bit depths are supported, but
+  /// interlaced images are not. Images of more than 2^28 pixels are rejected.
+  pub fn from_png( input: &[u8] ) -> Result< Image, ImportError > {
+    let (width, height, pixels) = png::read_rgba( input )?;
+    Ok( Image { width, height, pixels } )
+  }
+
+  /// Encodes the image as a PNG file.
+  ///
+  /// Returns [`ExportError::InvalidImage`] when the image is empty (as PNG
+  /// cannot represent empty images), or when `pixels` does not contain exactly
+  /// `width * height` pixels.
+  pub fn to_png( &self ) -> Result< Vec< u8 >, ExportError > {
+    png::write_rgba( self.width, self.height, &self.pixels )
+  }
+
+  /// Returns the pixel at column `x` and row `y`, where row 0 is the top.
+  /// Pixels outside the image are transparent.
+  pub fn pixel( &self, x: u32, y: u32 ) -> Rgba {
+    if x >= self.width || y >= self.height {
+      return (0, 0, 0, 0);
+    }
+    let i = 4 * ( y as usize * self.width as usize + x as usize );
+    match self.pixels.get( i..i + 4 ) {
+      Some( p ) => (p[ 0 ], p[ 1 ], p[ 2 ], p[ 3 ]),
+      None      => (0, 0, 0, 0)
+    }
+  }
+}
@@ -0,0 +1,527 @@
+//! Conversion to and from Minecraft schematics and structures.
+//!
+//! Two NBT-based formats are supported: Sponge schematics (`.schem`, as used
+//! by WorldEdit), and vanilla structure files (`.nbt`, as used by structure
+//! blocks). Files are gzip-compressed.
+//!
+//! Blocks are mapped to palette indices through [`MinecraftOptions::blocks`].
+//! Blocks without a mapping are matched by color instead: On import, every
+//! block gets the approximate color of its texture, which is quantized into
+//! the palette. On export, every palette index becomes the block with the
+//! closest color.
+//!
+//! Minecraft's _y_ axis points up, whereas the _z_ axis points up in `.vox`
+//! files. The axes are rotated accordingly: Minecraft's north (negative _z_)
+//! is the positive _y_ axis in MagicaVoxel.
+//!
+//! # Example: Convert a schematic, mapping stone to palette index 8
+//!
+//! ```no_run
+//! use vox_parser::formats::minecraft::{self, MinecraftOptions};
+//!
+//! let mut options = MinecraftOptions::default( );
+//! options.blocks.insert( "minecraft:stone".to_string( ), 8 );
+//!
+//! let content = std::fs::read( "castle.schem" ).unwrap( );
+//! let scene = minecraft::import( &content, &options ).unwrap( );
+//! std::fs::write( "castle.vox", vox_parser::unparse::file_custom( &scene ) ).unwrap( );
+//! ```
+
+
+// Stdlib imports
+use std::collections::{BTreeMap, HashMap};
+// Local imports
+use crate::builder::SceneBuilder;
+use crate::data::custom::VoxScene;
+use crate::formats::nbt::{self, Tag};
+use crate::formats::{ExportError, ImportError, build_scene, place_voxels, visible_instances};
+use crate::palette::{self, QuantizeOptions, Rgba};
+use crate::spatial::Aabb;
+
+
+/// The data version written to exported files, which is Minecraft 1.20.1.
+const DATA_VERSION : i32 = 3465;
+
+/// The file format produced by [`export`]. ([`import`] detects the format)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NbtFormat {
+  /// A Sponge schematic (version 2), as read by WorldEdit.
+  Sponge,
+  /// A vanilla structure, as read by structure blocks. Empty positions are
+  /// structure voids, which keep the existing blocks in the world.
+  Structure
+}
+
+/// Options for [`import`] and [`export`].
+#[derive(Debug, Clone)]
+pub struct MinecraftOptions {
+  pub format : NbtFormat,
+  /// Maps blocks to palette indices. A block is either a name (such as
+  /// `minecraft:stone`) or a block state (such as `minecraft:oak_log[axis=x]`),
+  /// where a block state takes precedence over its name.
+  ///
+  /// On import, the colors of these palette indices are left as they are in
+  /// the default palette, and blocks mapped to 0 are omitted. On export, a
+  /// palette index takes the first block (in alphabetical order) which maps to
+  /// it.
+  pub blocks : HashMap< String, u8 >
+}
+
+impl Default for MinecraftOptions {
+  fn default( ) -> MinecraftOptions {
+    MinecraftOptions {
+      format: NbtFormat::Sponge,
+      blocks: HashMap::new( )
+    }
+  }
+}
+
+/// Internal. A block in Minecraft's coordinates.
+struct Block {
+  position : (i32, i32, i32),
+  state    : String
+}
+
+/// Internal. The non-empty blocks of a file.
+struct Blocks {
+  /// The position of the lowest corner in the world
+  offset : (i32, i32, i32),
+  size   : (u32, u32, u32),
+  blocks : Vec< Block >
+}
+
+/// Converts a schematic or structure into a scene. Air and structure voids
+/// are empty.
+///
+/// The blocks keep their position in the world, where a schematic's offset is
+/// included. The colors of unmapped blocks are quantized into the palette
+/// indices which are not used by [`MinecraftOptions::blocks`]. (See
+/// [`palette::quantize_with`]) When all indices are used, unmapped blocks are
+/// omitted.
+pub fn import( input: &[u8], options: &MinecraftOptions ) -> Result< VoxScene, ImportError > {
+  let (_, root) = nbt::read( input )?;
+
+  let Blocks { offset, size: (width, height, length), blocks } =
+    if root.get( "blocks" ).is_some( ) {
+      structure_blocks( &root )?
+    } else {
+      // Version 3 nests the schematic in the root
+      sponge_blocks( root.get( "Schematic" ).unwrap_or( &root ) )?
+    };
+
+  let mut builder = SceneBuilder::new( );
+
+  // Quantize the colors of the unmapped blocks into the remaining indices
+  let mapped = |state: &str| {
+    options.blocks.get( state ).or_else( || options.blocks.get( base_name( state ) ) ).copied( )
+  };
+  let quantize_options =
+    QuantizeOptions {
+      reserved: options.blocks.values( ).copied( ).collect( ),
+      ..QuantizeOptions::default( )
+    };
+  let q =
+    palette::quantize_with(
+      blocks.iter( ).filter( |b| mapped( &b.state ).is_none( ) ).map( |b| block_color( &b.state ) ),
+      &quantize_options
+    );
+  for (i, c) in &q.entries {
+    builder.color( *i, *c );
+  }
+
+  let palette_index = |state: &str| {
+    mapped( state ).or_else( || q.index_of( block_color( state ) ) ).unwrap_or( 0 )
+  };
+
+  // Minecraft's block (x,y,z) is the voxel (x,-z-1,y)
+  let min = ( offset.0 as i64, -( offset.2 as i64 ) - length as i64, offset.1 as i64 );
+  let fits = |min: i64, size: u32| min >= i32::MIN as i64 && min + size as i64 <= i32::MAX as i64 + 1;
+  if !( fits( min.0, width ) && fits( min.1, length ) && fits( min.2, height ) ) {
+    return Err( ImportError::InvalidHeader );
+  }
+  let min = ( min.0 as i32, min.1 as i32, min.2 as i32 );
+  let mut voxels: Vec< ((u32,u32,u32), u8) > =
+    blocks.iter( )
+      .filter( |b| palette_index( &b.state ) != 0 )
+      .map( |b| {
+        let (x, y, z) = b.position;
+        ((x as u32, length - 1 - z as u32, y as u32), palette_index( &b.state ))
+      } )
+      .collect( );
+  voxels.sort( );
+
+  if !voxels.is_empty( ) {
+    let node = place_voxels( &mut builder, min, &voxels );
+    builder.add( node );
+  }
+
+  Ok( build_scene( builder ) )
+}
+
+/// Exports the visible instances of the scene as a schematic or structure.
+/// Instances on hidden layers are omitted.
+///
+/// When instances overlap, the voxel of the first instance (in the order of
+/// [`VoxScene::instances`]) is used. A schematic's offset is the position of
+/// its lowest corner in the world.
+///
+/// Fails with [`ExportError::TooLarge`] when the scene is too large for the
+/// format. Schematics hold at most 65535 blocks along every axis, and at most
+/// 2^31 - 1 blocks in total. Structures hold at most 2^31 - 1 blocks along
+/// every axis.
+pub fn export( scene: &VoxScene, options: &MinecraftOptions ) -> Result< Vec< u8 >, ExportError > {
+  let mut voxels = HashMap::new( );
+  for inst in visible_instances( scene ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, i) in &model.xyzi {
+      voxels.entry( inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) ) ).or_insert( *i );
+    }
+  }
+
+  let bounds = Aabb::enclosing( voxels.keys( ).copied( ) ).unwrap_or( Aabb { min: (0,0,0), max: (0,0,0) } );
+  let (x_size, y_size, z_size) = bounds.size( );
+  let max_size = if options.format == NbtFormat::Sponge { u16::MAX as u32 } else { i32::MAX as u32 };
+  if x_size.max( y_size ).max( z_size ) > max_size {
+    return Err( ExportError::TooLarge );
+  }
+  let size = (x_size as i32, z_size as i32, y_size as i32);
+  let offset = ( bounds.min.0, bounds.min.2, -bounds.max.1 );
+
+  // The block of every palette index, where mapped blocks take precedence
+  let mut index_blocks: BTreeMap< u8, &str > = BTreeMap::new( );
+  let mut mapped: Vec< (&String, &u8) > = options.blocks.iter( ).collect( );
+  mapped.sort( );
+  for (state, i) in mapped {
+    index_blocks.entry( *i ).or_insert( state );
+  }
+  let colors = block_colors( );
+  let block_of = |i: u8| {
+    index_blocks.get( &i ).map_or_else(
+      || {
+        let c = scene.palette[ i as usize - 1 ].rgba;
+        colors[ closest( &colors.iter( ).map( |b| b.1 ).collect::< Vec< _ > >( ), c ).unwrap_or( 0 ) ].0.clone( )
+      },
+      |s| s.to_string( ) )
+  };
+
+  // The blocks in Minecraft's coordinates, with their states
+  let mut blocks: Vec< ((i32,i32,i32), u8) > =
+    voxels.iter( )
+      .map( |((x, y, z), i)| ((x - bounds.min.0, z - bounds.min.2, bounds.max.1 - 1 - y), *i) )
+      .collect( );
+  blocks.sort_by_key( |((x, y, z), _)| (*y, *z, *x) );
+
+  let mut states: Vec< String > = vec![ "minecraft:air".to_string( ) ];
+  let mut state_ids: HashMap< u8, i32 > = HashMap::new( );
+  for (_, i) in &blocks {
+    state_ids.entry( *i ).or_insert_with( || {
+      states.push( block_of( *i ) );
+      ( states.len( ) - 1 ) as i32
+    } );
+  }
+
+  let (name, root) =
+    match options.format {
+      NbtFormat::Sponge    => ("Schematic", sponge( size, offset, &states, &blocks, &state_ids )?),
+      NbtFormat::Structure => ("", structure( size, &states, &blocks, &state_ids ))
+    };
+  nbt::write( name, &root )
+}
+
+/// Returns the blocks of a Sponge schematic.
+fn sponge_blocks( root: &Tag ) -> Result< Blocks, ImportError > {
+  let dim = |name: &'static str| {
+    root.get( name ).and_then( Tag::as_int ).map( |v| v as u16 as u32 ).ok_or( ImportError::MissingField( name ) )
+  };
+  let (width, height, length) = ( dim( "Width" )?, dim( "Height" )?, dim( "Length" )? );
+
+  let offset =
+    match root.get( "Offset" ).and_then( Tag::as_ints ) {
+      Some( v ) if v.len( ) == 3 => ( v[ 0 ] as i32, v[ 1 ] as i32, v[ 2 ] as i32 ),
+      _ => (0, 0, 0)
+    };
+
+  // Version 3 moved the blocks into their own compound
+  let (palette, data) =
+    match root.get( "Blocks" ) {
+      Some( blocks ) => ( blocks.get( "Palette" ), blocks.get( "Data" ) ),
+      None           => ( root.get( "Palette" ), root.get( "BlockData" ) )
+    };
+  let palette = palette.and_then( Tag::as_compound ).ok_or( ImportError::MissingField( "Palette" ) )?;
+  let data =
+    match data {
+      Some( Tag::ByteArray( data ) ) => data,
+      _ => return Err( ImportError::MissingField( "BlockData" ) )
+    };
+
+  let states: HashMap< i64, &str > =
+    palette.iter( ).filter_map( |(state, id)| id.as_int( ).map( |id| (id, state.as_str( )) ) ).collect( );
+
+  // The block data contains a variable-length palette id for every block,
+  // in the order of the y, z, and x coordinates
+  let mut blocks = Vec::new( );
+  let volume = width as u64 * height as u64 * length as u64;
+  let mut index = 0u64;
+  let mut value = 0i64;
+  let mut shift = 0;
+  for b in data {
+    value |= ( ( b & 0x7F ) as i64 ) << shift;
+    shift += 7;
+    if b & 0x80 == 0 {
+      let state = states.get( &value ).copied( ).unwrap_or( "minecraft:air" );
+      if !is_empty( state ) && index < volume {
+        let (width, length) = ( width as u64, length as u64 );
+        let position =
+          ( ( index % width ) as i32
+          , ( index / ( width * length ) ) as i32
+          , ( ( index / width ) % length ) as i32
+          );
+        blocks.push( Block { position, state: state.to_string( ) } );
+      }
+      index += 1;
+      value = 0;
+      shift = 0;
+    } else if shift > 35 {
+      return Err( ImportError::MissingField( "BlockData" ) );
+    }
+  }
+
+  Ok( Blocks { offset, size: (width, height, length), blocks } )
+}
+
+/// Returns the blocks of a vanilla structure, which has no offset.
+fn structure_blocks( root: &Tag ) -> Result< Blocks, ImportError > {
+  let size =
+    match root.get( "size" ).and_then( Tag::as_ints ) {
+      Some( v ) if v.len( ) == 3 => ( v[ 0 ].max( 0 ), v[ 1 ].max( 0 ), v[ 2 ].max( 0 ) ),
+      _ => return Err( ImportError::MissingField( "size" ) )
+    };
+  if size.0.max( size.1 ).max( size.2 ) > i32::MAX as i64 {
+    return Err( ImportError::InvalidHeader );
+  }
+  let size = ( size.0 as u32, size.1 as u32, size.2 as u32 );
+
+  // Structures with random variants have multiple palettes, of which the
+  // first is used
+  let palette =
+    root.get( "palette" )
+      .or_else( || root.get( "palettes" ).and_then( Tag::as_list ).and_then( |p| p.first( ) ) )
+      .and_then( Tag::as_list )
+      .ok_or( ImportError::MissingField( "palette" ) )?;
+  let states: Vec< String > = palette.iter( ).map( block_state ).collect( );
+
+  let mut blocks = Vec::new( );
+  for b in root.get( "blocks" ).and_then( Tag::as_list ).unwrap_or( &[] ) {
+    let state = b.get( "state" ).and_then( Tag::as_int ).and_then( |s| states.get( s as usize ) );
+    let pos = b.get( "pos" ).and_then( Tag::as_ints );
+    if let (Some( state ), Some( pos )) = (state, pos) {
+      let in_bounds =
+        pos.len( ) == 3 &&
+        pos.iter( ).zip( &[size.0, size.1, size.2] ).all( |(p, s)| *p >= 0 && *p < *s as i64 );
+      if in_bounds && !is_empty( state ) {
+        let position = (pos[ 0 ] as i32, pos[ 1 ] as i32, pos[ 2 ] as i32);
+        blocks.push( Block { position, state: state.clone( ) } );
+      }
+    }
+  }
+
+  Ok( Blocks { offset: (0, 0, 0), size, blocks } )
+}
+
+/// Returns the block state of a structure's palette entry, such as
+/// `minecraft:oak_log[axis=x]`. The properties are sorted.
+fn block_state( entry: &Tag ) -> String {
+  let name = entry.get( "Name" ).and_then( Tag::as_str ).unwrap_or( "minecraft:air" );
+  let mut properties: Vec< String > =
+    entry.get( "Properties" ).and_then( Tag::as_compound ).unwrap_or( &[] ).iter( )
+      .map( |(k, v)| format!( "{}={}", k, v.as_str( ).unwrap_or( "" ) ) )
+      .collect( );
+  properties.sort( );
+
+  if properties.is_empty( ) {
+    name.to_string( )
+  } else {
+    format!( "{}[{}]", name, properties.join( "," ) )
+  }
+}
+
+/// Constructs a Sponge schematic (version 2), which holds at most 2^31 - 1
+/// blocks.
+fn sponge(
+    size: (i32,i32,i32),
+    offset: (i32,i32,i32),
+    states: &[String],
+    blocks: &[((i32,i32,i32), u8)],
+    state_ids: &HashMap< u8, i32 > ) -> Result< Tag, ExportError > {
+
+  let (width, height, length) = ( size.0 as usize, size.1 as usize, size.2 as usize );
+  let volume = width.checked_mul( height ).and_then( |v| v.checked_mul( length ) );
+  let volume = volume.filter( |v| *v <= i32::MAX as usize ).ok_or( ExportError::TooLarge )?;
+  let mut ids = vec![ 0; volume ];
+  for ((x, y, z), i) in blocks {
+    ids[ *x as usize + width * ( *z as usize + length * *y as usize ) ] = state_ids[ i ];
+  }
+
+  let mut data = Vec::with_capacity( ids.len( ) );
+  for mut id in ids {
+    while id >= 0x80 {
+      data.push( ( id & 0x7F ) as u8 | 0x80 );
+      id >>= 7;
+    }
+    data.push( id as u8 );
+  }
+
+  let palette =
+    states.iter( ).enumerate( ).map( |(i, s)| (s.clone( ), Tag::Int( i as i32 )) ).collect( );
+
+  Ok( Tag::Compound( vec![
+    ( "Version".to_string( ),       Tag::Int( 2 ) ),
+    ( "DataVersion".to_string( ),   Tag::Int( DATA_VERSION ) ),
+    // The sizes are unsigned shorts
+    ( "Width".to_string( ),         Tag::Short( width as u16 as i16 ) ),
+    ( "Height".to_string( ),        Tag::Short( height as u16 as i16 ) ),
+    ( "Length".to_string( ),        Tag::Short( length as u16 as i16 ) ),
+    ( "Offset".to_string( ),        Tag::IntArray( vec![ offset.0, offset.1, offset.2 ] ) ),
+    ( "PaletteMax".to_string( ),    Tag::Int( states.len( ) as i32 ) ),
+    ( "Palette".to_string( ),       Tag::Compound( palette ) ),
+    ( "BlockData".to_string( ),     Tag::ByteArray( data ) ),
+    ( "BlockEntities".to_string( ), Tag::List( 10, Vec::new( ) ) )
+  ] ) )
+}
+
+/// Constructs a vanilla structure. Air is omitted from the blocks.
+fn structure(
+    size: (i32,i32,i32),
+    states: &[String],
+    blocks: &[((i32,i32,i32), u8)],
+    state_ids: &HashMap< u8, i32 > ) -> Tag {
+
+  let palette =
+    states.iter( )
+      .map( |s| Tag::Compound( vec![ ("Name".to_string( ), Tag::String( s.clone( ) )) ] ) )
+      .collect( );
+  let blocks =
+    blocks.iter( )
+      .map( |((x, y, z), i)| {
+        Tag::Compound( vec![
+          ( "state".to_string( ), Tag::Int( state_ids[ i ] ) ),
+          ( "pos".to_string( ),   Tag::List( 3, vec![ Tag::Int( *x ), Tag::Int( *y ), Tag::Int( *z ) ] ) )
+        ] )
+      } )
+      .collect( );
+
+  Tag::Compound( vec![
+    ( "DataVersion".to_string( ), Tag::Int( DATA_VERSION ) ),
+    ( "size".to_string( ),        Tag::List( 3, vec![ Tag::Int( size.0 ), Tag::Int( size.1 ), Tag::Int( size.2 ) ] ) ),
+    ( "palette".to_string( ),     Tag::List( 10, palette ) ),
+    ( "blocks".to_string( ),      Tag::List( 10, blocks ) ),
+    ( "entities".to_string( ),    Tag::List( 10, Vec::new( ) ) )
+  ] )
+}
+
+/// Returns `true` iff the block state does not occupy its position.
+fn is_empty( state: &str ) -> bool {
+  matches!( base_name( state ), "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air" | "minecraft:structure_void" )
+}
+
+/// Returns the block name of a block state, without its properties.
+fn base_name( state: &str ) -> &str {
+  state.split( '[' ).next( ).unwrap_or( state )
+}
+
+/// Returns the index of the color closest to `c`, or `None` if there are no
+/// colors.
+fn closest( colors: &[Rgba], c: Rgba ) -> Option< usize > {
+  let dist = |d: &Rgba| {
+    let dr = d.0 as i32 - c.0 as i32;
+    let dg = d.1 as i32 - c.1 as i32;
+    let db = d.2 as i32 - c.2 as i32;
+    dr * dr + dg * dg + db * db
+  };
+  colors.iter( ).enumerate( ).min_by_key( |(_, d)| dist( d ) ).map( |(i, _)| i )
+}
+
+/// The colors of the dyes, which color wool, concrete, glass, and more.
+const DYE_COLORS : [(&str, (u8,u8,u8)); 16] =
+  [ ("white",      (207, 213, 214)), ("orange",    (224,  97,   1))
+  , ("magenta",    (169,  48, 159)), ("light_blue", ( 36, 137, 199))
+  , ("yellow",     (241, 175,  21)), ("lime",      ( 94, 169,  24))
+  , ("pink",       (214, 101, 143)), ("gray",      ( 55,  58,  62))
+  , ("light_gray", (125, 125, 115)), ("cyan",      ( 21, 119, 136))
+  , ("purple",     (100,  32, 156)), ("blue",      ( 45,  47, 143))
+  , ("brown",      ( 96,  60,  32)), ("green",     ( 73,  91,  36))
+  , ("red",        (142,  33,  33)), ("black",     (  8,  10,  15))
+  ];
+
+/// The approximate colors of common full blocks. (Without the `minecraft:`
+/// namespace)
+const BLOCK_COLORS : [(&str, (u8,u8,u8)); 58] =
+  [ ("stone",          (125, 125, 125)), ("granite",         (149, 103,  85))
+  , ("diorite",        (188, 188, 188)), ("andesite",        (136, 136, 136))
+  , ("deepslate",      ( 80,  80,  82)), ("cobblestone",     (127, 127, 127))
+  , ("bedrock",        ( 85,  85,  85)), ("dirt",            (134,  96,  67))
+  , ("coarse_dirt",    (119,  85,  59)), ("grass_block",     ( 95, 159,  53))
+  , ("sand",           (219, 207, 163)), ("red_sand",        (190, 102,  33))
+  , ("gravel",         (131, 127, 126)), ("clay",            (160, 166, 179))
+  , ("sandstone",      (216, 203, 155)), ("red_sandstone",   (186,  99,  29))
+  , ("oak_planks",     (162, 130,  78)), ("spruce_planks",   (114,  84,  48))
+  , ("birch_planks",   (192, 175, 121)), ("jungle_planks",   (160, 115,  80))
+  , ("acacia_planks",  (168,  90,  50)), ("dark_oak_planks", ( 66,  43,  20))
+  , ("oak_log",        (109,  85,  50)), ("spruce_log",      ( 58,  37,  16))
+  , ("birch_log",      (216, 215, 210)), ("oak_leaves",      ( 60, 110,  30))
+  , ("snow_block",     (249, 254, 254)), ("ice",             (145, 183, 253))
+  , ("packed_ice",     (141, 180, 250)), ("water",           ( 63, 118, 228))
+  , ("lava",           (207,  92,  20)), ("obsidian",        ( 15,  10,  24))
+  , ("netherrack",     ( 97,  38,  38)), ("glowstone",       (171, 131,  84))
+  , ("end_stone",      (219, 222, 158)), ("bricks",          (150,  97,  83))
+  , ("stone_bricks",   (122, 121, 122)), ("mossy_cobblestone", (110, 118, 94))
+  , ("iron_block",     (220, 220, 220)), ("gold_block",      (246, 208,  61))
+  , ("diamond_block",  ( 98, 237, 228)), ("emerald_block",   ( 42, 203,  87))
+  , ("lapis_block",    ( 30,  67, 140)), ("redstone_block",  (175,  24,   5))
+  , ("coal_block",     ( 16,  15,  15)), ("quartz_block",    (235, 229, 222))
+  , ("glass",          (175, 213, 219)), ("terracotta",      (152,  94,  67))
+  , ("prismarine",     ( 99, 156, 151)), ("purpur_block",    (169, 125, 169))
+  , ("bone_block",     (229, 225, 207)), ("hay_block",       (166, 136,  38))
+  , ("pumpkin",        (198, 118,  24)), ("moss_block",      ( 89, 109,  45))
+  , ("copper_block",   (192, 107,  79)), ("amethyst_block",  (133,  97, 191))
+  , ("blackstone",     ( 42,  36,  41)), ("calcite",         (223, 224, 220))
+  ];
+
+/// Returns the blocks that palette colors are matched against on export,
+/// which are the common full blocks and the concretes.
+fn block_colors( ) -> Vec< (String, Rgba) > {
+  let blocks = BLOCK_COLORS.iter( ).map( |(name, c)| (name.to_string( ), *c) );
+  let concretes = DYE_COLORS.iter( ).map( |(dye, c)| (format!( "{}_concrete", dye ), *c) );
+  blocks.chain( concretes )
+    .map( |(name, (r, g, b))| (format!( "minecraft:{}", name ), (r, g, b, 255)) )
+    .collect( )
+}
+
+/// Returns the approximate color of the block state.
+///
+/// Besides the common full blocks, dyed blocks (such as `red_wool`) take the
+/// color of their dye, and shaped blocks (such as `oak_stairs`) take the color
+/// of their material. Other blocks are gray.
+fn block_color( state: &str ) -> Rgba {
+  let name = base_name( state );
+  let name = name.strip_prefix( "minecraft:" ).unwrap_or( name );
+  let name = name.strip_prefix( "stripped_" ).unwrap_or( name );
+
+  let lookup = |n: &str| BLOCK_COLORS.iter( ).find( |b| b.0 == n ).map( |b| b.1 );
+  let shaped =
+    || {
+      const SHAPES : [&str; 10] =
+        [ "_stairs", "_slab", "_wall", "_fence_gate", "_fence", "_trapdoor"
+        , "_door", "_pressure_plate", "_button", "_wood" ];
+      SHAPES.iter( )
+        .filter_map( |s| name.strip_suffix( s ) )
+        .flat_map( |m| vec![ lookup( m ), lookup( &format!( "{}_planks", m ) ), lookup( &format!( "{}s", m ) ), lookup( &format!( "{}_log", m ) ) ] )
+        .flatten( )
+        .next( )
+    };
+  let dyed =
+    || DYE_COLORS.iter( ).find( |(dye, _)| name.starts_with( &format!( "{}_", dye ) ) ).map( |d| d.1 );
+
+  let (r, g, b) = lookup( name ).or_else( shaped ).or_else( dyed ).unwrap_or( (128, 128, 128) );
+  (r, g, b, 255)
+}
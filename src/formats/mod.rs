@@ -0,0 +1,182 @@
+//! Conversions between [`VoxScene`]s and other file formats.
+//! 
+//! Exporters that produce meshes place every visible instance of the scene
+//! graph in the world. Instances on hidden layers are omitted, as MagicaVoxel
+//! does not display them either.
+//! 
+//! Importers produce a [`VoxScene`], where true colors are quantized into the
+//! palette. (See [`palette::quantize`](crate::palette::quantize)) Models are at
+//! most 256 voxels along every axis, so larger voxel volumes are split into
+//! multiple models, which are placed next to each other by the scene graph.
+
+
+pub mod gltf;
+pub mod heightmap;
+pub mod image;
+pub mod minecraft;
+pub mod obj;
+pub mod palettes;
+pub mod ply;
+pub mod qb;
+pub mod slices;
+pub mod stl;
+pub mod txt;
+
+mod nbt;
+mod png;
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::builder::{NodeBuilder, SceneBuilder};
+use crate::data::custom::{Instance, Model, VoxScene};
+use crate::spatial::Aabb;
+
+
+/// An error while importing a file from another format.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ImportError {
+  /// Generic parser error as specified by [`nom`]. Mostly, the input ended
+  /// before the file was complete.
+  Nom( nom::error::ErrorKind ),
+  /// The file version is not supported.
+  UnknownVersion( u32 ),
+  /// A field in the file header has an unsupported value.
+  InvalidHeader,
+  /// A line of a text file could not be parsed. (Contains the line number,
+  /// starting at 1)
+  InvalidLine( usize ),
+  /// The compressed data is invalid.
+  InvalidCompression,
+  /// A required field is missing (or invalid) in a structured file.
+  MissingField( &'static str )
+}
+
+/// An error while exporting a scene to another format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportError {
+  /// The image is empty, or its pixels do not match its size.
+  InvalidImage,
+  /// The scene exceeds the size limits of the format.
+  TooLarge
+}
+
+impl< I > From< nom::Err< nom::error::Error< I > > > for ImportError {
+  fn from( err: nom::Err< nom::error::Error< I > > ) -> ImportError {
+    match err {
+      nom::Err::Error( e ) | nom::Err::Failure( e ) => ImportError::Nom( e.code ),
+      nom::Err::Incomplete( _ ) => ImportError::Nom( nom::error::ErrorKind::Eof )
+    }
+  }
+}
+
+
+/// The point of the scene which is placed at the origin of the exported file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pivot {
+  /// Keep the world coordinates of the scene. (As in MagicaVoxel)
+  Origin,
+  /// The center of the bounding box of the scene.
+  Center,
+  /// The center of the bottom face of the bounding box of the scene. This is
+  /// convenient for objects which are placed on the ground.
+  BottomCenter,
+  /// The lowest corner of the bounding box of the scene. All coordinates are
+  /// then non-negative.
+  Corner
+}
+
+/// Returns the visible instances of the scene. (See
+/// [`VoxScene::is_hidden`])
+fn visible_instances( scene: &VoxScene ) -> Vec< Instance > {
+  scene.instances( ).into_iter( ).filter( |i| !scene.is_hidden( i ) ).collect( )
+}
+
+/// Returns the world position (in the `.vox` coordinate system) that is moved
+/// to the origin for the given pivot.
+fn pivot_point( scene: &VoxScene, instances: &[Instance], pivot: Pivot ) -> [f32; 3] {
+  let bounds =
+    instances.iter( )
+      .filter_map( |i| i.tight_bounds( &scene.models[ i.model_id as usize ] ) )
+      .reduce( |a, b| a.union( &b ) );
+  bounds_pivot( bounds, pivot )
+}
+
+/// Returns the position that is moved to the origin for the given pivot, where
+/// `bounds` are the bounds of the exported voxels.
+fn bounds_pivot( bounds: Option< Aabb >, pivot: Pivot ) -> [f32; 3] {
+  let bounds = bounds.unwrap_or( Aabb { min: (0,0,0), max: (0,0,0) } );
+  let (x0, y0, z0) = ( bounds.min.0 as f32, bounds.min.1 as f32, bounds.min.2 as f32 );
+  let (x1, y1, z1) = ( bounds.max.0 as f32, bounds.max.1 as f32, bounds.max.2 as f32 );
+
+  match pivot {
+    Pivot::Origin       => [0.0, 0.0, 0.0],
+    Pivot::Center       => [( x0 + x1 ) / 2.0, ( y0 + y1 ) / 2.0, ( z0 + z1 ) / 2.0],
+    Pivot::BottomCenter => [( x0 + x1 ) / 2.0, ( y0 + y1 ) / 2.0, z0],
+    Pivot::Corner       => [x0, y0, z0]
+  }
+}
+
+/// Converts a direction from the `.vox` coordinate system (where _z_ points
+/// up) to the output coordinate system. When `y_up` is set, _y_ points up in
+/// the output; This is a proper rotation, so handedness is preserved.
+fn to_output_axes( [x, y, z]: [f32; 3], y_up: bool ) -> [f32; 3] {
+  if y_up {
+    [x, z, -y]
+  } else {
+    [x, y, z]
+  }
+}
+
+/// Builds the scene of an importer. Importers only set non-zero palette
+/// indices, and only place the models added by [`place_voxels`], so this
+/// never fails.
+fn build_scene( builder: SceneBuilder ) -> VoxScene {
+  builder.build( ).expect( "Imported scenes are valid" )
+}
+
+/// Adds the voxels to the builder as models, and returns the node which places
+/// them. Voxel `(x,y,z)` is placed at the world voxel `min + (x,y,z)`.
+///
+/// Voxels beyond 256 along an axis are split into multiple models, which
+/// are then grouped in the returned node.
+fn place_voxels(
+    builder: &mut SceneBuilder,
+    min: (i32,i32,i32),
+    voxels: &[((u32,u32,u32), u8)] ) -> NodeBuilder {
+
+  let mut tiles: HashMap< (u32,u32,u32), Vec< _ > > = HashMap::new( );
+  for ((x, y, z), i) in voxels {
+    if *i != 0 {
+      tiles.entry( (x / 256, y / 256, z / 256) ).or_default( )
+        .push( ( ( x % 256 ) as u8, ( y % 256 ) as u8, ( z % 256 ) as u8, *i ) );
+    }
+  }
+
+  let mut keys: Vec< (u32,u32,u32) > = tiles.keys( ).copied( ).collect( );
+  keys.sort( );
+
+  let mut nodes = Vec::with_capacity( keys.len( ) );
+  for key in keys {
+    let xyzi = tiles.remove( &key ).unwrap_or_default( );
+    let mut size = (1, 1, 1);
+    for (x, y, z, _) in &xyzi {
+      size = ( size.0.max( *x as u32 + 1 ), size.1.max( *y as u32 + 1 ), size.2.max( *z as u32 + 1 ) );
+    }
+    let model = Model { size, xyzi };
+    let (px, py, pz) = model.pivot( );
+    let model_id = builder.add_model( model );
+
+    // The model maps voxel `v` to `v - pivot + translation`. The tile offset
+    // may exceed `i32` on its own, when `min` is far below 0.
+    let offset = |min: i32, key: u32, p: i32| ( min as i64 + 256 * key as i64 + p as i64 ) as i32;
+    let translation = ( offset( min.0, key.0, px ), offset( min.1, key.1, py ), offset( min.2, key.2, pz ) );
+    nodes.push( NodeBuilder::shape( model_id ).translation( translation ) );
+  }
+
+  if nodes.len( ) == 1 {
+    nodes.remove( 0 )
+  } else {
+    NodeBuilder::group( nodes )
+  }
+}
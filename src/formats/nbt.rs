@@ -0,0 +1,319 @@
+//! Minimal reading and writing of Minecraft's Named Binary Tag (NBT) format,
+//! including its gzip compression.
+
+
+// External library imports
+use nom::bytes::complete::take;
+use nom::multi::count;
+use nom::number::complete::{be_f32, be_f64, be_i16, be_i32, be_i64, be_i8, be_u16, be_u8};
+// Local imports
+use crate::formats::{ExportError, ImportError};
+use crate::formats::png::crc32;
+
+
+type IResult< 'a, O > = nom::IResult< &'a [u8], O >;
+
+/// The deepest nesting of lists and compounds that is read. (Minecraft itself
+/// rejects files nested deeper than 512)
+const MAX_DEPTH : usize = 512;
+
+/// The largest decompressed file that is read, which is 256 MiB.
+const MAX_SIZE : usize = 1 << 28;
+
+/// A tag value. Compounds keep their fields in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tag {
+  Byte( i8 ),
+  Short( i16 ),
+  Int( i32 ),
+  Long( i64 ),
+  Float( f32 ),
+  Double( f64 ),
+  ByteArray( Vec< u8 > ),
+  String( String ),
+  /// A list, along with the type id of its elements
+  List( u8, Vec< Tag > ),
+  Compound( Vec< (String, Tag) > ),
+  IntArray( Vec< i32 > ),
+  LongArray( Vec< i64 > )
+}
+
+impl Tag {
+  /// Returns the type id of the tag, as stored in the file.
+  pub fn id( &self ) -> u8 {
+    match self {
+      Tag::Byte( _ )      => 1,
+      Tag::Short( _ )     => 2,
+      Tag::Int( _ )       => 3,
+      Tag::Long( _ )      => 4,
+      Tag::Float( _ )     => 5,
+      Tag::Double( _ )    => 6,
+      Tag::ByteArray( _ ) => 7,
+      Tag::String( _ )    => 8,
+      Tag::List( .. )     => 9,
+      Tag::Compound( _ )  => 10,
+      Tag::IntArray( _ )  => 11,
+      Tag::LongArray( _ ) => 12
+    }
+  }
+
+  /// Returns the field of a compound with the given name.
+  pub fn get( &self, name: &str ) -> Option< &Tag > {
+    match self {
+      Tag::Compound( fields ) => fields.iter( ).find( |(n, _)| n == name ).map( |(_, t)| t ),
+      _ => None
+    }
+  }
+
+  /// Returns the value of any integer tag.
+  pub fn as_int( &self ) -> Option< i64 > {
+    match self {
+      Tag::Byte( v )  => Some( *v as i64 ),
+      Tag::Short( v ) => Some( *v as i64 ),
+      Tag::Int( v )   => Some( *v as i64 ),
+      Tag::Long( v )  => Some( *v ),
+      _ => None
+    }
+  }
+
+  pub fn as_str( &self ) -> Option< &str > {
+    match self {
+      Tag::String( s ) => Some( s ),
+      _ => None
+    }
+  }
+
+  pub fn as_list( &self ) -> Option< &[Tag] > {
+    match self {
+      Tag::List( _, items ) => Some( items ),
+      _ => None
+    }
+  }
+
+  pub fn as_compound( &self ) -> Option< &[(String, Tag)] > {
+    match self {
+      Tag::Compound( fields ) => Some( fields ),
+      _ => None
+    }
+  }
+
+  /// Returns the integers of an integer array, or a list of integers.
+  pub fn as_ints( &self ) -> Option< Vec< i64 > > {
+    match self {
+      Tag::IntArray( v ) => Some( v.iter( ).map( |i| *i as i64 ).collect( ) ),
+      Tag::List( _, v )  => v.iter( ).map( Tag::as_int ).collect( ),
+      _ => None
+    }
+  }
+}
+
+/// Reads the (possibly gzip-compressed) file, and returns its named root tag.
+pub fn read( input: &[u8] ) -> Result< (String, Tag), ImportError > {
+  let data;
+  let input =
+    if input.starts_with( &[0x1F, 0x8B] ) {
+      data = gunzip( input )?;
+      &data[ .. ]
+    } else {
+      input
+    };
+
+  let (input, id) = be_u8( input )?;
+  let (input, name) = string( input )?;
+  let (_, root) = payload( id, input, 0 )?;
+  Ok( (name, root) )
+}
+
+/// Writes the named root tag as a gzip-compressed file. Fails with
+/// [`ExportError::TooLarge`] when a string is longer than 65535 bytes, or an
+/// array or list has more than 2^31 - 1 elements.
+pub fn write( name: &str, root: &Tag ) -> Result< Vec< u8 >, ExportError > {
+  let mut out = vec![ root.id( ) ];
+  write_string( &mut out, name )?;
+  write_payload( &mut out, root )?;
+  Ok( gzip( &out ) )
+}
+
+/// Parses the payload of a tag with the given type id, which is nested in
+/// `depth` lists and compounds.
+fn payload( id: u8, input: &[u8], depth: usize ) -> IResult< '_, Tag > {
+  if depth > MAX_DEPTH {
+    return Err( nom::Err::Failure( nom::error::Error::new( input, nom::error::ErrorKind::TooLarge ) ) );
+  }
+  match id {
+    1  => be_i8( input ).map( |(i, v)| (i, Tag::Byte( v )) ),
+    2  => be_i16( input ).map( |(i, v)| (i, Tag::Short( v )) ),
+    3  => be_i32( input ).map( |(i, v)| (i, Tag::Int( v )) ),
+    4  => be_i64( input ).map( |(i, v)| (i, Tag::Long( v )) ),
+    5  => be_f32( input ).map( |(i, v)| (i, Tag::Float( v )) ),
+    6  => be_f64( input ).map( |(i, v)| (i, Tag::Double( v )) ),
+    7  => {
+      let (input, len) = length( input )?;
+      let (input, v) = take( len )( input )?;
+      Ok( (input, Tag::ByteArray( v.to_vec( ) )) )
+    },
+    8  => string( input ).map( |(i, v)| (i, Tag::String( v )) ),
+    9  => {
+      let (mut input, elem_id) = be_u8( input )?;
+      let (rest, len) = length( input )?;
+      input = rest;
+      let mut items = Vec::new( );
+      for _ in 0..len {
+        let (rest, item) = payload( elem_id, input, depth + 1 )?;
+        items.push( item );
+        input = rest;
+      }
+      Ok( (input, Tag::List( elem_id, items )) )
+    },
+    10 => {
+      let mut input = input;
+      let mut fields = Vec::new( );
+      loop {
+        let (rest, field_id) = be_u8( input )?;
+        if field_id == 0 {
+          return Ok( (rest, Tag::Compound( fields )) );
+        }
+        let (rest, name) = string( rest )?;
+        let (rest, value) = payload( field_id, rest, depth + 1 )?;
+        fields.push( (name, value) );
+        input = rest;
+      }
+    },
+    11 => {
+      let (input, len) = length( input )?;
+      check_length( input, len, 4 )?;
+      count( be_i32, len )( input ).map( |(i, v)| (i, Tag::IntArray( v )) )
+    },
+    12 => {
+      let (input, len) = length( input )?;
+      check_length( input, len, 8 )?;
+      count( be_i64, len )( input ).map( |(i, v)| (i, Tag::LongArray( v )) )
+    },
+    _  => Err( nom::Err::Error( nom::error::Error::new( input, nom::error::ErrorKind::Tag ) ) )
+  }
+}
+
+/// Parses the length of an array or list. Negative lengths are empty.
+fn length( input: &[u8] ) -> IResult< '_, usize > {
+  be_i32( input ).map( |(i, len)| (i, len.max( 0 ) as usize) )
+}
+
+/// Fails unless the input holds `len` elements of `size` bytes. This avoids
+/// allocating arrays for lengths beyond the end of the file.
+fn check_length( input: &[u8], len: usize, size: usize ) -> IResult< '_, () > {
+  if len.checked_mul( size ).map_or( true, |n| n > input.len( ) ) {
+    return Err( nom::Err::Error( nom::error::Error::new( input, nom::error::ErrorKind::Eof ) ) );
+  }
+  Ok( (input, ()) )
+}
+
+/// Parses a string, which is prefixed by its length.
+fn string( input: &[u8] ) -> IResult< '_, String > {
+  let (input, len) = be_u16( input )?;
+  let (input, v) = take( len )( input )?;
+  Ok( (input, String::from_utf8_lossy( v ).into_owned( )) )
+}
+
+fn write_string( out: &mut Vec< u8 >, s: &str ) -> Result< (), ExportError > {
+  if s.len( ) > u16::MAX as usize {
+    return Err( ExportError::TooLarge );
+  }
+  out.extend( &( s.len( ) as u16 ).to_be_bytes( ) );
+  out.extend( s.as_bytes( ) );
+  Ok( () )
+}
+
+/// Writes the length of an array or list.
+fn write_len( out: &mut Vec< u8 >, len: usize ) -> Result< (), ExportError > {
+  if len > i32::MAX as usize {
+    return Err( ExportError::TooLarge );
+  }
+  out.extend( &( len as i32 ).to_be_bytes( ) );
+  Ok( () )
+}
+
+fn write_payload( out: &mut Vec< u8 >, tag: &Tag ) -> Result< (), ExportError > {
+  match tag {
+    Tag::Byte( v )      => out.extend( &v.to_be_bytes( ) ),
+    Tag::Short( v )     => out.extend( &v.to_be_bytes( ) ),
+    Tag::Int( v )       => out.extend( &v.to_be_bytes( ) ),
+    Tag::Long( v )      => out.extend( &v.to_be_bytes( ) ),
+    Tag::Float( v )     => out.extend( &v.to_be_bytes( ) ),
+    Tag::Double( v )    => out.extend( &v.to_be_bytes( ) ),
+    Tag::ByteArray( v ) => {
+      write_len( out, v.len( ) )?;
+      out.extend( v );
+    },
+    Tag::String( s )    => write_string( out, s )?,
+    Tag::List( id, items ) => {
+      out.push( *id );
+      write_len( out, items.len( ) )?;
+      for item in items {
+        write_payload( out, item )?;
+      }
+    },
+    Tag::Compound( fields ) => {
+      for (name, value) in fields {
+        out.push( value.id( ) );
+        write_string( out, name )?;
+        write_payload( out, value )?;
+      }
+      out.push( 0 );
+    },
+    Tag::IntArray( v )  => {
+      write_len( out, v.len( ) )?;
+      for i in v {
+        out.extend( &i.to_be_bytes( ) );
+      }
+    },
+    Tag::LongArray( v ) => {
+      write_len( out, v.len( ) )?;
+      for i in v {
+        out.extend( &i.to_be_bytes( ) );
+      }
+    }
+  }
+  Ok( () )
+}
+
+/// Decompresses a gzip member. (See RFC 1952) Data beyond [`MAX_SIZE`] is
+/// rejected as invalid.
+fn gunzip( input: &[u8] ) -> Result< Vec< u8 >, ImportError > {
+  const FHCRC    : u8 = 2;
+  const FEXTRA   : u8 = 4;
+  const FNAME    : u8 = 8;
+  const FCOMMENT : u8 = 16;
+
+  // Magic bytes, compression method, flags, time, extra flags, and OS
+  let (mut input, header) = take( 10usize )( input )?;
+  if header[ 2 ] != 8 {
+    return Err( ImportError::InvalidCompression );
+  }
+  let flags = header[ 3 ];
+
+  if flags & FEXTRA != 0 {
+    let (rest, len) = nom::number::complete::le_u16( input )?;
+    input = take( len )( rest )?.0;
+  }
+  for flag in &[FNAME, FCOMMENT] {
+    if flags & flag != 0 {
+      let end = input.iter( ).position( |b| *b == 0 ).ok_or( ImportError::InvalidCompression )?;
+      input = &input[ end + 1.. ];
+    }
+  }
+  if flags & FHCRC != 0 {
+    input = take( 2usize )( input )?.0;
+  }
+
+  miniz_oxide::inflate::decompress_to_vec_with_limit( input, MAX_SIZE ).map_err( |_| ImportError::InvalidCompression )
+}
+
+/// Compresses the data as a gzip member.
+fn gzip( data: &[u8] ) -> Vec< u8 > {
+  // No flags or time, and an unknown OS
+  let mut out = vec![ 0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 255 ];
+  out.extend( miniz_oxide::deflate::compress_to_vec( data, 6 ) );
+  out.extend( &crc32( data ).to_le_bytes( ) );
+  out.extend( &( data.len( ) as u32 ).to_le_bytes( ) );
+  out
+}
@@ -0,0 +1,235 @@
+//! Export to Wavefront `.obj` meshes, with `.mtl` material libraries.
+//!
+//! Every visible instance in the scene becomes a separate object in the `.obj`
+//! file. Colors are taken from the palette, either through a palette texture
+//! or through a material per palette index. (See [`ObjMaterials`])
+//!
+//! # Example: Export a scene
+//!
+//! ```no_run
+//! use vox_parser::formats::obj::{self, ObjOptions};
+//!
+//! let content = std::fs::read( "input.vox" ).unwrap( );
+//! let scene = vox_parser::parse::file_custom( &content ).unwrap( );
+//!
+//! let options = ObjOptions { name: "output".to_string( ), ..ObjOptions::default( ) };
+//! let files = obj::export( &scene, &options );
+//! std::fs::write( "output.obj", files.obj ).unwrap( );
+//! std::fs::write( "output.mtl", files.mtl ).unwrap( );
+//! if let Some( texture ) = files.texture {
+//!   std::fs::write( "output.png", texture ).unwrap( );
+//! }
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::{Material, MaterialType, VoxScene};
+use crate::formats::{Pivot, png, pivot_point, to_output_axes, visible_instances};
+use crate::mesh::{self, Mesh, palette_uv};
+
+
+/// How the colors of the palette are represented in the exported files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjMaterials {
+  /// A single material with a 256x1 palette texture. Every vertex has a
+  /// texture coordinate pointing into the texture. (See
+  /// [`palette_uv`](crate::mesh::palette_uv))
+  PaletteTexture,
+  /// A separate material for every used palette index, which carries its
+  /// color. No texture is produced.
+  PerColor
+}
+
+/// Options for [`export`].
+#[derive(Debug, Clone)]
+pub struct ObjOptions {
+  /// The size of a single voxel in the output units.
+  pub scale          : f32,
+  /// The point of the scene which is placed at the origin.
+  pub pivot          : Pivot,
+  /// When set, the _y_ axis points up in the output (as is common in most
+  /// modelling tools). Otherwise, the _z_ axis points up (as in `.vox` files).
+  pub y_up           : bool,
+  pub materials      : ObjMaterials,
+  /// When set, faces covered by the voxels of other instances are omitted.
+  /// (See [`greedy_instances`](crate::mesh::greedy_instances))
+  pub cull_instances : bool,
+  /// The name of the material library (`{name}.mtl`) and the palette texture
+  /// (`{name}.png`), as referenced by the exported files.
+  pub name           : String
+}
+
+impl Default for ObjOptions {
+  fn default( ) -> ObjOptions {
+    ObjOptions {
+      scale:          1.0,
+      pivot:          Pivot::Origin,
+      y_up:           true,
+      materials:      ObjMaterials::PaletteTexture,
+      cull_instances: false,
+      name:           "scene".to_string( )
+    }
+  }
+}
+
+/// The files produced by [`export`].
+#[derive(Debug, Clone)]
+pub struct ObjFiles {
+  /// Contents of the `.obj` file, which references the `.mtl` file.
+  pub obj     : String,
+  /// Contents of the `.mtl` file, which may reference the texture.
+  pub mtl     : String,
+  /// The palette texture as PNG. Only present for
+  /// [`ObjMaterials::PaletteTexture`].
+  pub texture : Option< Vec< u8 > >
+}
+
+/// The six axis-aligned normals, in the order they are written.
+static NORMALS: [[f32; 3]; 6] =
+  [ [1.0, 0.0, 0.0], [-1.0, 0.0, 0.0]
+  , [0.0, 1.0, 0.0], [0.0, -1.0, 0.0]
+  , [0.0, 0.0, 1.0], [0.0, 0.0, -1.0]
+  ];
+
+/// Exports the visible instances of the scene as a `.obj` mesh, with its
+/// materials in a `.mtl` file.
+pub fn export( scene: &VoxScene, options: &ObjOptions ) -> ObjFiles {
+  let instances = visible_instances( scene );
+  let pivot = pivot_point( scene, &instances, options.pivot );
+  let is_textured = options.materials == ObjMaterials::PaletteTexture;
+
+  let mut obj = String::new( );
+  obj.push_str( &format!( "mtllib {}.mtl\n", options.name ) );
+
+  // Normals are axis-aligned, so there are only 6 different normals.
+  for n in &NORMALS {
+    obj.push_str( &format!( "vn {} {} {}\n", n[0], n[1], n[2] ) );
+  }
+  // One texture coordinate per palette index
+  if is_textured {
+    for i in 1..=255 {
+      let [u, v] = palette_uv( i );
+      obj.push_str( &format!( "vt {} {}\n", u, v ) );
+    }
+    obj.push_str( "usemtl palette\n" );
+  }
+
+  // Without culling between instances, all instances of a model share its mesh
+  let instance_meshes =
+    if options.cull_instances { mesh::greedy_instances( scene ) } else { Vec::new( ) };
+  let mut meshes: HashMap< u32, Mesh > = HashMap::new( );
+  let mut used = [false; 256];
+  let mut num_vertices = 0;
+
+  for (instance_id, inst) in scene.instances( ).iter( ).enumerate( ) {
+    if scene.is_hidden( inst ) {
+      continue;
+    }
+
+    let model = &scene.models[ inst.model_id as usize ];
+    let mesh =
+      match instance_meshes.get( instance_id ) {
+        Some( mesh ) => mesh,
+        None => meshes.entry( inst.model_id ).or_insert_with( || mesh::greedy( model ) )
+      };
+
+    obj.push_str( &format!( "o instance_{}\n", instance_id ) );
+
+    for p in &mesh.positions {
+      let (x, y, z) = inst.point_to_world( model, (p[0], p[1], p[2]) );
+      let [x, y, z] = to_output_axes( [x - pivot[0], y - pivot[1], z - pivot[2]], options.y_up );
+      obj.push_str(
+        &format!( "v {} {} {}\n", x * options.scale, y * options.scale, z * options.scale )
+      );
+    }
+
+    // Group the triangles by palette index, to minimize material switches
+    let mut triangles: Vec< &[u32] > = mesh.indices.chunks( 3 ).collect( );
+    triangles.sort_by_key( |t| mesh.palette_indices[ t[0] as usize ] );
+
+    let mut prev_index = None;
+    for t in triangles {
+      let palette_index = mesh.palette_indices[ t[0] as usize ];
+      used[ palette_index as usize ] = true;
+
+      if !is_textured && prev_index != Some( palette_index ) {
+        obj.push_str( &format!( "usemtl palette_{}\n", palette_index ) );
+        prev_index = Some( palette_index );
+      }
+
+      // Mirroring instances reverse the winding order
+      let t = if inst.rotation.is_mirror( ) { [t[0], t[2], t[1]] } else { [t[0], t[1], t[2]] };
+
+      obj.push( 'f' );
+      for v in &t {
+        let n = mesh.normals[ *v as usize ];
+        let (nx, ny, nz) = inst.rotation.apply_to_f32( (n[0], n[1], n[2]) );
+        let n_index = normal_index( to_output_axes( [nx, ny, nz], options.y_up ) );
+
+        if is_textured {
+          obj.push_str( &format!( " {}/{}/{}", num_vertices + v + 1, palette_index, n_index ) );
+        } else {
+          obj.push_str( &format!( " {}//{}", num_vertices + v + 1, n_index ) );
+        }
+      }
+      obj.push( '\n' );
+    }
+
+    num_vertices += mesh.num_vertices( ) as u32;
+  }
+
+  let (mtl, texture) =
+    if is_textured {
+      let mtl =
+        format!(
+          "newmtl palette\nKd 1 1 1\nKa 0 0 0\nKs 0 0 0\nmap_Kd {}.png\n", options.name
+        );
+      (mtl, Some( palette_texture( &scene.palette ) ))
+    } else {
+      let mut mtl = String::new( );
+      for (i, m) in scene.palette.iter( ).enumerate( ) {
+        if used[ i + 1 ] {
+          mtl.push_str( &color_material( i as u8 + 1, m ) );
+        }
+      }
+      (mtl, None)
+    };
+
+  ObjFiles { obj, mtl, texture }
+}
+
+/// Returns the 1-based index of the normal in [`NORMALS`].
+fn normal_index( n: [f32; 3] ) -> usize {
+  NORMALS.iter( )
+    .position( |m| m.iter( ).zip( n.iter( ) ).all( |(a, b)| ( a - b ).abs( ) < 0.5 ) )
+    .unwrap_or( 0 ) + 1
+}
+
+/// Returns the `.mtl` description of the material of a single palette index.
+fn color_material( palette_index: u8, m: &Material ) -> String {
+  let (r, g, b, a) = m.rgba;
+  let (r, g, b) = ( r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0 );
+
+  let mut out = format!( "newmtl palette_{}\nKd {} {} {}\nKa 0 0 0\n", palette_index, r, g, b );
+  if a < 255 {
+    out.push_str( &format!( "d {}\n", a as f32 / 255.0 ) );
+  }
+  if let MaterialType::Emit( e ) = m.mat_type {
+    out.push_str( &format!( "Ke {} {} {}\n", r * e.prop_emit, g * e.prop_emit, b * e.prop_emit ) );
+  }
+  out
+}
+
+/// Encodes the palette as a 256x1 PNG image. (See
+/// [`palette_uv`](crate::mesh::palette_uv))
+fn palette_texture( palette: &[Material; 255] ) -> Vec< u8 > {
+  let mut pixels = Vec::with_capacity( 256 * 4 );
+  for m in palette.iter( ) {
+    let (r, g, b, a) = m.rgba;
+    pixels.extend( &[r, g, b, a] );
+  }
+  pixels.extend( &[0, 0, 0, 0] );
+  png::write_rgba( 256, 1, &pixels ).expect( "The palette texture has 256 pixels" )
+}
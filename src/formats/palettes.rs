@@ -0,0 +1,188 @@
+//! Conversion to and from the palette files of other tools.
+//!
+//! The supported formats are:
+//! * PNG strips, as MagicaVoxel itself saves palettes. (See [`import_png`])
+//! * GIMP palettes (`.gpl`), which Aseprite and Krita read as well.
+//! * Paint.NET palettes (`.txt`), with lines of `AARRGGBB`.
+//! * Hex lists (`.hex`), with lines of `RRGGBB`. (As shared on Lospec)
+//!
+//! All formats list the colors in order, where the first color is palette
+//! index 1. So, color `k` (from 0) is `palette[ k ]`, as documented on
+//! [`VoxScene::palette`](crate::data::custom::VoxScene::palette). Imported
+//! palettes are diffuse. When a file contains fewer than 255 colors, the
+//! remaining entries keep the default palette; Colors beyond 255 are ignored.
+//!
+//! An imported palette can replace the palette of a scene directly, which
+//! changes the colors of its voxels. Alternatively,
+//! [`VoxScene::match_palette`](crate::data::custom::VoxScene::match_palette)
+//! moves every voxel to the closest color of the new palette.
+//!
+//! # Example: Apply a GIMP palette to a scene
+//!
+//! ```
+//! use vox_parser::builder::{NodeBuilder, SceneBuilder};
+//! use vox_parser::formats::palettes;
+//!
+//! let mut builder = SceneBuilder::new( );
+//! builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 255) );
+//! let model = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2) ] );
+//! builder.add( NodeBuilder::shape( model ) );
+//! let mut scene = builder.build( ).unwrap( );
+//!
+//! let input = "GIMP Palette\nName: Primary\n#\n  0   0 255\tBlue\n255   0   0\tRed\n";
+//! let palette = palettes::import_gpl( input ).unwrap( );
+//! scene.match_palette( &palette );
+//! assert_eq!( scene.models[ 0 ].xyzi, vec![ (0,0,0,2), (1,0,0,1) ] );
+//!
+//! let output = palettes::export_gpl( &scene.palette, "Primary" );
+//! assert!( output.contains( "\n  0   0 255\tIndex 1\n" ) );
+//! ```
+
+
+// Local imports
+use crate::data::custom::{Material, MaterialType};
+use crate::data::spec::DEFAULT_PALETTE;
+use crate::formats::ImportError;
+use crate::formats::image::Image;
+use crate::palette::Rgba;
+
+
+/// Reads a palette from a PNG image. The pixels are read row by row, so both
+/// horizontal and vertical strips work. MagicaVoxel's palette images are
+/// strips of 256 pixels, where the last pixel is unused.
+pub fn import_png( input: &[u8] ) -> Result< [Material; 255], ImportError > {
+  let image = Image::from_png( input )?;
+  let colors =
+    image.pixels.chunks_exact( 4 )
+      .map( |p| (p[ 0 ], p[ 1 ], p[ 2 ], p[ 3 ]) );
+  Ok( to_palette( colors ) )
+}
+
+/// Writes the palette as a PNG strip of 256 by 1 pixels, as MagicaVoxel does.
+/// The last pixel is transparent.
+pub fn export_png( palette: &[Material; 255] ) -> Vec< u8 > {
+  let mut pixels = Vec::with_capacity( 4 * 256 );
+  for m in palette.iter( ) {
+    let (r, g, b, a) = m.rgba;
+    pixels.extend( &[r, g, b, a] );
+  }
+  pixels.extend( &[0, 0, 0, 0] );
+  Image { width: 256, height: 1, pixels }.to_png( ).expect( "The palette image has 256 pixels" )
+}
+
+/// Reads a GIMP palette. The colors are opaque, and their names are ignored.
+pub fn import_gpl( input: &str ) -> Result< [Material; 255], ImportError > {
+  let mut lines = input.lines( ).enumerate( );
+  match lines.next( ) {
+    Some( (_, header) ) if header.trim( ) == "GIMP Palette" => { },
+    _ => return Err( ImportError::InvalidHeader )
+  }
+
+  let mut colors = Vec::new( );
+  for (i, line) in lines {
+    let line = line.trim( );
+    // Older versions lack the `Columns` field
+    if line.is_empty( ) || line.starts_with( '#' ) || line.starts_with( "Name:" ) || line.starts_with( "Columns:" ) {
+      continue;
+    }
+
+    let mut parts = line.split_whitespace( );
+    let mut channel = || parts.next( )?.parse::< u8 >( ).ok( );
+    match (channel( ), channel( ), channel( )) {
+      (Some( r ), Some( g ), Some( b )) => colors.push( (r, g, b, 255) ),
+      _ => return Err( ImportError::InvalidLine( i + 1 ) )
+    }
+  }
+  Ok( to_palette( colors ) )
+}
+
+/// Writes the palette as a GIMP palette with the given name. The colors are
+/// named after their palette indices, and arranged in 16 columns. (The alpha
+/// channel is lost)
+pub fn export_gpl( palette: &[Material; 255], name: &str ) -> String {
+  let mut out = format!( "GIMP Palette\nName: {}\nColumns: 16\n#\n", name );
+  for (i, m) in palette.iter( ).enumerate( ) {
+    let (r, g, b, _) = m.rgba;
+    out.push_str( &format!( "{:3} {:3} {:3}\tIndex {}\n", r, g, b, i + 1 ) );
+  }
+  out
+}
+
+/// Reads a Paint.NET palette, where every line is a color as `AARRGGBB`.
+/// Lines starting with `;` are comments.
+pub fn import_paint_net( input: &str ) -> Result< [Material; 255], ImportError > {
+  let mut colors = Vec::new( );
+  for (i, line) in input.lines( ).enumerate( ) {
+    let line = line.trim( );
+    if line.is_empty( ) || line.starts_with( ';' ) {
+      continue;
+    }
+    match parse_hex( line ).as_deref( ) {
+      Some( &[a, r, g, b] ) => colors.push( (r, g, b, a) ),
+      _ => return Err( ImportError::InvalidLine( i + 1 ) )
+    }
+  }
+  Ok( to_palette( colors ) )
+}
+
+/// Writes the palette as a Paint.NET palette. (Note that Paint.NET itself only
+/// shows the first 96 colors)
+pub fn export_paint_net( palette: &[Material; 255] ) -> String {
+  let mut out = String::from( "; paint.net Palette File\n; Colors: 255\n" );
+  for m in palette.iter( ) {
+    let (r, g, b, a) = m.rgba;
+    out.push_str( &format!( "{:02X}{:02X}{:02X}{:02X}\n", a, r, g, b ) );
+  }
+  out
+}
+
+/// Reads a hex list, where every line is a color as `RRGGBB`. (Optionally
+/// preceded by `#`) The colors are opaque.
+pub fn import_hex( input: &str ) -> Result< [Material; 255], ImportError > {
+  let mut colors = Vec::new( );
+  for (i, line) in input.lines( ).enumerate( ) {
+    let line = line.trim( );
+    if line.is_empty( ) {
+      continue;
+    }
+    match parse_hex( line.strip_prefix( '#' ).unwrap_or( line ) ).as_deref( ) {
+      Some( &[r, g, b] ) => colors.push( (r, g, b, 255) ),
+      _ => return Err( ImportError::InvalidLine( i + 1 ) )
+    }
+  }
+  Ok( to_palette( colors ) )
+}
+
+/// Writes the palette as a hex list. (The alpha channel is lost)
+pub fn export_hex( palette: &[Material; 255] ) -> String {
+  let mut out = String::new( );
+  for m in palette.iter( ) {
+    let (r, g, b, _) = m.rgba;
+    out.push_str( &format!( "{:02x}{:02x}{:02x}\n", r, g, b ) );
+  }
+  out
+}
+
+/// Returns a diffuse palette with the given colors, followed by the default
+/// palette.
+fn to_palette< I >( colors: I ) -> [Material; 255]
+  where I: IntoIterator< Item = Rgba > {
+
+  let mut palette =
+    std::array::from_fn( |i| Material::new( DEFAULT_PALETTE[ i ], MaterialType::Diffuse ) );
+  for (m, rgba) in palette.iter_mut( ).zip( colors ) {
+    m.rgba = rgba;
+  }
+  palette
+}
+
+/// Parses a string of hexadecimal digit pairs into bytes.
+fn parse_hex( s: &str ) -> Option< Vec< u8 > > {
+  // `from_str_radix` would also accept signs
+  if s.len( ) % 2 != 0 || !s.bytes( ).all( |b| b.is_ascii_hexdigit( ) ) {
+    return None;
+  }
+  ( 0..s.len( ) ).step_by( 2 )
+    .map( |i| u8::from_str_radix( &s[ i..i + 2 ], 16 ).ok( ) )
+    .collect( )
+}
@@ -0,0 +1,216 @@
+//! Export to Stanford `.ply` files, as point clouds or meshes.
+//!
+//! Every vertex carries the RGBA color of its palette index. All positions are
+//! in world space, as placed by the scene graph. Instances on hidden layers are
+//! omitted.
+//!
+//! # Example: Export a point cloud
+//!
+//! ```
+//! use vox_parser::builder::{NodeBuilder, SceneBuilder};
+//! use vox_parser::formats::ply::{self, PlyContent, PlyEncoding, PlyOptions};
+//!
+//! let mut builder = SceneBuilder::new( );
+//! let model = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2) ] );
+//! builder.add( NodeBuilder::shape( model ) );
+//! let scene = builder.build( ).unwrap( );
+//!
+//! let options =
+//!   PlyOptions {
+//!     encoding: PlyEncoding::Ascii,
+//!     content:  PlyContent::Points,
+//!     ..PlyOptions::default( )
+//!   };
+//! let ply = String::from_utf8( ply::export( &scene, &options ) ).unwrap( );
+//! assert!( ply.contains( "element vertex 2\n" ) );
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::VoxScene;
+use crate::formats::{Pivot, pivot_point, to_output_axes, visible_instances};
+use crate::mesh::{self, Mesh};
+
+
+/// How the values are stored in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyEncoding {
+  /// Human-readable text, with one element per line.
+  Ascii,
+  /// Little-endian binary values. This is far more compact.
+  BinaryLittleEndian
+}
+
+/// What the exported file contains.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyContent {
+  /// A vertex at the center of every voxel, without faces.
+  Points,
+  /// A triangle mesh of the surface, with normals. (See
+  /// [`greedy`](crate::mesh::greedy))
+  Mesh
+}
+
+/// Options for [`export`].
+#[derive(Debug, Clone)]
+pub struct PlyOptions {
+  pub encoding : PlyEncoding,
+  pub content  : PlyContent,
+  /// The size of a single voxel in the output units.
+  pub scale    : f32,
+  /// The point of the scene which is placed at the origin.
+  pub pivot    : Pivot,
+  /// When set, the _y_ axis points up in the output. Otherwise, the _z_ axis
+  /// points up (as in `.vox` files).
+  pub y_up     : bool
+}
+
+impl Default for PlyOptions {
+  fn default( ) -> PlyOptions {
+    PlyOptions {
+      encoding: PlyEncoding::BinaryLittleEndian,
+      content:  PlyContent::Points,
+      scale:    1.0,
+      pivot:    Pivot::Origin,
+      y_up:     false
+    }
+  }
+}
+
+/// Internal. A vertex, as written to the file.
+struct Vertex {
+  position : [f32; 3],
+  normal   : Option< [f32; 3] >,
+  rgba     : (u8, u8, u8, u8)
+}
+
+/// Exports the visible instances of the scene as a `.ply` file.
+pub fn export( scene: &VoxScene, options: &PlyOptions ) -> Vec< u8 > {
+  let instances = visible_instances( scene );
+  let pivot = pivot_point( scene, &instances, options.pivot );
+
+  // Maps a world position to the output
+  let to_output = |(x, y, z): (f32, f32, f32)| {
+    to_output_axes( [x - pivot[ 0 ], y - pivot[ 1 ], z - pivot[ 2 ]], options.y_up )
+      .map( |c| c * options.scale )
+  };
+  let color = |palette_index: u8| scene.palette[ palette_index as usize - 1 ].rgba;
+
+  let mut vertices = Vec::new( );
+  let mut triangles: Vec< [u32; 3] > = Vec::new( );
+
+  match options.content {
+    PlyContent::Points =>
+      for inst in &instances {
+        let model = &scene.models[ inst.model_id as usize ];
+        for (x, y, z, i) in &model.xyzi {
+          let (wx, wy, wz) = inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) );
+          vertices.push(
+            Vertex {
+              position: to_output( (wx as f32 + 0.5, wy as f32 + 0.5, wz as f32 + 0.5) ),
+              normal:   None,
+              rgba:     color( *i )
+            }
+          );
+        }
+      },
+    PlyContent::Mesh => {
+      let mut meshes: HashMap< u32, Mesh > = HashMap::new( );
+
+      for inst in &instances {
+        let model = &scene.models[ inst.model_id as usize ];
+        let mesh = meshes.entry( inst.model_id ).or_insert_with( || mesh::greedy( model ) );
+        let base = vertices.len( ) as u32;
+
+        for ((p, n), i) in mesh.positions.iter( ).zip( &mesh.normals ).zip( &mesh.palette_indices ) {
+          let (nx, ny, nz) = inst.rotation.apply_to_f32( (n[ 0 ], n[ 1 ], n[ 2 ]) );
+          vertices.push(
+            Vertex {
+              position: to_output( inst.point_to_world( model, (p[ 0 ], p[ 1 ], p[ 2 ]) ) ),
+              normal:   Some( to_output_axes( [nx, ny, nz], options.y_up ) ),
+              rgba:     color( *i )
+            }
+          );
+        }
+
+        for t in mesh.indices.chunks( 3 ) {
+          // Mirroring instances reverse the winding order
+          if inst.rotation.is_mirror( ) {
+            triangles.push( [base + t[ 0 ], base + t[ 2 ], base + t[ 1 ]] );
+          } else {
+            triangles.push( [base + t[ 0 ], base + t[ 1 ], base + t[ 2 ]] );
+          }
+        }
+      }
+    }
+  }
+
+  let has_normals = options.content == PlyContent::Mesh;
+  let mut out = header( options, vertices.len( ), triangles.len( ) ).into_bytes( );
+
+  match options.encoding {
+    PlyEncoding::Ascii => {
+      let mut text = String::new( );
+      for v in &vertices {
+        let [x, y, z] = v.position;
+        text.push_str( &format!( "{} {} {}", x, y, z ) );
+        if let Some( [nx, ny, nz] ) = v.normal {
+          text.push_str( &format!( " {} {} {}", nx, ny, nz ) );
+        }
+        let (r, g, b, a) = v.rgba;
+        text.push_str( &format!( " {} {} {} {}\n", r, g, b, a ) );
+      }
+      for [a, b, c] in &triangles {
+        text.push_str( &format!( "3 {} {} {}\n", a, b, c ) );
+      }
+      out.extend( text.into_bytes( ) );
+    },
+    PlyEncoding::BinaryLittleEndian => {
+      for v in &vertices {
+        for c in &v.position {
+          out.extend( &c.to_le_bytes( ) );
+        }
+        if has_normals {
+          for c in &v.normal.unwrap_or( [0.0; 3] ) {
+            out.extend( &c.to_le_bytes( ) );
+          }
+        }
+        let (r, g, b, a) = v.rgba;
+        out.extend( &[r, g, b, a] );
+      }
+      for t in &triangles {
+        out.push( 3 );
+        for i in t {
+          out.extend( &i.to_le_bytes( ) );
+        }
+      }
+    }
+  }
+
+  out
+}
+
+/// Returns the header, which declares the elements and their properties.
+fn header( options: &PlyOptions, num_vertices: usize, num_faces: usize ) -> String {
+  let format =
+    match options.encoding {
+      PlyEncoding::Ascii              => "ascii",
+      PlyEncoding::BinaryLittleEndian => "binary_little_endian"
+    };
+
+  let mut out = format!( "ply\nformat {} 1.0\ncomment vox_parser\n", format );
+  out.push_str( &format!( "element vertex {}\n", num_vertices ) );
+  out.push_str( "property float x\nproperty float y\nproperty float z\n" );
+  if options.content == PlyContent::Mesh {
+    out.push_str( "property float nx\nproperty float ny\nproperty float nz\n" );
+  }
+  out.push_str( "property uchar red\nproperty uchar green\nproperty uchar blue\nproperty uchar alpha\n" );
+  if options.content == PlyContent::Mesh {
+    out.push_str( &format!( "element face {}\n", num_faces ) );
+    out.push_str( "property list uchar uint vertex_indices\n" );
+  }
+  out.push_str( "end_header\n" );
+  out
+}
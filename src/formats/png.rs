@@ -0,0 +1,232 @@
+//! Minimal PNG encoding and decoding, used for palette textures and image
+//! imports.
+
+
+// Local imports
+use crate::formats::{ExportError, ImportError};
+
+
+/// The most pixels in a decoded image, which take 1 GiB as RGBA.
+const MAX_PIXELS : u64 = 1 << 28;
+
+/// Encodes a non-interlaced 8-bit RGBA image as PNG. `pixels` contains the
+/// rows from top to bottom, with 4 bytes per pixel.
+///
+/// Returns [`ExportError::InvalidImage`] when the image is empty (as PNG
+/// cannot represent empty images), or when `pixels` does not contain exactly
+/// `width * height` pixels.
+pub fn write_rgba( width: u32, height: u32, pixels: &[u8] ) -> Result< Vec< u8 >, ExportError > {
+  let len = ( width as u64 * height as u64 ).checked_mul( 4 );
+  if width == 0 || height == 0 || len != Some( pixels.len( ) as u64 ) {
+    return Err( ExportError::InvalidImage );
+  }
+
+  let mut dst = Vec::new( );
+  dst.extend( b"\x89PNG\r\n\x1a\n" );
+
+  let mut header = Vec::with_capacity( 13 );
+  header.extend( &width.to_be_bytes( ) );
+  header.extend( &height.to_be_bytes( ) );
+  // bit depth 8, color type 6 (RGBA), default compression, filter, interlace
+  header.extend( &[8, 6, 0, 0, 0] );
+  chunk( &mut dst, b"IHDR", &header );
+
+  // Every row is prefixed by its filter type (0 = none)
+  let row_len = width as usize * 4;
+  let mut raw = Vec::with_capacity( ( row_len + 1 ) * height as usize );
+  for row in pixels.chunks( row_len ) {
+    raw.push( 0 );
+    raw.extend( row );
+  }
+  chunk( &mut dst, b"IDAT", &miniz_oxide::deflate::compress_to_vec_zlib( &raw, 6 ) );
+  chunk( &mut dst, b"IEND", &[] );
+
+  Ok( dst )
+}
+
+/// Decodes a non-interlaced PNG image of any color type and bit depth into
+/// 8-bit RGBA pixels. Returns the width, the height, and the rows from top to
+/// bottom, with 4 bytes per pixel.
+///
+/// Empty images, and images of more than 2^28 pixels, are rejected as
+/// invalid.
+pub fn read_rgba( input: &[u8] ) -> Result< (u32, u32, Vec< u8 >), ImportError > {
+  if !input.starts_with( b"\x89PNG\r\n\x1a\n" ) {
+    return Err( ImportError::InvalidHeader );
+  }
+
+  let mut header = None;
+  let mut palette: &[u8] = &[];
+  let mut transparency: &[u8] = &[];
+  let mut data = Vec::new( );
+
+  let mut input = &input[ 8.. ];
+  while input.len( ) >= 12 {
+    let len = u32::from_be_bytes( [input[ 0 ], input[ 1 ], input[ 2 ], input[ 3 ]] ) as usize;
+    if input.len( ) < 12 + len {
+      break;
+    }
+    let (tag, content) = ( &input[ 4..8 ], &input[ 8..8 + len ] );
+    match tag {
+      b"IHDR" if len == 13 => header = Some( content ),
+      b"PLTE" => palette = content,
+      b"tRNS" => transparency = content,
+      b"IDAT" => data.extend( content ),
+      b"IEND" => break,
+      _ => { }
+    }
+    input = &input[ 12 + len.. ];
+  }
+
+  let header = header.ok_or( ImportError::InvalidHeader )?;
+  let width = u32::from_be_bytes( [header[ 0 ], header[ 1 ], header[ 2 ], header[ 3 ]] );
+  let height = u32::from_be_bytes( [header[ 4 ], header[ 5 ], header[ 6 ], header[ 7 ]] );
+  let (depth, color_type, interlace) = ( header[ 8 ] as usize, header[ 9 ], header[ 12 ] );
+
+  let channels =
+    match color_type {
+      0 => 1,
+      2 => 3,
+      3 => 1,
+      4 => 2,
+      6 => 4,
+      _ => return Err( ImportError::InvalidHeader )
+    };
+  if interlace != 0 || ![1, 2, 4, 8, 16].contains( &depth ) || ( depth < 8 && channels > 1 ) {
+    return Err( ImportError::InvalidHeader );
+  }
+  if width == 0 || height == 0 || width as u64 * height as u64 > MAX_PIXELS {
+    return Err( ImportError::InvalidHeader );
+  }
+
+  // Every row is prefixed by its filter type. (Pixels take at most 8 bytes,
+  // so the rows of `MAX_PIXELS` fit any `usize`)
+  let bits_per_pixel = channels * depth;
+  let stride = ( ( width as u64 * bits_per_pixel as u64 + 7 ) / 8 ) as usize;
+  let raw_len = ( stride + 1 ) * height as usize;
+
+  let raw =
+    miniz_oxide::inflate::decompress_to_vec_zlib_with_limit( &data, raw_len )
+      .map_err( |_| ImportError::InvalidCompression )?;
+  if raw.len( ) < raw_len {
+    return Err( ImportError::InvalidCompression );
+  }
+  let rows = unfilter( &raw, stride, height as usize, ( bits_per_pixel / 8 ).max( 1 ) )?;
+
+  // The raw value of sample `i` in the row
+  let sample = |row: &[u8], i: usize| -> u16 {
+    match depth {
+      16 => u16::from_be_bytes( [row[ 2 * i ], row[ 2 * i + 1 ]] ),
+      8  => row[ i ] as u16,
+      _  => ( ( row[ i * depth / 8 ] >> ( 8 - depth - ( i * depth ) % 8 ) ) & ( ( 1 << depth ) - 1 ) ) as u16
+    }
+  };
+  // Scales a raw value to 8 bits
+  let scale = |v: u16| -> u8 {
+    match depth {
+      16 => ( v >> 8 ) as u8,
+      _  => ( v as u32 * 255 / ( ( 1 << depth ) - 1 ) ) as u8
+    }
+  };
+  // The raw sample values that are transparent for gray and RGB images
+  let transparent: Vec< u16 > =
+    transparency.chunks( 2 ).map( |c| u16::from_be_bytes( [c[ 0 ], *c.get( 1 ).unwrap_or( &0 )] ) ).collect( );
+
+  let mut pixels = Vec::with_capacity( width as usize * height as usize * 4 );
+  for row in rows.chunks( stride ) {
+    for x in 0..width as usize {
+      let s: Vec< u16 > = ( 0..channels ).map( |c| sample( row, x * channels + c ) ).collect( );
+      let rgba =
+        match color_type {
+          0 => {
+            let g = scale( s[ 0 ] );
+            [g, g, g, if transparent.first( ) == Some( &s[ 0 ] ) { 0 } else { 255 }]
+          },
+          2 => {
+            let a = if transparent.len( ) == 3 && transparent[ .. ] == s[ .. ] { 0 } else { 255 };
+            [scale( s[ 0 ] ), scale( s[ 1 ] ), scale( s[ 2 ] ), a]
+          },
+          3 => {
+            let i = s[ 0 ] as usize;
+            let c = palette.get( 3 * i..3 * i + 3 ).ok_or( ImportError::InvalidHeader )?;
+            [c[ 0 ], c[ 1 ], c[ 2 ], *transparency.get( i ).unwrap_or( &255 )]
+          },
+          4 => {
+            let g = scale( s[ 0 ] );
+            [g, g, g, scale( s[ 1 ] )]
+          },
+          _ => [scale( s[ 0 ] ), scale( s[ 1 ] ), scale( s[ 2 ] ), scale( s[ 3 ] )]
+        };
+      pixels.extend( &rgba );
+    }
+  }
+
+  Ok( (width, height, pixels) )
+}
+
+/// Reverses the filter of every row, and returns the rows without their filter
+/// type. `bpp` is the number of bytes per pixel, rounded up.
+fn unfilter( raw: &[u8], stride: usize, height: usize, bpp: usize ) -> Result< Vec< u8 >, ImportError > {
+  let mut rows = vec![ 0u8; stride * height ];
+
+  for y in 0..height {
+    let filter = raw[ y * ( stride + 1 ) ];
+    let line = &raw[ y * ( stride + 1 ) + 1..( y + 1 ) * ( stride + 1 ) ];
+    let (prev, cur) = rows.split_at_mut( y * stride );
+    let prev: &[u8] = if y > 0 { &prev[ ( y - 1 ) * stride.. ] } else { &[] };
+    let cur = &mut cur[ ..stride ];
+
+    for x in 0..stride {
+      let a = if x >= bpp { cur[ x - bpp ] } else { 0 };
+      let b = prev.get( x ).copied( ).unwrap_or( 0 );
+      let c = if x >= bpp { prev.get( x - bpp ).copied( ).unwrap_or( 0 ) } else { 0 };
+      let predictor =
+        match filter {
+          0 => 0,
+          1 => a,
+          2 => b,
+          3 => ( ( a as u16 + b as u16 ) / 2 ) as u8,
+          4 => paeth( a, b, c ),
+          _ => return Err( ImportError::InvalidCompression )
+        };
+      cur[ x ] = line[ x ].wrapping_add( predictor );
+    }
+  }
+
+  Ok( rows )
+}
+
+/// The Paeth predictor, which picks the neighbour closest to `a + b - c`.
+fn paeth( a: u8, b: u8, c: u8 ) -> u8 {
+  let p = a as i16 + b as i16 - c as i16;
+  let (pa, pb, pc) = ( ( p - a as i16 ).abs( ), ( p - b as i16 ).abs( ), ( p - c as i16 ).abs( ) );
+  if pa <= pb && pa <= pc {
+    a
+  } else if pb <= pc {
+    b
+  } else {
+    c
+  }
+}
+
+/// Writes a PNG chunk, which is followed by the CRC of its tag and data.
+fn chunk( dst: &mut Vec< u8 >, tag: &[u8; 4], data: &[u8] ) {
+  dst.extend( &( data.len( ) as u32 ).to_be_bytes( ) );
+  let start = dst.len( );
+  dst.extend( tag );
+  dst.extend( data );
+  let crc = crc32( &dst[ start.. ] );
+  dst.extend( &crc.to_be_bytes( ) );
+}
+
+/// Computes the CRC-32 (ISO-HDLC) checksum, as used by PNG and gzip.
+pub fn crc32( data: &[u8] ) -> u32 {
+  let mut crc = 0xFFFFFFFFu32;
+  for b in data {
+    crc ^= *b as u32;
+    for _i in 0..8 {
+      crc = if crc & 1 != 0 { ( crc >> 1 ) ^ 0xEDB88320 } else { crc >> 1 };
+    }
+  }
+  !crc
+}
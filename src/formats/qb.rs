@@ -0,0 +1,415 @@
+//! Conversion to and from Qubicle binary (`.qb`) files.
+//!
+//! A `.qb` file contains named _matrices_, which are voxel volumes with an
+//! offset in the world. On import, every matrix becomes a named shape node in
+//! the scene graph. Qubicle stores true colors, which are quantized into the
+//! palette. On export, every visible instance (or every layer) becomes a
+//! matrix, with the colors of its palette indices.
+//!
+//! Qubicle's _y_ axis points up, whereas the _z_ axis points up in `.vox`
+//! files. The axes are swapped accordingly, such that models keep their
+//! handedness.
+//!
+//! # Example: Convert a `.qb` file to `.vox`
+//!
+//! ```no_run
+//! let content = std::fs::read( "input.qb" ).unwrap( );
+//! let scene = vox_parser::formats::qb::import( &content ).unwrap( );
+//! std::fs::write( "output.vox", vox_parser::unparse::file_custom( &scene ) ).unwrap( );
+//! ```
+//!
+//! # Example: Export a scene with one matrix per layer
+//!
+//! ```no_run
+//! use vox_parser::formats::qb::{self, QbOptions};
+//!
+//! let content = std::fs::read( "input.vox" ).unwrap( );
+//! let scene = vox_parser::parse::file_custom( &content ).unwrap( );
+//!
+//! let options = QbOptions { merge_layers: true, ..QbOptions::default( ) };
+//! std::fs::write( "output.qb", qb::export( &scene, &options ) ).unwrap( );
+//! ```
+
+
+// Stdlib imports
+use std::collections::{HashMap, HashSet};
+// External library imports
+use nom::bytes::complete::take;
+use nom::number::complete::{le_i32, le_u32, le_u8};
+use nom::sequence::tuple;
+// Local imports
+use crate::builder::SceneBuilder;
+use crate::data::custom::{NodeType, SceneNode, VoxScene};
+use crate::formats::{ImportError, build_scene, place_voxels};
+use crate::palette::{self, Rgba};
+use crate::spatial::Aabb;
+
+
+/// Flag in compressed data, which precedes a run of `count` equal colors.
+const CODEFLAG      : u32 = 2;
+/// Flag in compressed data, which ends the current _z_ slice.
+const NEXTSLICEFLAG : u32 = 6;
+/// The largest number of voxels in a single matrix (e.g., 512x512x512) that is
+/// imported. Compressed matrices are tiny in the file, but not in memory.
+const MAX_VOLUME    : u64 = 1 << 27;
+
+/// Options for [`export`].
+#[derive(Debug, Clone)]
+pub struct QbOptions {
+  /// When set, the matrices are run-length encoded. This is far more compact
+  /// for sparse models.
+  pub compressed   : bool,
+  /// When set, all instances on the same layer are merged into a single
+  /// matrix, which is named after the layer. Otherwise, every instance becomes
+  /// a matrix, which is named after its node.
+  pub merge_layers : bool
+}
+
+impl Default for QbOptions {
+  fn default( ) -> QbOptions {
+    QbOptions {
+      compressed:   true,
+      merge_layers: false
+    }
+  }
+}
+
+/// Internal. A matrix as read from the file, in Qubicle's coordinates.
+struct Matrix {
+  name     : String,
+  size     : (u32, u32, u32),
+  position : (i32, i32, i32),
+  /// The non-empty voxels with their colors
+  voxels   : Vec< ((u32, u32, u32), Rgba) >
+}
+
+/// Internal. The global settings in the file header.
+struct Header {
+  is_bgra          : bool,
+  is_right_handed  : bool,
+  is_compressed    : bool,
+  num_matrices     : u32
+}
+
+/// Converts a `.qb` file into a scene.
+///
+/// Both raw and RLE-compressed matrices are supported. Voxels are empty when
+/// their alpha value is 0; Otherwise, they are opaque. (Qubicle stores a
+/// visibility mask in the alpha value, rather than transparency)
+///
+/// Matrices of more than 2^27 voxels, or which extend beyond the range of
+/// `i32` coordinates, are rejected as [`ImportError::InvalidHeader`].
+pub fn import( input: &[u8] ) -> Result< VoxScene, ImportError > {
+  let (mut input, header) = header( input )?;
+
+  let mut matrices = Vec::new( );
+  for _ in 0..header.num_matrices {
+    let (rest, m) = matrix( &header, input )?;
+    matrices.push( m );
+    input = rest;
+  }
+
+  let q = palette::quantize( matrices.iter( ).flat_map( |m| m.voxels.iter( ).map( |v| v.1 ) ) );
+
+  let mut builder = SceneBuilder::new( );
+  for (i, c) in &q.entries {
+    builder.color( *i, *c );
+  }
+
+  for m in &matrices {
+    let (_, _, z_size) = m.size;
+    let (px, py, pz) = m.position;
+
+    // Map Qubicle's y-up coordinates to z-up. For right-handed matrices, the
+    // new y axis is mirrored. (Which cannot overflow, as checked by `matrix`)
+    let to_vox = |(x, y, z): (u32, u32, u32)| {
+      if header.is_right_handed { (x, z_size - 1 - z, y) } else { (x, z, y) }
+    };
+    let min =
+      if header.is_right_handed {
+        (px, ( -( pz as i64 ) - z_size as i64 ) as i32, py)
+      } else {
+        (px, pz, py)
+      };
+
+    let voxels: Vec< ((u32, u32, u32), u8) > =
+      m.voxels.iter( )
+        .map( |(pos, c)| (to_vox( *pos ), q.index_of( *c ).unwrap_or( 1 )) )
+        .collect( );
+
+    let node = place_voxels( &mut builder, min, &voxels ).name( &m.name );
+    builder.add( node );
+  }
+
+  Ok( build_scene( builder ) )
+}
+
+/// Exports the visible instances of the scene as a `.qb` file. Instances on
+/// hidden layers are omitted.
+///
+/// Every matrix is placed at its position in the world, as given by the scene
+/// graph. Matrices without a name in the scene are named after their model (or
+/// layer). Voxels are exported as opaque, regardless of their material.
+pub fn export( scene: &VoxScene, options: &QbOptions ) -> Vec< u8 > {
+  let mut names = Vec::new( );
+  shape_names( &scene.graph, None, &mut names );
+
+  // The instances are in the same order as their names
+  let instances: Vec< _ > =
+    scene.instances( ).into_iter( ).zip( names )
+      .filter( |(i, _)| !scene.is_hidden( i ) )
+      .collect( );
+
+  // The matrices map world positions to palette indices
+  let mut matrices = Vec::new( );
+  let mut layer_matrix: HashMap< Option< u32 >, usize > = HashMap::new( );
+
+  for (inst, name) in &instances {
+    let model = &scene.models[ inst.model_id as usize ];
+
+    let index =
+      if options.merge_layers {
+        *layer_matrix.entry( inst.layer_id ).or_insert_with( || {
+          let name =
+            match inst.layer_id.and_then( |l| scene.layers.get( l as usize ) ) {
+              Some( layer ) if !layer.name.is_empty( ) => layer.name.clone( ),
+              _ => inst.layer_id.map_or( "Default".to_string( ), |l| format!( "Layer {}", l ) )
+            };
+          matrices.push( (name, HashMap::new( )) );
+          matrices.len( ) - 1
+        } )
+      } else {
+        let name = name.clone( ).unwrap_or_else( || format!( "Model {}", inst.model_id ) );
+        matrices.push( (name, HashMap::new( )) );
+        matrices.len( ) - 1
+      };
+
+    // When instances overlap, the first one is kept
+    for (x, y, z, i) in &model.xyzi {
+      let pos = inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) );
+      matrices[ index ].1.entry( pos ).or_insert( *i );
+    }
+  }
+
+  let mut out = Vec::new( );
+  // Version 1.1, RGBA, left-handed, no visibility mask
+  for v in &[0x0000_0101, 0, 0, options.compressed as u32, 0, matrices.len( ) as u32] {
+    out.extend( &u32::to_le_bytes( *v ) );
+  }
+
+  let mut used_names = HashSet::new( );
+  for (name, voxels) in &matrices {
+    let name = unique_name( name, &mut used_names );
+    out.push( name.len( ) as u8 );
+    out.extend( name.as_bytes( ) );
+
+    // Swap the y and z axes, which mirrors the matrix into Qubicle's
+    // left-handed coordinates
+    let (min, (x_size, y_size, z_size)) =
+      match Aabb::enclosing( voxels.keys( ).copied( ) ) {
+        Some( b ) => {
+          let (x_size, y_size, z_size) = b.size( );
+          ((b.min.0, b.min.2, b.min.1), (x_size, z_size, y_size))
+        },
+        None      => ((0, 0, 0), (1, 1, 1))
+      };
+    for v in &[x_size, y_size, z_size] {
+      out.extend( &v.to_le_bytes( ) );
+    }
+    for v in &[min.0, min.1, min.2] {
+      out.extend( &v.to_le_bytes( ) );
+    }
+
+    let (x_len, y_len, z_len) = ( x_size as usize, y_size as usize, z_size as usize );
+    let mut data = vec![ 0u32; x_len * y_len * z_len ];
+    for ((x, y, z), i) in voxels {
+      let (qx, qy, qz) = ( ( x - min.0 ) as usize, ( z - min.1 ) as usize, ( y - min.2 ) as usize );
+      let (r, g, b, _) = scene.palette[ *i as usize - 1 ].rgba;
+      data[ qx + x_len * ( qy + y_len * qz ) ] = u32::from_le_bytes( [r, g, b, 255] );
+    }
+
+    if options.compressed {
+      for slice in data.chunks( x_len * y_len ) {
+        compress_slice( &mut out, slice );
+      }
+    } else {
+      for v in &data {
+        out.extend( &v.to_le_bytes( ) );
+      }
+    }
+  }
+
+  out
+}
+
+/// Appends the run-length encoding of a single _z_ slice of a matrix.
+fn compress_slice( out: &mut Vec< u8 >, slice: &[u32] ) {
+  let mut i = 0;
+  while i < slice.len( ) {
+    let run = slice[ i.. ].iter( ).take_while( |v| **v == slice[ i ] ).count( );
+    // Single values are stored as is, which does not clash with the flags; As
+    // their alpha value is either 0 or 255.
+    if run == 1 {
+      out.extend( &slice[ i ].to_le_bytes( ) );
+    } else {
+      for v in &[CODEFLAG, run as u32, slice[ i ]] {
+        out.extend( &v.to_le_bytes( ) );
+      }
+    }
+    i += run;
+  }
+  out.extend( &NEXTSLICEFLAG.to_le_bytes( ) );
+}
+
+/// Appends the name of every shape node, in the order of
+/// [`VoxScene::instances`]. A shape node without a name takes the name of its
+/// closest named ancestor.
+fn shape_names( node: &SceneNode, parent: Option< &str >, dst: &mut Vec< Option< String > > ) {
+  let name = node.name.as_deref( ).or( parent );
+  match &node.node_type {
+    NodeType::Group( children ) =>
+      for c in children {
+        shape_names( c, name, dst );
+      },
+    NodeType::Shape( _ ) =>
+      dst.push( name.map( str::to_string ) )
+  }
+}
+
+/// Returns the name (of at most 255 bytes) with a numbered suffix if it is
+/// already in use, and marks the result as used. Qubicle requires unique names.
+fn unique_name( name: &str, used: &mut HashSet< String > ) -> String {
+  let truncate = |s: &str, len: usize| {
+    let mut end = s.len( ).min( len );
+    while !s.is_char_boundary( end ) {
+      end -= 1;
+    }
+    s[ ..end ].to_string( )
+  };
+
+  let mut result = truncate( name, 255 );
+  let mut n = 2;
+  while used.contains( &result ) {
+    let suffix = format!( " {}", n );
+    result = truncate( name, 255 - suffix.len( ) ) + &suffix;
+    n += 1;
+  }
+  used.insert( result.clone( ) );
+  result
+}
+
+/// Parses the file header.
+fn header( input: &[u8] ) -> Result< (&[u8], Header), ImportError > {
+  let (input, (version, color_format, z_orientation, compressed, _mask_encoded, num_matrices)) =
+    tuple( (le_u32, le_u32, le_u32, le_u32, le_u32, le_u32) )( input )?;
+
+  // The version is stored as the bytes `1 1 0 0`
+  if version & 0xFF != 1 {
+    return Err( ImportError::UnknownVersion( version ) );
+  }
+  if color_format > 1 || z_orientation > 1 || compressed > 1 {
+    return Err( ImportError::InvalidHeader );
+  }
+
+  let header =
+    Header {
+      is_bgra:         color_format == 1,
+      is_right_handed: z_orientation == 1,
+      is_compressed:   compressed == 1,
+      num_matrices
+    };
+  Ok( (input, header) )
+}
+
+/// Parses a single matrix, with its voxel data.
+fn matrix< 'a >( header: &Header, input: &'a [u8] ) -> Result< (&'a [u8], Matrix), ImportError > {
+  let (input, name_len) = le_u8( input )?;
+  let (input, name) = take( name_len )( input )?;
+  let (mut input, (x_size, y_size, z_size, px, py, pz)) =
+    tuple( (le_u32, le_u32, le_u32, le_i32, le_i32, le_i32) )( input )?;
+
+  // The matrix (also when mirrored) must stay within `i32` coordinates
+  let fits = |p: i64, size: u32| p >= i32::MIN as i64 && p + size as i64 <= i32::MAX as i64;
+  let mirrored_z = -( pz as i64 ) - z_size as i64;
+  let slice_len = x_size as u64 * y_size as u64;
+  if slice_len * z_size as u64 > MAX_VOLUME
+      || !fits( px as i64, x_size ) || !fits( py as i64, y_size ) || !fits( pz as i64, z_size )
+      || ( header.is_right_handed && !fits( mirrored_z, z_size ) ) {
+    return Err( ImportError::InvalidHeader );
+  }
+  // Uncompressed matrices store every voxel; Compressed ones at least a flag
+  // per slice. Check before looping, as the sizes need not match the file.
+  let min_len = if header.is_compressed { z_size as u64 } else { slice_len * z_size as u64 };
+  if min_len * 4 > input.len( ) as u64 {
+    return Err( ImportError::Nom( nom::error::ErrorKind::Eof ) );
+  }
+  // This fits, as the volume is bounded
+  let slice_len = slice_len as u32;
+
+  let mut voxels = Vec::new( );
+  let mut push = |index: u32, z: u32, data: u32| {
+    let c = color( header, data );
+    if c.3 != 0 {
+      voxels.push( ((index % x_size, index / x_size, z), c) );
+    }
+  };
+
+  if header.is_compressed {
+    for z in 0..z_size {
+      let mut index = 0;
+      loop {
+        let (rest, data) = le_u32( input )?;
+        input = rest;
+
+        if data == NEXTSLICEFLAG {
+          break;
+        }
+        let (count, data) =
+          if data == CODEFLAG {
+            let (rest, run) = tuple( (le_u32, le_u32) )( input )?;
+            input = rest;
+            run
+          } else {
+            (1, data)
+          };
+        // Runs may not extend beyond the slice
+        if count > slice_len - index {
+          return Err( ImportError::InvalidCompression );
+        }
+        for i in index..index + count {
+          push( i, z, data );
+        }
+        index += count;
+      }
+    }
+  } else {
+    for z in 0..z_size {
+      for index in 0..slice_len {
+        let (rest, data) = le_u32( input )?;
+        input = rest;
+        push( index, z, data );
+      }
+    }
+  }
+
+  let m =
+    Matrix {
+      name:     String::from_utf8_lossy( name ).into_owned( ),
+      size:     (x_size, y_size, z_size),
+      position: (px, py, pz),
+      voxels
+    };
+  Ok( (input, m) )
+}
+
+/// Extracts the RGBA color from the little-endian value, as stored in the
+/// file. The alpha value is 0 (empty) or 255 (opaque).
+fn color( header: &Header, data: u32 ) -> Rgba {
+  let [b0, b1, b2, b3] = data.to_le_bytes( );
+  let a = if b3 == 0 { 0 } else { 255 };
+  if header.is_bgra {
+    (b2, b1, b0, a)
+  } else {
+    (b0, b1, b2, a)
+  }
+}
@@ -0,0 +1,69 @@
+//! Conversion from a stack of image slices.
+//!
+//! Every image is a horizontal slice of the model, where the first image is
+//! the bottom. Seen from above, the top row of an image is the far end (the
+//! positive _y_ axis). Transparent pixels are empty, and all other pixels are
+//! opaque voxels, whose colors are quantized into the palette.
+//!
+//! # Example: Stack two slices
+//!
+//! ```
+//! use vox_parser::formats::image::Image;
+//! use vox_parser::formats::slices;
+//!
+//! // A red 2x1 bottom slice, and a single blue voxel on top
+//! let bottom = Image { width: 2, height: 1, pixels: vec![ 255,0,0,255, 255,0,0,255 ] };
+//! let top = Image { width: 2, height: 1, pixels: vec![ 0,0,255,255, 0,0,0,0 ] };
+//!
+//! let scene = slices::import( &[bottom, top] );
+//! assert_eq!( scene.models[ 0 ].size, (2, 1, 2) );
+//! assert_eq!( scene.models[ 0 ].xyzi.len( ), 3 );
+//! ```
+
+
+// Local imports
+use crate::builder::SceneBuilder;
+use crate::data::custom::VoxScene;
+use crate::formats::image::Image;
+use crate::formats::{build_scene, place_voxels};
+use crate::palette;
+
+
+/// Converts the slices (from bottom to top) into a scene. The colors of all
+/// slices are quantized into the palette. (See [`palette::quantize`])
+///
+/// When the slices fit in 256 voxels along every axis, the scene contains a
+/// single model, which spans all slices. Otherwise, it is split into multiple
+/// models. Slices of different sizes are aligned at their top-left pixel.
+pub fn import( slices: &[Image] ) -> VoxScene {
+  let height = slices.iter( ).map( |s| s.height ).max( ).unwrap_or( 0 );
+
+  // The non-transparent pixels as voxels, with their colors
+  let mut pixels = Vec::new( );
+  for (z, slice) in slices.iter( ).enumerate( ) {
+    for y in 0..slice.height {
+      for x in 0..slice.width {
+        let (r, g, b, a) = slice.pixel( x, y );
+        if a != 0 {
+          pixels.push( ((x, height - 1 - y, z as u32), (r, g, b, 255)) );
+        }
+      }
+    }
+  }
+
+  let q = palette::quantize( pixels.iter( ).map( |p| p.1 ) );
+
+  let mut builder = SceneBuilder::new( );
+  for (i, c) in &q.entries {
+    builder.color( *i, *c );
+  }
+
+  let voxels: Vec< ((u32,u32,u32), u8) > =
+    pixels.iter( ).map( |(pos, c)| (*pos, q.index_of( *c ).unwrap_or( 1 )) ).collect( );
+  if !voxels.is_empty( ) {
+    let node = place_voxels( &mut builder, (0, 0, 0), &voxels );
+    builder.add( node );
+  }
+
+  build_scene( builder )
+}
@@ -0,0 +1,234 @@
+//! Export to `.stl` files, for 3D printing.
+//!
+//! All exported voxels are merged into a single watertight solid: Faces
+//! between adjacent voxels are removed (also across instances), and every
+//! remaining voxel face becomes two triangles. (Faces are not merged, as that
+//! would introduce T-junctions, which slicers handle poorly) The normals point
+//! outward, and the triangles are counter-clockwise when viewed from outside.
+//!
+//! Positions are in millimetres, with the _z_ axis pointing up.
+//!
+//! # Example: Export a figurine
+//!
+//! ```no_run
+//! use vox_parser::formats::stl::{self, StlOptions};
+//!
+//! let content = std::fs::read( "figurine.vox" ).unwrap( );
+//! let scene = vox_parser::parse::file_custom( &content ).unwrap( );
+//!
+//! let options = StlOptions { voxel_size: 0.5, ..StlOptions::default( ) };
+//! std::fs::write( "figurine.stl", stl::export( &scene, &options ) ).unwrap( );
+//! ```
+
+
+// Stdlib imports
+use std::collections::{HashMap, HashSet};
+// Local imports
+use crate::data::custom::{Model, VoxScene};
+use crate::formats::{Pivot, bounds_pivot};
+use crate::mesh::Mesh;
+use crate::spatial::Aabb;
+use crate::storage::{DenseGrid, VoxelStorage};
+
+
+/// How the triangles are stored in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StlEncoding {
+  /// Human-readable text.
+  Ascii,
+  /// The compact binary format, which most slicers prefer.
+  Binary
+}
+
+/// Options for [`export`] and [`export_model`].
+#[derive(Debug, Clone)]
+pub struct StlOptions {
+  pub encoding        : StlEncoding,
+  /// The edge length of a single voxel in millimetres.
+  pub voxel_size      : f32,
+  /// The point of the voxels which is placed at the origin. By default, the
+  /// lowest corner is; So, the solid is on the build plate.
+  pub pivot           : Pivot,
+  /// When set, only faces that are reachable from outside the solid are
+  /// exported. Enclosed cavities are then filled, which avoids internal
+  /// shells that confuse slicers.
+  pub exterior_only   : bool,
+  /// When set, only instances on these layers are exported. (Ignored by
+  /// [`export_model`])
+  pub layers          : Option< Vec< u32 > >,
+  /// When set, only voxels with these palette indices are exported.
+  pub palette_indices : Option< Vec< u8 > >
+}
+
+impl Default for StlOptions {
+  fn default( ) -> StlOptions {
+    StlOptions {
+      encoding:        StlEncoding::Binary,
+      voxel_size:      1.0,
+      pivot:           Pivot::Corner,
+      exterior_only:   true,
+      layers:          None,
+      palette_indices: None
+    }
+  }
+}
+
+/// Exports the visible instances of the scene as a single solid. Instances on
+/// hidden layers are omitted.
+pub fn export( scene: &VoxScene, options: &StlOptions ) -> Vec< u8 > {
+  let mut voxels = HashMap::new( );
+
+  for inst in scene.instances( ) {
+    if scene.is_hidden( &inst ) {
+      continue;
+    }
+    if let Some( layers ) = &options.layers {
+      if !inst.layer_id.is_some_and( |l| layers.contains( &l ) ) {
+        continue;
+      }
+    }
+
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, i) in &model.xyzi {
+      voxels.insert( inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) ), *i );
+    }
+  }
+
+  write( voxels, options )
+}
+
+/// Exports the voxels of a single model as a solid.
+pub fn export_model( model: &Model, options: &StlOptions ) -> Vec< u8 > {
+  let voxels =
+    model.xyzi.iter( )
+      .map( |(x, y, z, i)| ((*x as i32, *y as i32, *z as i32), *i) )
+      .collect( );
+  write( voxels, options )
+}
+
+/// Writes the voxels (which map positions to palette indices) as a solid.
+fn write( mut voxels: HashMap< (i32,i32,i32), u8 >, options: &StlOptions ) -> Vec< u8 > {
+  if let Some( palette_indices ) = &options.palette_indices {
+    voxels.retain( |_, i| palette_indices.contains( i ) );
+  }
+
+  let bounds = Aabb::enclosing( voxels.keys( ).copied( ) );
+  let mesh = bounds.map_or_else( Mesh::default, |b| solid( &voxels, b, options.exterior_only ) );
+
+  let pivot = bounds_pivot( bounds, options.pivot );
+  let triangles: Vec< ([f32; 3], [[f32; 3]; 3]) > =
+    mesh.indices.chunks( 3 )
+      .map( |t| {
+        let corners =
+          [t[ 0 ], t[ 1 ], t[ 2 ]].map( |i| {
+            let p = mesh.positions[ i as usize ];
+            [0, 1, 2].map( |a| ( p[ a ] - pivot[ a ] ) * options.voxel_size )
+          } );
+        (mesh.normals[ t[ 0 ] as usize ], corners)
+      } )
+      .collect( );
+
+  match options.encoding {
+    StlEncoding::Ascii  => ascii( &triangles ),
+    StlEncoding::Binary => binary( &triangles )
+  }
+}
+
+/// Returns the mesh of the voxels' faces, in world space. With `exterior_only`,
+/// only faces reachable from outside the bounds are included.
+fn solid( voxels: &HashMap< (i32,i32,i32), u8 >, bounds: Aabb, exterior_only: bool ) -> Mesh {
+  // The grid has an empty border around the voxels, through which the
+  // outside is connected
+  let (x_size, y_size, z_size) = bounds.size( );
+  let mut grid = DenseGrid::new( (x_size + 2, y_size + 2, z_size + 2) );
+  let offset = ( bounds.min.0 - 1, bounds.min.1 - 1, bounds.min.2 - 1 );
+  for ((x, y, z), i) in voxels {
+    grid.set( (x - offset.0, y - offset.1, z - offset.2), *i );
+  }
+
+  let outside = if exterior_only { Some( outside( &grid ) ) } else { None };
+  let is_open = |p: [i32; 3]| {
+    let pos = (p[ 0 ], p[ 1 ], p[ 2 ]);
+    match &outside {
+      Some( outside ) => outside.contains( &pos ),
+      None            => grid.get( pos ).unwrap_or( 0 ) == 0
+    }
+  };
+
+  let mut mesh = Mesh::default( );
+  for ((x, y, z), palette_index) in grid.voxels( ) {
+    let pos = [x, y, z];
+
+    for axis in 0..3 {
+      for &is_positive in &[false, true] {
+        let mut neighbour = pos;
+        neighbour[ axis ] += if is_positive { 1 } else { -1 };
+
+        if is_open( neighbour ) {
+          let mut min = [x + offset.0, y + offset.1, z + offset.2];
+          if is_positive {
+            min[ axis ] += 1;
+          }
+          mesh.push_quad( min, axis, is_positive, (1, 1), palette_index, [[3; 2]; 2] );
+        }
+      }
+    }
+  }
+
+  mesh
+}
+
+/// Returns the empty positions in the grid that are connected to its corner,
+/// which is outside the voxels.
+fn outside( grid: &DenseGrid ) -> HashSet< (i32,i32,i32) > {
+  let mut visited = HashSet::new( );
+  let mut stack = vec![ (0, 0, 0) ];
+  visited.insert( (0, 0, 0) );
+
+  while let Some( (x, y, z) ) = stack.pop( ) {
+    let neighbours =
+      [ (x - 1, y, z), (x + 1, y, z), (x, y - 1, z), (x, y + 1, z), (x, y, z - 1), (x, y, z + 1) ];
+    for n in &neighbours {
+      if grid.get( *n ) == Some( 0 ) && visited.insert( *n ) {
+        stack.push( *n );
+      }
+    }
+  }
+
+  visited
+}
+
+/// Encodes the triangles (with their normals) as an ASCII STL file.
+fn ascii( triangles: &[([f32; 3], [[f32; 3]; 3])] ) -> Vec< u8 > {
+  let mut out = String::from( "solid vox\n" );
+  for ([nx, ny, nz], corners) in triangles {
+    out.push_str( &format!( "facet normal {} {} {}\n  outer loop\n", nx, ny, nz ) );
+    for [x, y, z] in corners {
+      out.push_str( &format!( "    vertex {} {} {}\n", x, y, z ) );
+    }
+    out.push_str( "  endloop\nendfacet\n" );
+  }
+  out.push_str( "endsolid vox\n" );
+  out.into_bytes( )
+}
+
+/// Encodes the triangles (with their normals) as a binary STL file.
+fn binary( triangles: &[([f32; 3], [[f32; 3]; 3])] ) -> Vec< u8 > {
+  let mut out = Vec::with_capacity( 84 + 50 * triangles.len( ) );
+
+  // The header must not start with "solid", as it would be mistaken for ASCII
+  let mut header = [b' '; 80];
+  header[ ..10 ].copy_from_slice( b"vox_parser" );
+  out.extend( &header );
+  out.extend( &( triangles.len( ) as u32 ).to_le_bytes( ) );
+
+  for (normal, corners) in triangles {
+    for v in normal.iter( ).chain( corners.iter( ).flatten( ) ) {
+      out.extend( &v.to_le_bytes( ) );
+    }
+    // Attribute byte count, which is unused
+    out.extend( &[0, 0] );
+  }
+
+  out
+}
@@ -0,0 +1,128 @@
+//! Conversion to and from plain-text voxel lists.
+//!
+//! Every line contains a single voxel as `x y z RRGGBB`, where the color is
+//! hexadecimal. Lines starting with `#` are comments. Goxel (among others)
+//! exports this format, and it is convenient for readable test fixtures.
+//!
+//! # Example: Read and write a voxel list
+//!
+//! ```
+//! use vox_parser::formats::txt;
+//!
+//! let input = "# X Y Z RRGGBB\n0 0 0 ff0000\n1 0 0 0000ff\n-1 2 0 ff0000\n";
+//! let scene = txt::import( input ).unwrap( );
+//! assert_eq!( scene.palette[ 0 ].rgba, (0, 0, 255, 255) );
+//!
+//! let output = txt::export( &scene );
+//! assert!( output.contains( "\n-1 2 0 ff0000\n" ) );
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::builder::SceneBuilder;
+use crate::data::custom::{Material, Model, VoxScene};
+use crate::formats::{ImportError, build_scene, place_voxels, visible_instances};
+use crate::palette::{self, Rgba};
+
+
+/// Converts a voxel list into a scene. The voxels keep their coordinates in
+/// the world, and their colors are quantized into the palette. (See
+/// [`palette::quantize`])
+///
+/// When a position occurs multiple times, the last voxel is kept.
+pub fn import( input: &str ) -> Result< VoxScene, ImportError > {
+  let mut voxels = HashMap::new( );
+  for (i, line) in input.lines( ).enumerate( ) {
+    let line = line.trim( );
+    if line.is_empty( ) || line.starts_with( '#' ) {
+      continue;
+    }
+    let (pos, rgba) = parse_line( line ).ok_or( ImportError::InvalidLine( i + 1 ) )?;
+    voxels.insert( pos, rgba );
+  }
+
+  let q = palette::quantize( voxels.values( ).copied( ) );
+
+  let mut builder = SceneBuilder::new( );
+  for (i, c) in &q.entries {
+    builder.color( *i, *c );
+  }
+
+  let min =
+    voxels.keys( ).fold( (i32::MAX, i32::MAX, i32::MAX), |m, (x, y, z)| {
+      ( m.0.min( *x ), m.1.min( *y ), m.2.min( *z ) )
+    } );
+  let mut local: Vec< ((u32,u32,u32), u8) > =
+    voxels.iter( )
+      .map( |((x, y, z), c)| {
+        // The span of `i32` coordinates always fits in `u32`
+        let offset = |v: i32, min: i32| ( v as i64 - min as i64 ) as u32;
+        let pos = ( offset( *x, min.0 ), offset( *y, min.1 ), offset( *z, min.2 ) );
+        (pos, q.index_of( *c ).unwrap_or( 1 ))
+      } )
+      .collect( );
+  local.sort( );
+
+  if !local.is_empty( ) {
+    let node = place_voxels( &mut builder, min, &local );
+    builder.add( node );
+  }
+
+  Ok( build_scene( builder ) )
+}
+
+/// Converts the visible instances of the scene into a voxel list, in world
+/// coordinates. Instances on hidden layers are omitted.
+///
+/// When instances overlap, the voxel of the first instance (in the order of
+/// [`VoxScene::instances`]) is used.
+pub fn export( scene: &VoxScene ) -> String {
+  let mut voxels = HashMap::new( );
+  for inst in visible_instances( scene ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, i) in &model.xyzi {
+      voxels.entry( inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) ) ).or_insert( *i );
+    }
+  }
+  write( voxels.into_iter( ).collect( ), &scene.palette )
+}
+
+/// Converts a single model into a voxel list, in the coordinates of the
+/// model. The palette provides the colors of its voxels.
+pub fn export_model( model: &Model, palette: &[Material] ) -> String {
+  let voxels =
+    model.xyzi.iter( )
+      .map( |(x, y, z, i)| ((*x as i32, *y as i32, *z as i32), *i) )
+      .collect( );
+  write( voxels, palette )
+}
+
+/// Writes the voxels (as positions with palette indices) in a stable order.
+fn write( mut voxels: Vec< ((i32,i32,i32), u8) >, palette: &[Material] ) -> String {
+  voxels.sort_by_key( |((x, y, z), _)| (*z, *y, *x) );
+
+  let mut out = String::from( "# vox_parser\n# One line per voxel\n# X Y Z RRGGBB\n" );
+  for ((x, y, z), i) in voxels {
+    let (r, g, b, _) = palette.get( i as usize - 1 ).map_or( (0, 0, 0, 255), |m| m.rgba );
+    out.push_str( &format!( "{} {} {} {:02x}{:02x}{:02x}\n", x, y, z, r, g, b ) );
+  }
+  out
+}
+
+/// Parses a line of the form `x y z RRGGBB`. The color is opaque.
+fn parse_line( line: &str ) -> Option< ((i32,i32,i32), Rgba) > {
+  let mut parts = line.split_whitespace( );
+  let x = parts.next( )?.parse( ).ok( )?;
+  let y = parts.next( )?.parse( ).ok( )?;
+  let z = parts.next( )?.parse( ).ok( )?;
+  let color = parts.next( )?;
+  // Also reject signs, which `from_str_radix` accepts
+  if parts.next( ).is_some( ) || color.len( ) != 6 || !color.bytes( ).all( |b| b.is_ascii_hexdigit( ) ) {
+    return None;
+  }
+
+  let channel = |i: usize| u8::from_str_radix( &color[ i..i + 2 ], 16 ).ok( );
+  Some( ((x, y, z), (channel( 0 )?, channel( 2 )?, channel( 4 )?, 255)) )
+}
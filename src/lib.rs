@@ -14,6 +14,16 @@
 //!   have your own scene, which you construct yourself.
 //! * [`data::custom`] - Custom voxel scene, which is much easier to work with.
 //! 
+//! Further utilities operate on the custom representation:
+//! * [`builder`] - Construct custom scenes from code.
+//! * [`storage`] - Random-access voxel storage (dense, sparse, and octree),
+//!   convertible to and from models.
+//! * [`raycast`] - Ray casting against models and scenes.
+//! * [`spatial`] - Bounding boxes, and voxel queries by world position.
+//! * [`mesh`] - Triangle meshes generated from models.
+//! * [`formats`] - Conversions to and from other file formats.
+//! * [`palette`] - Quantization of true colors into the palette.
+//! 
 //! The parser uses [`nom`] (v6).
 //! 
 //! # Supported chunks:
@@ -75,7 +85,7 @@
 //!       let model = &scene.models[ *i as usize ];
 //!       model.xyzi.iter( )
 //!         // Warning: Palette index 0 is not stored, as it does not exist. Subtract one.
-//!         .map( |(_,_,_,palette_index)| scene.palette[ (*palette_index - 1) as usize ] )
+//!         .map( |(_,_,_,palette_index)| &scene.palette[ (*palette_index - 1) as usize ] )
 //!         .filter( |m| m.rgba.0 > 0 ) // Check if the material color has a red component
 //!         .count( )
 //!     }
@@ -87,7 +97,15 @@
 pub mod data;
 pub mod parse;
 pub mod unparse;
+pub mod builder;
+pub mod storage;
+pub mod raycast;
+pub mod spatial;
+pub mod mesh;
+pub mod formats;
+pub mod palette;
 
 mod convert;
+mod transform;
 
 pub use convert::{to_custom, from_custom};
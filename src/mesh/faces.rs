@@ -0,0 +1,42 @@
+//! Simple meshing, with one quad per visible voxel face.
+
+
+// Local imports
+use crate::data::custom::Model;
+use crate::mesh::{Mesh, corner_occlusion};
+use crate::storage::{DenseGrid, VoxelStorage};
+
+
+/// Meshes the model with a quad for every visible voxel face. Faces between
+/// two adjacent voxels are hidden, and thus omitted.
+/// 
+/// As every quad covers exactly one voxel face, the resulting mesh is closed
+/// and free of T-junctions.
+pub fn faces( model: &Model ) -> Mesh {
+  let grid = DenseGrid::from_model( model );
+  let mut mesh = Mesh::default( );
+  let is_solid = |p: [i32; 3]| grid.get( (p[ 0 ], p[ 1 ], p[ 2 ]) ).unwrap_or( 0 ) != 0;
+
+  for ((x, y, z), palette_index) in grid.voxels( ) {
+    let pos = [x, y, z];
+
+    for axis in 0..3 {
+      for &is_positive in &[false, true] {
+        let mut neighbour = pos;
+        neighbour[ axis ] += if is_positive { 1 } else { -1 };
+
+        let n = grid.get( (neighbour[ 0 ], neighbour[ 1 ], neighbour[ 2 ]) ).unwrap_or( 0 );
+        if n == 0 {
+          let mut min = pos;
+          if is_positive {
+            min[ axis ] += 1;
+          }
+          let ao = corner_occlusion( is_solid, neighbour, axis );
+          mesh.push_quad( min, axis, is_positive, (1, 1), palette_index, ao );
+        }
+      }
+    }
+  }
+
+  mesh
+}
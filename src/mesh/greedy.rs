@@ -0,0 +1,189 @@
+//! Greedy meshing, which merges adjacent faces into larger quads.
+
+
+// Stdlib imports
+use std::collections::HashSet;
+// Local imports
+use crate::data::custom::{Model, VoxScene};
+use crate::mesh::{Mesh, corner_occlusion};
+use crate::storage::DenseGrid;
+
+
+/// Meshes the model, where coplanar adjacent faces with the same palette index
+/// are merged into a single quad. Faces between two adjacent voxels are
+/// hidden, and thus omitted.
+///
+/// This produces far fewer quads than [`faces`](crate::mesh::faces). Note that
+/// the mesh may contain T-junctions, where the corner of a quad lies on the
+/// edge of another quad.
+///
+/// The ambient occlusion of a merged quad is taken from its corners, so any
+/// occlusion inside the quad is lost. (See [`greedy_ao`] to keep it)
+pub fn greedy( model: &Model ) -> Mesh {
+  greedy_occluded( model, |_| false )
+}
+
+/// Meshes the model as [`greedy`], but only merges faces which also have the
+/// same ambient occlusion. The occlusion is thus exact, at the cost of more
+/// quads. This suits renderers, whereas exporters that do not write the
+/// occlusion should use [`greedy`].
+pub fn greedy_ao( model: &Model ) -> Mesh {
+  greedy_occluded_ao( model, |_| false )
+}
+
+/// Meshes the model as [`greedy`], where `is_occluded` tells which empty
+/// positions are covered by other voxels. Faces in front of those positions
+/// are hidden, and they contribute to the ambient occlusion.
+///
+/// The position is in the space of the model, and may be outside its bounds.
+/// This allows culling the faces covered by neighbouring models.
+pub fn greedy_occluded< F >( model: &Model, is_occluded: F ) -> Mesh
+    where F : Fn( (i32,i32,i32) ) -> bool {
+  mesh_planes( model, is_occluded, false )
+}
+
+/// Meshes the model as [`greedy_occluded`], but only merges faces which also
+/// have the same ambient occlusion. (See [`greedy_ao`])
+pub fn greedy_occluded_ao< F >( model: &Model, is_occluded: F ) -> Mesh
+    where F : Fn( (i32,i32,i32) ) -> bool {
+  mesh_planes( model, is_occluded, true )
+}
+
+/// Meshes the model plane by plane. (See [`greedy_occluded`]) Faces are only
+/// merged when they have the same occlusion iff `merge_ao` is set.
+fn mesh_planes< F >( model: &Model, is_occluded: F, merge_ao: bool ) -> Mesh
+    where F : Fn( (i32,i32,i32) ) -> bool {
+  let grid = DenseGrid::from_model( model );
+  let (x_size, y_size, z_size) = model.size;
+  let size = [x_size as i32, y_size as i32, z_size as i32];
+
+  let get = |p: [i32; 3]| grid.get( (p[ 0 ], p[ 1 ], p[ 2 ]) ).unwrap_or( 0 );
+  let is_solid = |p: [i32; 3]| get( p ) != 0 || is_occluded( (p[ 0 ], p[ 1 ], p[ 2 ]) );
+
+  let mut mesh = Mesh::default( );
+
+  for axis in 0..3 {
+    let u = ( axis + 1 ) % 3;
+    let v = ( axis + 2 ) % 3;
+    let (u_size, v_size) = ( size[ u ], size[ v ] );
+
+    for &is_positive in &[false, true] {
+      // The plane `d` separates the voxels at `d-1` and `d` along the axis
+      for d in 0..=size[ axis ] {
+        // The palette index and occlusion of the visible face at every
+        // position in the plane. Index 0 means there is no face.
+        let mut mask = vec![ (0u8, [[0u8; 2]; 2]); ( u_size * v_size ) as usize ];
+
+        for j in 0..v_size {
+          for i in 0..u_size {
+            let mut behind = [0; 3];
+            behind[ axis ] = d - 1;
+            behind[ u ] = i;
+            behind[ v ] = j;
+            let mut front = behind;
+            front[ axis ] = d;
+
+            // Faces point from the solid voxel to the open position
+            let (solid, open) = if is_positive { (behind, front) } else { (front, behind) };
+            let palette_index = get( solid );
+            if palette_index != 0 && !is_solid( open ) {
+              let ao = corner_occlusion( is_solid, open, axis );
+              mask[ ( i + j * u_size ) as usize ] = (palette_index, ao);
+            }
+          }
+        }
+
+        merge_plane( &mut mesh, &mut mask, (u_size, v_size), d, axis, is_positive, merge_ao );
+      }
+    }
+  }
+
+  mesh
+}
+
+/// Meshes every instance of the scene as [`greedy`], where faces covered by
+/// the voxels of other instances are also omitted. The meshes are in the
+/// space of the instance's model, in the order of [`VoxScene::instances`].
+///
+/// Instances on hidden layers do not cover the faces of others.
+pub fn greedy_instances( scene: &VoxScene ) -> Vec< Mesh > {
+  let instances = scene.instances( );
+
+  let mut occupied = HashSet::new( );
+  for inst in instances.iter( ).filter( |i| !scene.is_hidden( i ) ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, _) in &model.xyzi {
+      occupied.insert( inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) ) );
+    }
+  }
+
+  instances.iter( )
+    .map( |inst| {
+      let model = &scene.models[ inst.model_id as usize ];
+      greedy_occluded( model, |p| occupied.contains( &inst.voxel_to_world( model, p ) ) )
+    } )
+    .collect( )
+}
+
+/// Merges the faces in the plane into quads, and appends those to the mesh.
+/// The mask contains the palette index and corner occlusion of the face at
+/// every position in the plane, and is cleared in the process.
+///
+/// Faces with the same palette index are merged, where `merge_ao` also
+/// requires the same occlusion. Every corner of a quad takes the occlusion of
+/// the face in that corner.
+fn merge_plane(
+    mesh: &mut Mesh,
+    mask: &mut [(u8, [[u8; 2]; 2])],
+    (u_size, v_size): (i32, i32),
+    d: i32,
+    axis: usize,
+    is_positive: bool,
+    merge_ao: bool ) {
+
+  let at = |i: i32, j: i32| ( i + j * u_size ) as usize;
+  let same = |a: (u8, [[u8; 2]; 2]), b: (u8, [[u8; 2]; 2])| a.0 == b.0 && ( !merge_ao || a.1 == b.1 );
+
+  for j in 0..v_size {
+    let mut i = 0;
+    while i < u_size {
+      let face = mask[ at( i, j ) ];
+      if face.0 == 0 {
+        i += 1;
+        continue;
+      }
+
+      // Grow the quad along u, then along v for as long as entire rows match
+      let mut width = 1;
+      while i + width < u_size && same( mask[ at( i + width, j ) ], face ) {
+        width += 1;
+      }
+
+      let mut height = 1;
+      while j + height < v_size
+          && ( i..i + width ).all( |k| same( mask[ at( k, j + height ) ], face ) ) {
+        height += 1;
+      }
+
+      let (i1, j1) = ( i + width - 1, j + height - 1 );
+      let ao =
+        [ [mask[ at( i, j ) ].1[ 0 ][ 0 ], mask[ at( i, j1 ) ].1[ 0 ][ 1 ]]
+        , [mask[ at( i1, j ) ].1[ 1 ][ 0 ], mask[ at( i1, j1 ) ].1[ 1 ][ 1 ]]
+        ];
+
+      for jj in j..j + height {
+        for ii in i..i + width {
+          mask[ at( ii, jj ) ].0 = 0;
+        }
+      }
+
+      let mut min = [0; 3];
+      min[ axis ] = d;
+      min[ ( axis + 1 ) % 3 ] = i;
+      min[ ( axis + 2 ) % 3 ] = j;
+      mesh.push_quad( min, axis, is_positive, (width, height), face.0, ao );
+
+      i += width;
+    }
+  }
+}
@@ -0,0 +1,191 @@
+//! Triangle meshes generated from voxel models.
+//! 
+//! The meshes are shared by the exporters in [`formats`](crate::formats), but
+//! may equally be used by renderers. Meshes are in the space of the model,
+//! where voxel `(x,y,z)` is the unit cube with its lowest corner at `(x,y,z)`.
+//! 
+//! Every vertex carries an ambient occlusion value, which darkens the corners
+//! of faces that are surrounded by other voxels. (See
+//! [`Mesh::ambient_occlusion`])
+//! 
+//! # Example: Mesh a model
+//! 
+//! ```
+//! use vox_parser::data::custom::Model;
+//! 
+//! let model = Model { size: (2, 1, 1), xyzi: vec![ (0,0,0,1), (1,0,0,1) ] };
+//! let mesh = vox_parser::mesh::faces( &model );
+//! 
+//! // The shared face between the two voxels is hidden
+//! assert_eq!( mesh.num_quads( ), 10 );
+//! 
+//! // Adjacent faces of the same color are merged
+//! let mesh = vox_parser::mesh::greedy( &model );
+//! assert_eq!( mesh.num_quads( ), 6 );
+//! ```
+
+
+mod faces;
+mod greedy;
+mod surface_nets;
+
+pub use self::faces::faces;
+pub use self::greedy::{greedy, greedy_ao, greedy_instances, greedy_occluded, greedy_occluded_ao};
+pub use self::surface_nets::{surface_nets, surface_nets_scene};
+
+
+/// An indexed triangle mesh.
+/// 
+/// All vertex attributes have the same length. The mesh consists of quads,
+/// which each have 2 triangles. The triangles are counter-clockwise when viewed
+/// from the front (i.e., from the side the normal points to).
+/// 
+/// The blocky meshers ([`faces`] and [`greedy`]) produce axis-aligned quads
+/// with 4 vertices of their own. The smooth mesher ([`surface_nets`]) shares
+/// vertices between quads.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+  pub positions         : Vec< [f32; 3] >,
+  /// Unit normal of every vertex. For the blocky meshers, these are the
+  /// normals of the faces.
+  pub normals           : Vec< [f32; 3] >,
+  /// Palette index of every vertex. (See [`palette_uv`] for a corresponding
+  /// texture coordinate)
+  pub palette_indices   : Vec< u8 >,
+  /// Ambient occlusion of every vertex, from `0.0` (fully occluded) to `1.0`
+  /// (unoccluded). This is the fraction of the three voxels around the corner
+  /// (in front of the face) that are empty. A corner between two voxels is
+  /// fully occluded. (Smooth meshes are unoccluded)
+  /// 
+  /// [`greedy`] merges faces regardless of their occlusion, so only the
+  /// corners of its quads are exact. [`greedy_ao`] keeps every occluded corner.
+  /// 
+  /// Triangles are split along the diagonal which interpolates these values
+  /// most evenly, which avoids anisotropic artifacts.
+  pub ambient_occlusion : Vec< f32 >,
+  /// Triangle list. Every three consecutive indices form a triangle.
+  pub indices           : Vec< u32 >
+}
+
+impl Mesh {
+  /// Returns the number of vertices.
+  pub fn num_vertices( &self ) -> usize {
+    self.positions.len( )
+  }
+
+  /// Returns the number of quads. (Every quad consists of two triangles)
+  pub fn num_quads( &self ) -> usize {
+    self.indices.len( ) / 6
+  }
+
+  /// Returns the texture coordinate of every vertex in a palette texture.
+  /// (See [`palette_uv`])
+  pub fn uvs( &self ) -> Vec< [f32; 2] > {
+    self.palette_indices.iter( ).map( |i| palette_uv( *i ) ).collect( )
+  }
+
+  /// Appends the axis-aligned quad with its lowest corner at `min`, which is
+  /// perpendicular to `axis` (0=x, 1=y, 2=z). It spans `width` along the next
+  /// axis (`(axis+1) % 3`) and `height` along the axis after that. The normal
+  /// points in the positive direction of `axis` iff `is_positive`.
+  /// 
+  /// `ao[du][dv]` is the occlusion level (See [`corner_occlusion`]) of the
+  /// corner at `min`, offset by `du` along the width and `dv` along the
+  /// height.
+  pub(crate) fn push_quad(
+      &mut self,
+      min: [i32; 3],
+      axis: usize,
+      is_positive: bool,
+      (width, height): (i32, i32),
+      palette_index: u8,
+      ao: [[u8; 2]; 2] ) {
+
+    let u = ( axis + 1 ) % 3;
+    let v = ( axis + 2 ) % 3;
+
+    let mut du = [0; 3];
+    du[ u ] = width;
+    let mut dv = [0; 3];
+    dv[ v ] = height;
+
+    let add = |a: [i32; 3], b: [i32; 3]| [a[0] + b[0], a[1] + b[1], a[2] + b[2]];
+    // As (u, v, axis) is a cyclic permutation of (x, y, z), `du x dv` points
+    // in the positive direction of `axis`. Reverse the order otherwise.
+    let (corners, levels) =
+      if is_positive {
+        ( [min, add( min, du ), add( add( min, du ), dv ), add( min, dv )]
+        , [ao[ 0 ][ 0 ], ao[ 1 ][ 0 ], ao[ 1 ][ 1 ], ao[ 0 ][ 1 ]]
+        )
+      } else {
+        ( [min, add( min, dv ), add( add( min, du ), dv ), add( min, du )]
+        , [ao[ 0 ][ 0 ], ao[ 0 ][ 1 ], ao[ 1 ][ 1 ], ao[ 1 ][ 0 ]]
+        )
+      };
+
+    let mut normal = [0.0; 3];
+    normal[ axis ] = if is_positive { 1.0 } else { -1.0 };
+
+    let base = self.positions.len( ) as u32;
+    for (c, level) in corners.iter( ).zip( &levels ) {
+      self.positions.push( [c[0] as f32, c[1] as f32, c[2] as f32] );
+      self.normals.push( normal );
+      self.palette_indices.push( palette_index );
+      self.ambient_occlusion.push( *level as f32 / 3.0 );
+    }
+
+    // Split along the diagonal whose corners are darkest. Otherwise, the
+    // occlusion of a single corner would bleed across the quad.
+    if levels[ 0 ] + levels[ 2 ] > levels[ 1 ] + levels[ 3 ] {
+      self.indices.extend( &[base + 1, base + 2, base + 3, base + 1, base + 3, base] );
+    } else {
+      self.indices.extend( &[base, base + 1, base + 2, base, base + 2, base + 3] );
+    }
+  }
+}
+
+/// Returns the occlusion levels of the corners of the face in front of the
+/// `open` position, which is perpendicular to `axis`. `is_solid` tells which
+/// positions are occupied.
+/// 
+/// The levels are indexed as `[du][dv]`, which are the offsets of the corner
+/// along the next axis (`(axis+1) % 3`) and the axis after that. A level is
+/// the number of empty voxels among the three voxels touching the corner in
+/// front of the face; Except that when both side voxels are occupied, the
+/// corner is fully occluded (level 0).
+pub(crate) fn corner_occlusion< F >( is_solid: F, open: [i32; 3], axis: usize ) -> [[u8; 2]; 2]
+    where F : Fn( [i32; 3] ) -> bool {
+  let u = ( axis + 1 ) % 3;
+  let v = ( axis + 2 ) % 3;
+  let mut levels = [[0; 2]; 2];
+
+  for (du, row) in levels.iter_mut( ).enumerate( ) {
+    for (dv, level) in row.iter_mut( ).enumerate( ) {
+      let mut side1 = open;
+      side1[ u ] += if du == 0 { -1 } else { 1 };
+      let mut side2 = open;
+      side2[ v ] += if dv == 0 { -1 } else { 1 };
+      let mut corner = side1;
+      corner[ v ] = side2[ v ];
+
+      let (s1, s2, c) = ( is_solid( side1 ), is_solid( side2 ), is_solid( corner ) );
+      *level =
+        if s1 && s2 {
+          0
+        } else {
+          3 - s1 as u8 - s2 as u8 - c as u8
+        };
+    }
+  }
+
+  levels
+}
+
+/// Returns the texture coordinate of the palette index in a palette texture.
+/// 
+/// The palette texture is a 256x1 image, where pixel `i` contains the color of
+/// palette index `i+1`. (This is the layout of MagicaVoxel's palette images)
+/// The coordinate is in the center of the pixel.
+pub fn palette_uv( palette_index: u8 ) -> [f32; 2] {
+  [( palette_index as f32 - 0.5 ) / 256.0, 0.5]
+}
@@ -0,0 +1,294 @@
+//! Smooth meshing with surface nets.
+//!
+//! The surface is extracted from the occupancy of the voxel centers. Every
+//! _cell_ between 8 neighbouring voxel centers that contains the surface gets
+//! a single vertex, and the vertices of the 4 cells around every occupied-empty
+//! pair of voxels are connected by a quad. Unlike marching cubes, this yields
+//! far fewer (and better shaped) triangles.
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::{Model, VoxScene};
+use crate::mesh::Mesh;
+use crate::spatial::Aabb;
+use crate::storage::DenseGrid;
+
+
+/// Meshes the model with a smooth surface.
+///
+/// Every vertex is initially placed at the average of the points where the
+/// surface crosses the edges of its cell, which bevels the corners of the
+/// voxels. Then, `iterations` rounds of smoothing move every vertex toward the
+/// average of its neighbours, while keeping it within its cell. This uses
+/// Taubin's method, which (unlike plain averaging) does not shrink the
+/// surface.
+///
+/// Every vertex takes the most common palette index among the (non-empty)
+/// voxels of its cell. The mesh is closed, and is in the space of the model.
+///
+/// # Example: Mesh a single voxel
+///
+/// ```
+/// use vox_parser::data::custom::Model;
+///
+/// let model = Model { size: (1, 1, 1), xyzi: vec![ (0,0,0,5) ] };
+/// let mesh = vox_parser::mesh::surface_nets( &model, 0 );
+///
+/// // Every cell around the voxel is mostly empty, but keeps its color
+/// assert_eq!( mesh.palette_indices.len( ), 8 );
+/// assert!( mesh.palette_indices.iter( ).all( |i| *i == 5 ) );
+/// ```
+pub fn surface_nets( model: &Model, iterations: u32 ) -> Mesh {
+  let grid = DenseGrid::from_model( model );
+  let get = |p: [i32; 3]| grid.get( (p[ 0 ], p[ 1 ], p[ 2 ]) ).unwrap_or( 0 );
+  extract( Aabb::from_size( model.size ), get, iterations )
+}
+
+/// Meshes all visible instances of the scene as a single smooth surface, in
+/// world space. Overlapping and touching instances are merged into one
+/// surface. (See [`surface_nets`])
+///
+/// When instances overlap, the voxel of the first instance (in the order of
+/// [`VoxScene::instances`]) is used.
+pub fn surface_nets_scene( scene: &VoxScene, iterations: u32 ) -> Mesh {
+  let mut voxels = HashMap::new( );
+
+  for inst in scene.instances( ).iter( ).filter( |i| !scene.is_hidden( i ) ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, i) in &model.xyzi {
+      let (wx, wy, wz) = inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) );
+      voxels.entry( [wx, wy, wz] ).or_insert( *i );
+    }
+  }
+
+  match Aabb::enclosing( voxels.keys( ).map( |p| (p[ 0 ], p[ 1 ], p[ 2 ]) ) ) {
+    Some( bounds ) => extract( bounds, |p| voxels.get( &p ).copied( ).unwrap_or( 0 ), iterations ),
+    None           => Mesh::default( )
+  }
+}
+
+/// Extracts the surface of the voxels within the bounds. `get` returns the
+/// palette index at a position, where 0 is empty.
+fn extract< F >( bounds: Aabb, get: F, iterations: u32 ) -> Mesh
+    where F : Fn( [i32; 3] ) -> u8 {
+  let lo = [bounds.min.0, bounds.min.1, bounds.min.2];
+  let hi = [bounds.max.0, bounds.max.1, bounds.max.2];
+
+  // Cell `c` spans the voxel centers from `c` to `c+1`. The cells around the
+  // bounds are included, such that the surface is closed.
+  let num_cells = [0, 1, 2].map( |a| ( hi[ a ] - lo[ a ] + 1 ).max( 0 ) as usize );
+  let cell_index = |c: [i32; 3]| {
+    let [x, y, z] = [0, 1, 2].map( |a| ( c[ a ] - lo[ a ] + 1 ) as usize );
+    x + num_cells[ 0 ] * ( y + num_cells[ 1 ] * z )
+  };
+
+  let mut mesh = Mesh::default( );
+  // The vertex of every cell, if any
+  let mut cell_vertex = vec![ u32::MAX; num_cells.iter( ).product( ) ];
+  // The cell of every vertex
+  let mut vertex_cell = Vec::new( );
+  // The direction from the occupied to the empty corners of the cell of
+  // every vertex
+  let mut gradients = Vec::new( );
+
+  for z in lo[ 2 ] - 1..hi[ 2 ] {
+    for y in lo[ 1 ] - 1..hi[ 1 ] {
+      for x in lo[ 0 ] - 1..hi[ 0 ] {
+        let c = [x, y, z];
+        let corners: Vec< u8 > =
+          ( 0..8 ).map( |i| get( [x + ( i & 1 ), y + ( ( i >> 1 ) & 1 ), z + ( ( i >> 2 ) & 1 )] ) ).collect( );
+
+        let num_solid = corners.iter( ).filter( |i| **i != 0 ).count( );
+        if num_solid == 0 || num_solid == 8 {
+          continue;
+        }
+
+        let mut gradient = [0.0f32; 3];
+        for (i, palette_index) in corners.iter( ).enumerate( ) {
+          let sign = if *palette_index == 0 { 1.0 } else { -1.0 };
+          for (a, g) in gradient.iter_mut( ).enumerate( ) {
+            *g += sign * ( ( ( i >> a ) & 1 ) as f32 - 0.5 );
+          }
+        }
+
+        // Average the midpoints of the edges which cross the surface
+        let mut sum = [0.0f32; 3];
+        let mut num_crossings = 0;
+        for i in 0..8 {
+          for axis in 0..3 {
+            let j = i | ( 1 << axis );
+            if i != j && ( corners[ i ] == 0 ) != ( corners[ j ] == 0 ) {
+              for (a, s) in sum.iter_mut( ).enumerate( ) {
+                *s += ( ( i >> a ) & 1 ) as f32 + if a == axis { 0.5 } else { 0.0 };
+              }
+              num_crossings += 1;
+            }
+          }
+        }
+
+        // Voxel centers are offset by half a voxel
+        let position = [0, 1, 2].map( |a| c[ a ] as f32 + 0.5 + sum[ a ] / num_crossings as f32 );
+
+        cell_vertex[ cell_index( c ) ] = mesh.positions.len( ) as u32;
+        vertex_cell.push( c );
+        gradients.push( gradient );
+        mesh.positions.push( position );
+        mesh.palette_indices.push( most_common( &corners ) );
+        mesh.ambient_occlusion.push( 1.0 );
+      }
+    }
+  }
+
+  // Connect the 4 cells around every edge between an occupied and an empty
+  // voxel center
+  for z in lo[ 2 ] - 1..hi[ 2 ] {
+    for y in lo[ 1 ] - 1..hi[ 1 ] {
+      for x in lo[ 0 ] - 1..hi[ 0 ] {
+        let p = [x, y, z];
+        let is_solid = get( p ) != 0;
+
+        for axis in 0..3 {
+          let mut q = p;
+          q[ axis ] += 1;
+          if is_solid == ( get( q ) != 0 ) {
+            continue;
+          }
+          let u = ( axis + 1 ) % 3;
+          let v = ( axis + 2 ) % 3;
+
+          let vertex = |du: i32, dv: i32| {
+            let mut c = p;
+            c[ u ] -= du;
+            c[ v ] -= dv;
+            cell_vertex[ cell_index( c ) ]
+          };
+          // Counter-clockwise around the positive axis
+          let mut quad = [vertex( 1, 1 ), vertex( 0, 1 ), vertex( 0, 0 ), vertex( 1, 0 )];
+          // The surface faces away from the occupied voxel
+          if !is_solid {
+            quad.reverse( );
+          }
+          push_quad( &mut mesh, quad );
+        }
+      }
+    }
+  }
+
+  smooth( &mut mesh, &vertex_cell, iterations );
+  mesh.normals = vertex_normals( &mesh, &gradients );
+  mesh
+}
+
+/// Appends the quad, which is split along its shorter diagonal.
+fn push_quad( mesh: &mut Mesh, [a, b, c, d]: [u32; 4] ) {
+  let dist = |i: u32, j: u32| {
+    let (p, q) = ( mesh.positions[ i as usize ], mesh.positions[ j as usize ] );
+    ( 0..3 ).map( |k| ( p[ k ] - q[ k ] ).powi( 2 ) ).sum::< f32 >( )
+  };
+
+  if dist( a, c ) <= dist( b, d ) {
+    mesh.indices.extend( &[a, b, c, a, c, d] );
+  } else {
+    mesh.indices.extend( &[b, c, d, b, d, a] );
+  }
+}
+
+/// Moves every vertex toward the average of its neighbours in the mesh, while
+/// keeping it within its cell.
+///
+/// Every iteration consists of a shrinking step, followed by an inflating
+/// step. (As by Taubin, "A signal processing approach to fair surface design")
+fn smooth( mesh: &mut Mesh, vertex_cell: &[[i32; 3]], iterations: u32 ) {
+  if iterations == 0 {
+    return;
+  }
+
+  let mut neighbours = vec![ Vec::new( ); mesh.positions.len( ) ];
+  for t in mesh.indices.chunks( 3 ) {
+    for k in 0..3 {
+      let (i, j) = ( t[ k ] as usize, t[ ( k + 1 ) % 3 ] as usize );
+      if !neighbours[ i ].contains( &j ) {
+        neighbours[ i ].push( j );
+        neighbours[ j ].push( i );
+      }
+    }
+  }
+
+  for _ in 0..iterations {
+    for &factor in &[0.5f32, -0.53] {
+      let prev = mesh.positions.clone( );
+
+      for (i, n) in neighbours.iter( ).enumerate( ) {
+        if n.is_empty( ) {
+          continue;
+        }
+        let c = vertex_cell[ i ];
+        for a in 0..3 {
+          let avg = n.iter( ).map( |j| prev[ *j ][ a ] ).sum::< f32 >( ) / n.len( ) as f32;
+          let p = prev[ i ][ a ] + factor * ( avg - prev[ i ][ a ] );
+          let min = c[ a ] as f32 + 0.5;
+          mesh.positions[ i ][ a ] = p.max( min ).min( min + 1.0 );
+        }
+      }
+    }
+  }
+}
+
+/// Returns the unit normal of every vertex, which is the area-weighted average
+/// of the normals of its triangles. When those cancel out, the gradient of its
+/// cell is used instead.
+fn vertex_normals( mesh: &Mesh, gradients: &[[f32; 3]] ) -> Vec< [f32; 3] > {
+  let mut normals = vec![ [0.0f32; 3]; mesh.positions.len( ) ];
+
+  for t in mesh.indices.chunks( 3 ) {
+    let [a, b, c] = [t[ 0 ], t[ 1 ], t[ 2 ]].map( |i| mesh.positions[ i as usize ] );
+    let e1 = [b[ 0 ] - a[ 0 ], b[ 1 ] - a[ 1 ], b[ 2 ] - a[ 2 ]];
+    let e2 = [c[ 0 ] - a[ 0 ], c[ 1 ] - a[ 1 ], c[ 2 ] - a[ 2 ]];
+    // The length of the cross product is twice the area
+    let n =
+      [ e1[ 1 ] * e2[ 2 ] - e1[ 2 ] * e2[ 1 ]
+      , e1[ 2 ] * e2[ 0 ] - e1[ 0 ] * e2[ 2 ]
+      , e1[ 0 ] * e2[ 1 ] - e1[ 1 ] * e2[ 0 ]
+      ];
+    for i in t {
+      for a in 0..3 {
+        normals[ *i as usize ][ a ] += n[ a ];
+      }
+    }
+  }
+
+  normals.iter( ).zip( gradients )
+    .map( |(n, g)| normalize( *n ).or_else( || normalize( *g ) ).unwrap_or( [0.0, 0.0, 1.0] ) )
+    .collect( )
+}
+
+/// Returns the vector scaled to unit length, or `None` if it (nearly) has no
+/// length.
+fn normalize( n: [f32; 3] ) -> Option< [f32; 3] > {
+  let len = ( n[ 0 ] * n[ 0 ] + n[ 1 ] * n[ 1 ] + n[ 2 ] * n[ 2 ] ).sqrt( );
+  if len > 1e-6 {
+    Some( n.map( |c| c / len ) )
+  } else {
+    None
+  }
+}
+
+/// Returns the most common non-zero palette index. Ties are resolved by the
+/// lowest index. Only when all indices are zero, 0 is returned.
+fn most_common( indices: &[u8] ) -> u8 {
+  let mut counts = [0u8; 256];
+  for i in indices {
+    counts[ *i as usize ] += 1;
+  }
+
+  // Empty voxels (index 0) never win, even when they are in the majority
+  let mut best = 0;
+  for i in 1..256 {
+    if counts[ i ] > 0 && ( best == 0 || counts[ i ] > counts[ best ] ) {
+      best = i;
+    }
+  }
+  best as u8
+}
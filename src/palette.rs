@@ -0,0 +1,560 @@
+//! Fitting arbitrary colors into the 255 entries of a palette.
+//!
+//! Importers from true-color formats produce more distinct colors than a
+//! `.vox` palette can hold. [`quantize`] picks at most 255 representative
+//! colors, and maps every input color to one of them. [`quantize_with`]
+//! additionally keeps palette entries free, or fixes their colors.
+//!
+//! Colors are compared in the CIELAB color space, where distances roughly
+//! match perceived differences. So, the palette spends its entries where the
+//! eye distinguishes colors best.
+//!
+//! Further, this module provides methods on [`VoxScene`] which rearrange the
+//! palette, such as [`VoxScene::compact_palette`] and
+//! [`VoxScene::sort_palette`]. These always update the voxels of all models,
+//! such that the scene looks the same.
+//!
+//! # Example: Quantize colors
+//!
+//! ```
+//! let colors = vec![ (255, 0, 0, 255), (0, 0, 255, 255), (255, 0, 0, 255) ];
+//! let q = vox_parser::palette::quantize( colors );
+//!
+//! // Few colors fit in the palette exactly
+//! assert_eq!( q.colors, vec![ (0, 0, 255, 255), (255, 0, 0, 255) ] );
+//! assert_eq!( q.index_of( (255, 0, 0, 255) ), Some( 2 ) );
+//! ```
+//!
+//! # Example: Keep existing palette entries
+//!
+//! ```
+//! use vox_parser::palette::{self, QuantizeOptions};
+//!
+//! // Index 1 is black, and index 2 is kept free for a glass material
+//! let options =
+//!   QuantizeOptions {
+//!     locked:   vec![ (1, (0, 0, 0, 255)) ],
+//!     reserved: vec![ 2 ],
+//!     ..QuantizeOptions::default( )
+//!   };
+//! let colors = vec![ (0, 0, 0, 255), (255, 255, 255, 255) ];
+//! let q = palette::quantize_with( colors, &options );
+//!
+//! assert_eq!( q.index_of( (0, 0, 0, 255) ), Some( 1 ) );
+//! assert_eq!( q.index_of( (255, 255, 255, 255) ), Some( 3 ) );
+//! assert_eq!( q.entries, vec![ (1, (0, 0, 0, 255)), (3, (255, 255, 255, 255)) ] );
+//! ```
+//!
+//! # Example: Compact the palette
+//!
+//! ```
+//! use vox_parser::builder::{NodeBuilder, SceneBuilder};
+//!
+//! let mut builder = SceneBuilder::new( );
+//! let model = builder.add_voxels( vec![ (0,0,0,10), (1,0,0,20) ] );
+//! builder.add( NodeBuilder::shape( model ) );
+//! let mut scene = builder.build( ).unwrap( );
+//! let color = scene.palette[ 19 ].rgba;
+//!
+//! assert_eq!( scene.compact_palette( ), 2 );
+//! assert_eq!( scene.models[ 0 ].xyzi, vec![ (0,0,0,1), (1,0,0,2) ] );
+//! assert_eq!( scene.palette[ 1 ].rgba, color );
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::{Material, VoxScene};
+
+
+/// A color with red, green, blue, and alpha channels.
+pub type Rgba = (u8,u8,u8,u8);
+
+/// Internal. A color in CIELAB space, with alpha as a fourth coordinate.
+type Lab = [f32; 4];
+
+/// The result of [`quantize`] and [`quantize_with`].
+#[derive(Debug, Clone)]
+pub struct Quantization {
+  /// The colors of the palette, of which there are at most 255. Color `i` is
+  /// at palette index `i+1`. Entries which are not used (such as reserved
+  /// ones) are transparent black.
+  pub colors  : Vec< Rgba >,
+  /// The palette index and color of every palette entry that is used, in the
+  /// order of their indices. This includes the locked entries.
+  pub entries : Vec< (u8, Rgba) >,
+  /// The palette index of every input color
+  lookup      : HashMap< Rgba, u8 >
+}
+
+impl Quantization {
+  /// Returns the palette index of the given input color, or `None` if the
+  /// color was not quantized.
+  pub fn index_of( &self, rgba: Rgba ) -> Option< u8 > {
+    self.lookup.get( &rgba ).copied( )
+  }
+}
+
+/// Options for [`quantize_with`].
+#[derive(Debug, Clone)]
+pub struct QuantizeOptions {
+  /// Palette indices which are not used. (e.g., because they hold special
+  /// materials)
+  pub reserved   : Vec< u8 >,
+  /// Palette entries with fixed colors. Input colors are mapped to these
+  /// entries when they are the closest.
+  pub locked     : Vec< (u8, Rgba) >,
+  /// The number of k-means rounds which refine the palette after the median
+  /// cut. More rounds give a closer fit, but take longer.
+  pub iterations : u32
+}
+
+impl Default for QuantizeOptions {
+  fn default( ) -> QuantizeOptions {
+    QuantizeOptions {
+      reserved:   Vec::new( ),
+      locked:     Vec::new( ),
+      iterations: 8
+    }
+  }
+}
+
+/// The order of [`VoxScene::sort_palette`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteOrder {
+  /// Grays (from dark to light) come first, followed by the other colors
+  /// around the color wheel. (From red, through green and blue, to magenta)
+  /// Colors of equal hue are ordered from dark to light.
+  Hue,
+  /// From dark to light, by perceived lightness.
+  Luminance
+}
+
+/// Quantizes the colors into a palette of at most 255 colors. (See
+/// [`quantize_with`], which this calls with the default options)
+pub fn quantize< I >( colors: I ) -> Quantization
+    where I : IntoIterator< Item = Rgba > {
+  quantize_with( colors, &QuantizeOptions::default( ) )
+}
+
+/// Quantizes the colors into the palette entries that are neither reserved
+/// nor locked. Colors may occur multiple times, in which case they weigh more
+/// heavily.
+///
+/// When the distinct colors fit in the free entries, those are the palette
+/// exactly. (Colors equal to a locked entry use that entry) Otherwise, the
+/// colors are reduced with the median cut algorithm: The color space is
+/// recursively split into boxes at the median of their widest channel, where
+/// the box with the largest error is split first. Then, k-means rounds move
+/// every palette color to the average of the input colors closest to it.
+///
+/// When all entries are reserved, no colors are mapped.
+pub fn quantize_with< I >( colors: I, options: &QuantizeOptions ) -> Quantization
+    where I : IntoIterator< Item = Rgba > {
+  let mut counts: HashMap< Rgba, usize > = HashMap::new( );
+  for c in colors {
+    *counts.entry( c ).or_insert( 0 ) += 1;
+  }
+
+  let locked: Vec< (u8, Rgba) > =
+    options.locked.iter( ).copied( ).filter( |(i, _)| *i != 0 ).collect( );
+  let free: Vec< u8 > =
+    ( 1..=255 )
+      .filter( |i| !options.reserved.contains( i ) && !locked.iter( ).any( |l| l.0 == *i ) )
+      .collect( );
+
+  let mut lookup = HashMap::new( );
+  for (i, c) in &locked {
+    if counts.remove( c ).is_some( ) {
+      lookup.insert( *c, *i );
+    }
+  }
+
+  let mut entries: Vec< (Rgba, usize) > = counts.into_iter( ).collect( );
+  entries.sort( );
+
+  let mut used = locked.clone( );
+  if entries.len( ) <= free.len( ) {
+    for ((c, _), i) in entries.iter( ).zip( &free ) {
+      used.push( (*i, *c) );
+      lookup.insert( *c, *i );
+    }
+  } else if !free.is_empty( ) || !locked.is_empty( ) {
+    let points: Vec< (Lab, usize) > = entries.iter( ).map( |(c, n)| (to_lab( *c ), *n) ).collect( );
+    let locked_centers: Vec< Lab > = locked.iter( ).map( |(_, c)| to_lab( *c ) ).collect( );
+
+    let mut centers = median_cut( &points, free.len( ) );
+    let assignment = refine( &points, &mut centers, &locked_centers, options.iterations );
+
+    for (c, center) in free.iter( ).zip( &centers ) {
+      used.push( (*c, from_lab( *center )) );
+    }
+    for ((rgba, _), a) in entries.iter( ).zip( assignment ) {
+      // Assignments beyond the free centers are locked entries
+      let index = if a < centers.len( ) { free[ a ] } else { locked[ a - centers.len( ) ].0 };
+      lookup.insert( *rgba, index );
+    }
+  }
+
+  used.sort_by_key( |(i, _)| *i );
+  let mut colors = vec![ (0, 0, 0, 0); used.last( ).map_or( 0, |(i, _)| *i as usize ) ];
+  for (i, c) in &used {
+    colors[ *i as usize - 1 ] = *c;
+  }
+  Quantization { colors, entries: used, lookup }
+}
+
+/// Splits the weighted points into at most `k` boxes, and returns the
+/// weighted average of every box.
+fn median_cut( points: &[(Lab, usize)], k: usize ) -> Vec< Lab > {
+  if k == 0 {
+    return Vec::new( );
+  }
+
+  // The boxes, along with their errors
+  let mut boxes = vec![ (error( points ), points.to_vec( )) ];
+  while boxes.len( ) < k {
+    // Split the box with the largest error
+    let worst =
+      boxes.iter( ).enumerate( )
+        .filter( |(_, (_, b))| b.len( ) > 1 )
+        .max_by( |(_, a), (_, b)| a.0.total_cmp( &b.0 ) )
+        .map( |(i, _)| i );
+
+    match worst {
+      Some( i ) => {
+        let upper = split( &mut boxes[ i ].1 );
+        boxes[ i ].0 = error( &boxes[ i ].1 );
+        boxes.push( (error( &upper ), upper) );
+      },
+      None => { break; }
+    }
+  }
+
+  boxes.iter( ).map( |(_, b)| average( b ) ).collect( )
+}
+
+/// Performs k-means rounds, which move every center to the average of the
+/// points closest to it. Locked centers do not move. Returns the index of the
+/// closest center of every point, where the locked centers follow the others.
+fn refine( points: &[(Lab, usize)], centers: &mut [Lab], locked: &[Lab], iterations: u32 ) -> Vec< usize > {
+  let closest = |centers: &[Lab], p: Lab| {
+    let mut best = (0, f32::INFINITY);
+    for (i, c) in centers.iter( ).chain( locked ).enumerate( ) {
+      let d = dist( *c, p );
+      if d < best.1 {
+        best = (i, d);
+      }
+    }
+    best.0
+  };
+
+  let mut assignment: Vec< usize > = points.iter( ).map( |(p, _)| closest( centers, *p ) ).collect( );
+  for _ in 0..iterations {
+    let mut sums = vec![ ([0.0f32; 4], 0usize); centers.len( ) ];
+    for ((p, n), a) in points.iter( ).zip( &assignment ) {
+      if let Some( (sum, total) ) = sums.get_mut( *a ) {
+        for (s, v) in sum.iter_mut( ).zip( p ) {
+          *s += v * *n as f32;
+        }
+        *total += n;
+      }
+    }
+    // Centers without points stay in place
+    for (c, (sum, total)) in centers.iter_mut( ).zip( sums ) {
+      if total > 0 {
+        *c = sum.map( |s| s / total as f32 );
+      }
+    }
+
+    let next: Vec< usize > = points.iter( ).map( |(p, _)| closest( centers, *p ) ).collect( );
+    if next == assignment {
+      break;
+    }
+    assignment = next;
+  }
+  assignment
+}
+
+/// Returns the weighted sum of squared distances of the points to their
+/// average.
+fn error( b: &[(Lab, usize)] ) -> f32 {
+  let avg = average( b );
+  b.iter( ).map( |(p, n)| dist( *p, avg ) * *n as f32 ).sum( )
+}
+
+/// Splits the box (of at least 2 points) at the weighted median of its widest
+/// channel. The lower half remains in `b`, and the upper half is returned.
+fn split( b: &mut Vec< (Lab, usize) > ) -> Vec< (Lab, usize) > {
+  let range = |ch: usize| {
+    let values = b.iter( ).map( |(p, _)| p[ ch ] );
+    values.clone( ).fold( f32::MIN, f32::max ) - values.fold( f32::MAX, f32::min )
+  };
+  let ch = ( 0..4 ).max_by( |x, y| range( *x ).total_cmp( &range( *y ) ) ).unwrap_or( 0 );
+  b.sort_by( |x, y| x.0[ ch ].total_cmp( &y.0[ ch ] ) );
+
+  let total: usize = b.iter( ).map( |(_, n)| n ).sum( );
+  let mut acc = 0;
+  let mut at = 1;
+  for (i, (_, n)) in b.iter( ).enumerate( ) {
+    acc += n;
+    if acc * 2 >= total {
+      at = i + 1;
+      break;
+    }
+  }
+
+  // Both halves must contain a point
+  let at = at.min( b.len( ) - 1 );
+  b.split_off( at )
+}
+
+/// Returns the count-weighted average of the points.
+fn average( b: &[(Lab, usize)] ) -> Lab {
+  let total: usize = b.iter( ).map( |(_, n)| n ).sum( );
+  let mut sum = [0.0f32; 4];
+  for (p, n) in b {
+    for (s, v) in sum.iter_mut( ).zip( p ) {
+      *s += v * *n as f32;
+    }
+  }
+  sum.map( |s| s / total.max( 1 ) as f32 )
+}
+
+/// Returns the squared distance between the colors.
+fn dist( a: Lab, b: Lab ) -> f32 {
+  let d = [a[ 0 ] - b[ 0 ], a[ 1 ] - b[ 1 ], a[ 2 ] - b[ 2 ], a[ 3 ] - b[ 3 ]];
+  d[ 0 ] * d[ 0 ] + d[ 1 ] * d[ 1 ] + d[ 2 ] * d[ 2 ] + d[ 3 ] * d[ 3 ]
+}
+
+/// The white point (D65) of the XYZ color space
+const WHITE : [f32; 3] = [0.950_47, 1.0, 1.088_83];
+
+/// Converts an sRGB color to CIELAB. Alpha is scaled to the range of
+/// lightness (`0..=100`).
+fn to_lab( (r, g, b, a): Rgba ) -> Lab {
+  let linear = |c: u8| {
+    let c = c as f32 / 255.0;
+    if c <= 0.040_45 { c / 12.92 } else { ( ( c + 0.055 ) / 1.055 ).powf( 2.4 ) }
+  };
+  let (r, g, b) = ( linear( r ), linear( g ), linear( b ) );
+  let xyz =
+    [ 0.4124 * r + 0.3576 * g + 0.1805 * b
+    , 0.2126 * r + 0.7152 * g + 0.0722 * b
+    , 0.0193 * r + 0.1192 * g + 0.9505 * b
+    ];
+
+  let f = |t: f32| if t > 0.008_856 { t.cbrt( ) } else { 7.787 * t + 16.0 / 116.0 };
+  let [fx, fy, fz] = [0, 1, 2].map( |i| f( xyz[ i ] / WHITE[ i ] ) );
+  [116.0 * fy - 16.0, 500.0 * ( fx - fy ), 200.0 * ( fy - fz ), a as f32 * 100.0 / 255.0]
+}
+
+/// Converts a CIELAB color back to sRGB. (See [`to_lab`])
+fn from_lab( [l, a, b, alpha]: Lab ) -> Rgba {
+  let fy = ( l + 16.0 ) / 116.0;
+  let f = [fy + a / 500.0, fy, fy - b / 200.0];
+  let finv = |t: f32| if t * t * t > 0.008_856 { t * t * t } else { ( t - 16.0 / 116.0 ) / 7.787 };
+  let [x, y, z] = [0, 1, 2].map( |i| WHITE[ i ] * finv( f[ i ] ) );
+
+  let srgb = |c: f32| {
+    let c = if c <= 0.003_130_8 { 12.92 * c } else { 1.055 * c.powf( 1.0 / 2.4 ) - 0.055 };
+    ( c * 255.0 ).round( ).clamp( 0.0, 255.0 ) as u8
+  };
+  ( srgb(  3.2406 * x - 1.5372 * y - 0.4986 * z )
+  , srgb( -0.9689 * x + 1.8758 * y + 0.0415 * z )
+  , srgb(  0.0557 * x - 0.2040 * y + 1.0570 * z )
+  , ( alpha * 255.0 / 100.0 ).round( ).clamp( 0.0, 255.0 ) as u8
+  )
+}
+
+impl VoxScene {
+  /// Returns the number of voxels in all models which use every palette
+  /// index. (Where index 0 is always unused)
+  pub fn palette_usage( &self ) -> [usize; 256] {
+    let mut counts = [0; 256];
+    for m in &self.models {
+      for (_, _, _, i) in &m.xyzi {
+        counts[ *i as usize ] += 1;
+      }
+    }
+    counts[ 0 ] = 0;
+    counts
+  }
+
+  /// Moves every palette entry to a new index, where `permutation[i-1]` is the
+  /// new index of entry `i`.
+  ///
+  /// # Panics
+  /// When the permutation contains 0, or contains an index more than once.
+  pub fn permute_palette( &mut self, permutation: &[u8; 255] ) {
+    let mut is_taken = [false; 256];
+    for i in permutation {
+      assert!( *i != 0 && !is_taken[ *i as usize ], "Not a permutation of the palette" );
+      is_taken[ *i as usize ] = true;
+    }
+
+    let old = self.palette.clone( );
+    let mut map = [0; 256];
+    for (i, new) in permutation.iter( ).enumerate( ) {
+      self.palette[ *new as usize - 1 ] = old[ i ].clone( );
+      map[ i + 1 ] = *new;
+    }
+    self.remap_voxels( &map );
+  }
+
+  /// Swaps two palette entries.
+  ///
+  /// # Panics
+  /// When either index is 0.
+  pub fn swap_palette_entries( &mut self, a: u8, b: u8 ) {
+    assert!( a != 0 && b != 0, "Palette index 0 does not exist" );
+    let mut permutation = identity( );
+    permutation.swap( a as usize - 1, b as usize - 1 );
+    self.permute_palette( &permutation );
+  }
+
+  /// Makes all voxels with palette index `from` use index `into` instead. The
+  /// entry of `from` itself is unchanged, but no longer used.
+  ///
+  /// # Panics
+  /// When either index is 0.
+  pub fn merge_palette_entries( &mut self, from: u8, into: u8 ) {
+    assert!( from != 0 && into != 0, "Palette index 0 does not exist" );
+    let mut map = identity_map( );
+    map[ from as usize ] = into;
+    self.remap_voxels( &map );
+  }
+
+  /// Merges entries with identical materials (including their colors) into
+  /// the lowest such index. Returns the number of entries that were merged
+  /// away.
+  pub fn merge_identical_materials( &mut self ) -> usize {
+    let mut map = identity_map( );
+    let mut num_merged = 0;
+    for (i, m) in self.palette.iter( ).enumerate( ) {
+      let first = self.palette.iter( ).position( |p| p == m ).unwrap_or( i );
+      if first != i {
+        map[ i + 1 ] = first as u8 + 1;
+        num_merged += 1;
+      }
+    }
+    self.remap_voxels( &map );
+    num_merged
+  }
+
+  /// Moves the used palette entries to the front, while keeping their order.
+  /// The unused entries follow. Returns the number of used entries.
+  pub fn compact_palette( &mut self ) -> usize {
+    let usage = self.palette_usage( );
+    let mut indices: Vec< u8 > = ( 1..=255 ).collect( );
+    // Stable, so the order is kept within both groups
+    indices.sort_by_key( |i| usage[ *i as usize ] == 0 );
+    self.permute_palette( &inverse( &indices ) );
+    usage.iter( ).filter( |n| **n > 0 ).count( )
+  }
+
+  /// Sorts all palette entries by their colors.
+  pub fn sort_palette( &mut self, order: PaletteOrder ) {
+    let lightness = |m: &Material| to_lab( m.rgba )[ 0 ];
+    let key = |m: &Material| -> (u8, f32, f32) {
+      match order {
+        PaletteOrder::Luminance => (0, 0.0, lightness( m )),
+        PaletteOrder::Hue       =>
+          match hue( m.rgba ) {
+            Some( h ) => (1, h, lightness( m )),
+            None      => (0, 0.0, lightness( m ))
+          }
+      }
+    };
+
+    let mut indices: Vec< u8 > = ( 1..=255 ).collect( );
+    indices.sort_by( |a, b| {
+      let (ka, kb) = ( key( &self.palette[ *a as usize - 1 ] ), key( &self.palette[ *b as usize - 1 ] ) );
+      ka.0.cmp( &kb.0 ).then( ka.1.total_cmp( &kb.1 ) ).then( ka.2.total_cmp( &kb.2 ) )
+    } );
+    self.permute_palette( &inverse( &indices ) );
+  }
+
+  /// Replaces the palette by another palette (e.g., from another file). Every
+  /// voxel uses the entry with an identical material, if there is one.
+  /// Otherwise, it uses the entry of the closest color.
+  pub fn match_palette( &mut self, palette: &[Material; 255] ) {
+    let usage = self.palette_usage( );
+    let mut map = identity_map( );
+
+    for i in ( 1..=255 ).filter( |i| usage[ *i ] > 0 ) {
+      let m = &self.palette[ i - 1 ];
+      let closest =
+        palette.iter( ).position( |p| p == m ).unwrap_or_else( || {
+          let lab = to_lab( m.rgba );
+          palette.iter( ).enumerate( )
+            .min_by( |(_, a), (_, b)| dist( to_lab( a.rgba ), lab ).total_cmp( &dist( to_lab( b.rgba ), lab ) ) )
+            .map_or( 0, |(j, _)| j )
+        } );
+      map[ i ] = closest as u8 + 1;
+    }
+
+    self.palette = palette.clone( );
+    self.remap_voxels( &map );
+  }
+
+  /// Replaces the palette index `i` of every voxel by `map[i]`.
+  fn remap_voxels( &mut self, map: &[u8; 256] ) {
+    for m in &mut self.models {
+      for (_, _, _, i) in &mut m.xyzi {
+        *i = map[ *i as usize ];
+      }
+    }
+  }
+}
+
+/// Returns the permutation which keeps every palette entry in place.
+fn identity( ) -> [u8; 255] {
+  let mut permutation = [0; 255];
+  for (i, p) in permutation.iter_mut( ).enumerate( ) {
+    *p = i as u8 + 1;
+  }
+  permutation
+}
+
+/// Returns the map of palette indices which keeps every index (including 0).
+fn identity_map( ) -> [u8; 256] {
+  let mut map = [0; 256];
+  for (i, m) in map.iter_mut( ).enumerate( ) {
+    *m = i as u8;
+  }
+  map
+}
+
+/// Returns the permutation that moves entry `order[k]` to index `k+1`.
+fn inverse( order: &[u8] ) -> [u8; 255] {
+  let mut permutation = [0; 255];
+  for (k, i) in order.iter( ).enumerate( ) {
+    permutation[ *i as usize - 1 ] = k as u8 + 1;
+  }
+  permutation
+}
+
+/// Returns the hue of the color in degrees (`0.0..360.0`), or `None` if the
+/// color is (nearly) gray.
+fn hue( (r, g, b, _): Rgba ) -> Option< f32 > {
+  let (r, g, b) = ( r as f32, g as f32, b as f32 );
+  let max = r.max( g ).max( b );
+  let min = r.min( g ).min( b );
+  let chroma = max - min;
+  // Below this, hues are indistinguishable
+  if chroma < 8.0 {
+    return None;
+  }
+
+  let h =
+    if max == r {
+      ( ( g - b ) / chroma ).rem_euclid( 6.0 )
+    } else if max == g {
+      ( b - r ) / chroma + 2.0
+    } else {
+      ( r - g ) / chroma + 4.0
+    };
+  Some( h * 60.0 )
+}
@@ -6,12 +6,19 @@ use std::collections::HashMap;
 use nom::combinator::all_consuming;
 use nom::number::complete::le_i32;
 // Local imports
-use crate::data::spec::{Matl, MatlType};
+use crate::data::spec::{Matl, MatlMediaType, MatlType};
 use crate::parse::error::VoxErrorKind;
 use crate::parse::helpers::{IResult, failure};
 use crate::parse::special::dict;
 
 
+/// The properties which are fields of [`Matl`]. Other properties are kept in
+/// [`Matl::unknown_props`].
+const KNOWN_PROPS: [&str; 15] =
+  [ "_type", "_weight", "_rough", "_spec", "_ior", "_att", "_flux", "_d"
+  , "_alpha", "_emit", "_ldr", "_metal", "_plastic", "_sp", "_g"
+  ];
+
 /// Parses the payload of a MATL chunk, or fails if bytes are remaining.
 /// See `chunk_matl`.
 pub fn chunk_matl_all< 'a >( input: &'a [u8] ) -> IResult< &'a [u8], Matl > {
@@ -58,6 +65,25 @@ pub fn chunk_matl< 'a >(input: &'a [u8]) -> IResult<&'a [u8], Matl> {
   let (_, prop_ldr)     = prop_f32( &properties,  "_ldr",    |_| true, input )?;
   let (_, prop_metal)   = prop_f32( &properties,  "_metal",  |_| true, input )?;
   let (_, prop_plastic) = prop_bool( &properties, "_plastic", input )?;
+  let (_, prop_sp)      = prop_f32( &properties,  "_sp",     |_| true, input )?;
+  let (_, prop_g)       = prop_f32( &properties,  "_g",      |_| true, input )?;
+
+  // Unrecognized media types are kept as unknown properties
+  let prop_media_type =
+    match properties.get( "_media_type" ) {
+      Some( &"_absorb" )  => Some( MatlMediaType::Absorb ),
+      Some( &"_scatter" ) => Some( MatlMediaType::Scatter ),
+      Some( &"_emit" )    => Some( MatlMediaType::Emit ),
+      Some( &"_sss" )     => Some( MatlMediaType::Sss ),
+      _ => None
+    };
+
+  let unknown_props =
+    properties.iter( )
+      .filter( |(k, _)| !KNOWN_PROPS.contains( k ) )
+      .filter( |(k, _)| **k != "_media_type" || prop_media_type.is_none( ) )
+      .map( |(k, v)| (k.to_string( ), v.to_string( )) )
+      .collect( );
 
   let matl =
     Matl {
@@ -75,7 +101,11 @@ pub fn chunk_matl< 'a >(input: &'a [u8]) -> IResult<&'a [u8], Matl> {
       prop_emit,
       prop_ldr,
       prop_metal,
-      prop_plastic
+      prop_plastic,
+      prop_sp,
+      prop_g,
+      prop_media_type,
+      unknown_props
     };
 
   nom::IResult::Ok((input2, matl))
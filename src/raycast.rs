@@ -0,0 +1,239 @@
+//! Ray casting against voxel models and scenes.
+//!
+//! Rays are traversed voxel-by-voxel with a 3D digital differential analyser
+//! (DDA), which visits exactly the voxels pierced by the ray. Voxel `(x,y,z)`
+//! is the unit cube with its lowest corner at `(x,y,z)`.
+//!
+//! Scenes are cast against in world space, where every model is placed by the
+//! scene graph. (See [`Instance`])
+//!
+//! # Example: Pick the voxel below a point
+//!
+//! ```
+//! use vox_parser::data::custom::Model;
+//! use vox_parser::raycast::{self, Ray};
+//!
+//! let model = Model { size: (4, 4, 4), xyzi: vec![ (1,2,0,5) ] };
+//! let ray = Ray { origin: (1.5, 2.5, 10.0), direction: (0.0, 0.0, -1.0) };
+//!
+//! let hit = raycast::model( &model, &ray, f32::INFINITY ).unwrap( );
+//! assert_eq!( hit.voxel, (1,2,0) );
+//! assert_eq!( hit.normal, (0,0,1) );
+//! assert_eq!( hit.palette_index, 5 );
+//! ```
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::custom::{Instance, Model, VoxScene};
+use crate::storage::{DenseGrid, VoxelStorage};
+
+
+/// A half-line, starting at `origin` and extending in `direction`.
+///
+/// The direction need not be normalized. Distances along the ray are measured
+/// in multiples of the direction's length. Rays with a zero direction, or with
+/// non-finite components, hit nothing.
+#[derive(Debug, Clone, Copy)]
+pub struct Ray {
+  pub origin    : (f32, f32, f32),
+  pub direction : (f32, f32, f32)
+}
+
+/// The first voxel hit by a ray.
+#[derive(Debug, Clone, Copy)]
+pub struct RayHit {
+  /// The distance along the ray, at which the voxel is entered. The entry
+  /// point is `origin + distance * direction`.
+  pub distance      : f32,
+  /// The hit voxel, in the space of the ray. For scenes, this is the world
+  /// voxel occupied by the hit voxel.
+  pub voxel         : (i32, i32, i32),
+  /// The hit voxel, within its model.
+  pub local_voxel   : (i32, i32, i32),
+  /// Normal of the face through which the ray entered the voxel, in the space
+  /// of the ray. When the ray starts inside the voxel, the normal is
+  /// `(0,0,0)`.
+  pub normal        : (i32, i32, i32),
+  pub palette_index : u8,
+  /// The index of the hit instance in [`VoxScene::instances`]. `None` when
+  /// casting against a single model.
+  pub instance      : Option< usize >
+}
+
+/// Casts the ray against a single model, and returns the first hit voxel. The
+/// ray is in the space of the model.
+///
+/// Only voxels within `max_distance` are considered. (This may be
+/// [`f32::INFINITY`])
+///
+/// This converts the model to a [`DenseGrid`]. When casting many rays against
+/// the same model, convert it once and use [`storage`] instead.
+pub fn model( model: &Model, ray: &Ray, max_distance: f32 ) -> Option< RayHit > {
+  storage( &DenseGrid::from_model( model ), ray, max_distance )
+}
+
+/// Casts the ray against the voxels in the storage, and returns the first hit
+/// voxel.
+///
+/// Only voxels within `max_distance` are considered. (This may be
+/// [`f32::INFINITY`])
+pub fn storage< S: VoxelStorage + ?Sized >( s: &S, ray: &Ray, max_distance: f32 ) -> Option< RayHit > {
+  let origin = [ray.origin.0, ray.origin.1, ray.origin.2];
+  let dir    = [ray.direction.0, ray.direction.1, ray.direction.2];
+
+  // Such rays never advance, so the traversal would not end
+  if origin.iter( ).chain( &dir ).any( |v| !v.is_finite( ) ) || dir.iter( ).all( |d| *d == 0.0 ) {
+    return None;
+  }
+  let (x_size, y_size, z_size) = s.size( );
+  let size   = [x_size as f32, y_size as f32, z_size as f32];
+
+  // Clip the ray to the bounding box of the storage
+  let mut t_enter = 0.0f32;
+  let mut t_exit  = max_distance;
+  let mut enter_axis = None;
+
+  for axis in 0..3 {
+    if dir[ axis ] == 0.0 {
+      if origin[ axis ] < 0.0 || origin[ axis ] >= size[ axis ] {
+        return None;
+      }
+    } else {
+      let t0 = ( 0.0 - origin[ axis ] ) / dir[ axis ];
+      let t1 = ( size[ axis ] - origin[ axis ] ) / dir[ axis ];
+      let (t_near, t_far) = if t0 < t1 { (t0, t1) } else { (t1, t0) };
+
+      if t_near > t_enter {
+        t_enter = t_near;
+        enter_axis = Some( axis );
+      }
+      t_exit = t_exit.min( t_far );
+    }
+  }
+
+  if t_enter > t_exit {
+    return None;
+  }
+
+  // The voxel containing the entry point. Clamp it, as rounding errors may
+  // place it just outside the bounds.
+  let mut voxel = [0i32; 3];
+  for axis in 0..3 {
+    let p = origin[ axis ] + t_enter * dir[ axis ];
+    voxel[ axis ] = ( p.floor( ) as i32 ).max( 0 ).min( size[ axis ] as i32 - 1 );
+  }
+
+  let mut step    = [0i32; 3];
+  let mut t_max   = [f32::INFINITY; 3];
+  let mut t_delta = [f32::INFINITY; 3];
+
+  for axis in 0..3 {
+    if dir[ axis ] > 0.0 {
+      step[ axis ]    = 1;
+      t_max[ axis ]   = ( ( voxel[ axis ] + 1 ) as f32 - origin[ axis ] ) / dir[ axis ];
+      t_delta[ axis ] = 1.0 / dir[ axis ];
+    } else if dir[ axis ] < 0.0 {
+      step[ axis ]    = -1;
+      t_max[ axis ]   = ( voxel[ axis ] as f32 - origin[ axis ] ) / dir[ axis ];
+      t_delta[ axis ] = -1.0 / dir[ axis ];
+    }
+  }
+
+  let mut normal = [0i32; 3];
+  if let Some( axis ) = enter_axis {
+    normal[ axis ] = -step[ axis ];
+  }
+  let mut t = t_enter;
+
+  loop {
+    let pos = (voxel[ 0 ], voxel[ 1 ], voxel[ 2 ]);
+
+    match s.get( pos ) {
+      Some( 0 ) => { },
+      Some( palette_index ) =>
+        return Some(
+          RayHit {
+            distance: t,
+            voxel: pos,
+            local_voxel: pos,
+            normal: (normal[ 0 ], normal[ 1 ], normal[ 2 ]),
+            palette_index,
+            instance: None
+          }
+        ),
+      None => { return None; } // Left the bounds
+    }
+
+    // Step into the neighbouring voxel whose boundary is closest
+    let axis =
+      if t_max[ 0 ] <= t_max[ 1 ] && t_max[ 0 ] <= t_max[ 2 ] {
+        0
+      } else if t_max[ 1 ] <= t_max[ 2 ] {
+        1
+      } else {
+        2
+      };
+
+    t = t_max[ axis ];
+    // No boundary is left when the direction is too small to ever cross one
+    if t > t_exit || step[ axis ] == 0 {
+      return None;
+    }
+    voxel[ axis ] += step[ axis ];
+    t_max[ axis ] += t_delta[ axis ];
+    normal = [0; 3];
+    normal[ axis ] = -step[ axis ];
+  }
+}
+
+/// Casts the ray against all models placed in the scene, and returns the first
+/// hit voxel. The ray is in world space.
+///
+/// Only voxels within `max_distance` are considered. (This may be
+/// [`f32::INFINITY`]) Models on hidden layers are ignored.
+pub fn scene( scene: &VoxScene, ray: &Ray, max_distance: f32 ) -> Option< RayHit > {
+  let mut grids: HashMap< u32, DenseGrid > = HashMap::new( );
+  let mut best: Option< RayHit > = None;
+
+  for (instance_id, instance) in scene.instances( ).iter( ).enumerate( ) {
+    if scene.is_hidden( instance ) {
+      continue;
+    }
+
+    let model = &scene.models[ instance.model_id as usize ];
+    let grid =
+      grids.entry( instance.model_id ).or_insert_with( || DenseGrid::from_model( model ) );
+
+    let local_ray = to_local_ray( instance, model, ray );
+    let max_dist  = best.map_or( max_distance, |b| b.distance );
+
+    if let Some( hit ) = storage( grid, &local_ray, max_dist ) {
+      if best.map_or( true, |b| hit.distance < b.distance ) {
+        best =
+          Some(
+            RayHit {
+              voxel:    instance.voxel_to_world( model, hit.local_voxel ),
+              normal:   instance.rotation.apply_to( hit.normal ),
+              instance: Some( instance_id ),
+              ..hit
+            }
+          );
+      }
+    }
+  }
+
+  best
+}
+
+/// Maps a ray in world space to the space of the instance's model.
+///
+/// As the rotation preserves lengths, distances along the ray are identical
+/// in both spaces.
+fn to_local_ray( instance: &Instance, model: &Model, ray: &Ray ) -> Ray {
+  Ray {
+    origin:    instance.point_to_local( model, ray.origin ),
+    direction: instance.rotation.inverse( ).apply_to_f32( ray.direction )
+  }
+}
@@ -0,0 +1,222 @@
+//! Spatial queries over models and scenes.
+//!
+//! This module provides axis-aligned bounding boxes ([`Aabb`]), and methods on
+//! [`Model`], [`Instance`], and [`VoxScene`] for computing bounds and querying
+//! voxels by their world position.
+//!
+//! All scene queries consider every instance in the scene graph, including
+//! those on hidden layers. (See [`VoxScene::is_hidden`] to filter these)
+//!
+//! # Example: Find the voxel at a world position
+//!
+//! ```
+//! use vox_parser::builder::{NodeBuilder, SceneBuilder};
+//!
+//! let mut builder = SceneBuilder::new( );
+//! let model = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2) ] );
+//! builder.add( NodeBuilder::shape( model ).translation( (10, 0, 0) ) );
+//! let scene = builder.build( ).unwrap( );
+//!
+//! // The model of size (2,1,1) is centered at (10,0,0).
+//! let bounds = scene.bounds( ).unwrap( );
+//! assert_eq!( (bounds.min, bounds.max), ((9,0,0), (11,1,1)) );
+//! assert_eq!( scene.voxel_at( (10,0,0) ).unwrap( ).palette_index, 2 );
+//! ```
+
+
+// Local imports
+use crate::data::custom::{Instance, Model, VoxScene};
+
+
+/// An axis-aligned box of voxels.
+///
+/// The box contains the voxels from `min` (inclusive) to `max` (exclusive).
+/// When viewed as a volume, voxel `(x,y,z)` is the unit cube with its lowest
+/// corner at `(x,y,z)`, so the box spans the volume from `min` to `max`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Aabb {
+  pub min : (i32,i32,i32),
+  pub max : (i32,i32,i32)
+}
+
+/// A voxel positioned in the world by the scene graph. (Returned by the scene
+/// queries)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WorldVoxel {
+  /// The voxel occupied in the world
+  pub position      : (i32,i32,i32),
+  /// The position of the voxel within its model
+  pub local         : (u8,u8,u8),
+  pub palette_index : u8,
+  /// The index of the containing instance in [`VoxScene::instances`]
+  pub instance      : usize
+}
+
+impl Aabb {
+  /// Constructs the box spanning the voxels of a model of the given size; i.e.,
+  /// from `(0,0,0)` to `size`.
+  pub fn from_size( (x_size, y_size, z_size): (u32,u32,u32) ) -> Aabb {
+    Aabb { min: (0,0,0), max: (x_size as i32, y_size as i32, z_size as i32) }
+  }
+
+  /// Returns the size of the box along every axis.
+  pub fn size( &self ) -> (u32,u32,u32) {
+    ( ( self.max.0 - self.min.0 ).max( 0 ) as u32
+    , ( self.max.1 - self.min.1 ).max( 0 ) as u32
+    , ( self.max.2 - self.min.2 ).max( 0 ) as u32
+    )
+  }
+
+  /// Returns `true` iff the box contains no voxels.
+  pub fn is_empty( &self ) -> bool {
+    self.min.0 >= self.max.0 || self.min.1 >= self.max.1 || self.min.2 >= self.max.2
+  }
+
+  /// Returns `true` iff the voxel is inside the box.
+  pub fn contains( &self, (x,y,z): (i32,i32,i32) ) -> bool {
+    x >= self.min.0 && y >= self.min.1 && z >= self.min.2
+      && x < self.max.0 && y < self.max.1 && z < self.max.2
+  }
+
+  /// Returns `true` iff the boxes have at least one voxel in common.
+  pub fn intersects( &self, other: &Aabb ) -> bool {
+    self.intersection( other ).is_some( )
+  }
+
+  /// Returns the voxels the boxes have in common, or `None` if they do not
+  /// overlap.
+  pub fn intersection( &self, other: &Aabb ) -> Option< Aabb > {
+    let b =
+      Aabb {
+        min: ( self.min.0.max( other.min.0 ), self.min.1.max( other.min.1 ), self.min.2.max( other.min.2 ) ),
+        max: ( self.max.0.min( other.max.0 ), self.max.1.min( other.max.1 ), self.max.2.min( other.max.2 ) )
+      };
+
+    if b.is_empty( ) {
+      None
+    } else {
+      Some( b )
+    }
+  }
+
+  /// Returns the smallest box containing both boxes.
+  pub fn union( &self, other: &Aabb ) -> Aabb {
+    Aabb {
+      min: ( self.min.0.min( other.min.0 ), self.min.1.min( other.min.1 ), self.min.2.min( other.min.2 ) ),
+      max: ( self.max.0.max( other.max.0 ), self.max.1.max( other.max.1 ), self.max.2.max( other.max.2 ) )
+    }
+  }
+
+  /// Returns the smallest box containing all given voxels, or `None` if there
+  /// are no voxels.
+  pub fn enclosing< I >( voxels: I ) -> Option< Aabb >
+      where I : IntoIterator< Item = (i32,i32,i32) > {
+    voxels.into_iter( )
+      .map( |(x,y,z)| Aabb { min: (x,y,z), max: (x + 1, y + 1, z + 1) } )
+      .reduce( |a, b| a.union( &b ) )
+  }
+}
+
+impl Model {
+  /// Returns the tight bounds of the voxels in the model, or `None` if the
+  /// model contains no voxels.
+  ///
+  /// These bounds may be smaller than the declared size of the model. (For
+  /// those bounds, see [`Aabb::from_size`])
+  pub fn bounds( &self ) -> Option< Aabb > {
+    Aabb::enclosing(
+      self.xyzi.iter( ).map( |(x,y,z,_)| (*x as i32, *y as i32, *z as i32) )
+    )
+  }
+}
+
+impl Instance {
+  /// Returns the bounds of the instance's model in world space. These bounds
+  /// span the entire declared size of the model.
+  pub fn bounds( &self, model: &Model ) -> Aabb {
+    self.box_to_world( model, Aabb::from_size( model.size ) )
+  }
+
+  /// Returns the tight bounds of the instance's voxels in world space, or
+  /// `None` if the model contains no voxels.
+  pub fn tight_bounds( &self, model: &Model ) -> Option< Aabb > {
+    model.bounds( ).map( |b| self.box_to_world( model, b ) )
+  }
+
+  /// Maps a box in the model to its box in world space.
+  fn box_to_world( &self, model: &Model, b: Aabb ) -> Aabb {
+    if b.is_empty( ) {
+      return Aabb { min: self.translation, max: self.translation };
+    }
+
+    // The voxels in the opposing corners of the box remain in opposing corners
+    let (x0, y0, z0) = self.voxel_to_world( model, b.min );
+    let (x1, y1, z1) = self.voxel_to_world( model, ( b.max.0 - 1, b.max.1 - 1, b.max.2 - 1 ) );
+
+    Aabb {
+      min: ( x0.min( x1 ), y0.min( y1 ), z0.min( z1 ) ),
+      max: ( x0.max( x1 ) + 1, y0.max( y1 ) + 1, z0.max( z1 ) + 1 )
+    }
+  }
+}
+
+impl VoxScene {
+  /// Returns the world bounds of every instance, in the order of
+  /// [`VoxScene::instances`]. These bounds span the entire declared sizes of
+  /// the models.
+  pub fn instance_bounds( &self ) -> Vec< Aabb > {
+    self.instances( ).iter( )
+      .map( |inst| inst.bounds( &self.models[ inst.model_id as usize ] ) )
+      .collect( )
+  }
+
+  /// Returns the tight world bounds of all voxels in the scene, or `None` if
+  /// the scene contains no voxels.
+  pub fn bounds( &self ) -> Option< Aabb > {
+    self.instances( ).iter( )
+      .filter_map( |inst| inst.tight_bounds( &self.models[ inst.model_id as usize ] ) )
+      .reduce( |a, b| a.union( &b ) )
+  }
+
+  /// Returns the voxel at the given world position, or `None` if the position
+  /// is empty. When multiple instances overlap at the position, the voxel of
+  /// the first instance (in the order of [`VoxScene::instances`]) is returned.
+  ///
+  /// Every call scans the voxels of all instances overlapping the position.
+  /// For many lookups, consider collecting the voxels of
+  /// [`VoxScene::voxels_in`] into a lookup structure instead.
+  pub fn voxel_at( &self, pos: (i32,i32,i32) ) -> Option< WorldVoxel > {
+    let region = Aabb { min: pos, max: ( pos.0 + 1, pos.1 + 1, pos.2 + 1 ) };
+    self.voxels_in( &region ).into_iter( ).next( )
+  }
+
+  /// Returns all voxels whose world position is inside the region. The voxels
+  /// are ordered by instance (in the order of [`VoxScene::instances`]), and
+  /// then by their order within the model.
+  pub fn voxels_in( &self, region: &Aabb ) -> Vec< WorldVoxel > {
+    let mut dst = Vec::new( );
+
+    for (instance_id, inst) in self.instances( ).iter( ).enumerate( ) {
+      let model = &self.models[ inst.model_id as usize ];
+      if !inst.bounds( model ).intersects( region ) {
+        continue;
+      }
+
+      for (x,y,z,i) in &model.xyzi {
+        let position = inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) );
+        if region.contains( position ) {
+          dst.push(
+            WorldVoxel {
+              position,
+              local: (*x, *y, *z),
+              palette_index: *i,
+              instance: instance_id
+            }
+          );
+        }
+      }
+    }
+
+    dst
+  }
+}
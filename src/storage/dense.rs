@@ -0,0 +1,126 @@
+//! Dense voxel grid, which stores a palette index for every position.
+
+
+// Local imports
+use crate::data::custom::Model;
+use crate::storage::VoxelStorage;
+
+
+/// A dense 3D grid of palette indices.
+///
+/// Every position within the bounds stores a palette index, where palette
+/// index 0 represents empty space. Memory usage is thus proportional to the
+/// volume of the grid (`x_size * y_size * z_size` bytes).
+///
+/// Voxels are laid out in x/y/z order; i.e., the _x_ coordinate changes
+/// fastest, and the _z_ coordinate slowest. [`DenseGrid::iter`] (and
+/// [`VoxelStorage::voxels`]) iterate in this order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DenseGrid {
+  size : (u32, u32, u32),
+  data : Vec< u8 >
+}
+
+impl DenseGrid {
+  /// Constructs an empty grid of the given size `(x_size, y_size, z_size)`.
+  pub fn new( size: (u32, u32, u32) ) -> DenseGrid {
+    let len = size.0 as usize * size.1 as usize * size.2 as usize;
+    DenseGrid { size, data: vec![ 0; len ] }
+  }
+
+  /// Constructs a grid with the size and voxels of the model. (See
+  /// [`VoxelStorage::from_model`])
+  pub fn from_model( model: &Model ) -> DenseGrid {
+    < DenseGrid as VoxelStorage >::from_model( model )
+  }
+
+  /// Converts the grid back into a model of the same size. (See
+  /// [`VoxelStorage::to_model`])
+  pub fn to_model( &self ) -> Model {
+    < DenseGrid as VoxelStorage >::to_model( self )
+  }
+
+  /// Returns the size of the grid `(x_size, y_size, z_size)`.
+  pub fn size( &self ) -> (u32, u32, u32) {
+    self.size
+  }
+
+  /// Returns `true` iff the position is within the bounds of the grid.
+  pub fn contains( &self, pos: (i32, i32, i32) ) -> bool {
+    self.index( pos ).is_some( )
+  }
+
+  /// Returns the palette index at the given position, or `None` if the
+  /// position is out of bounds. Empty positions have palette index 0.
+  pub fn get( &self, pos: (i32, i32, i32) ) -> Option< u8 > {
+    self.index( pos ).map( |i| self.data[ i ] )
+  }
+
+  /// Assigns the palette index at the given position. Palette index 0 clears
+  /// the voxel.
+  ///
+  /// Returns `false` if the position is out of bounds, in which case the grid
+  /// is unchanged.
+  pub fn set( &mut self, pos: (i32, i32, i32), palette_index: u8 ) -> bool {
+    if let Some( i ) = self.index( pos ) {
+      self.data[ i ] = palette_index;
+      true
+    } else {
+      false
+    }
+  }
+
+  /// Iterates over all non-empty voxels in x/y/z order. Every element is a
+  /// position and its palette index.
+  pub fn iter( &self ) -> impl Iterator< Item = ((i32, i32, i32), u8) > + '_ {
+    let (x_size, y_size, _) = self.size;
+    let (x_size, y_size) = (x_size as usize, y_size as usize);
+
+    self.data.iter( ).enumerate( )
+      .filter( |(_, i)| **i != 0 )
+      .map( move |(index, i)| {
+        let x = index % x_size;
+        let y = ( index / x_size ) % y_size;
+        let z = index / ( x_size * y_size );
+        ((x as i32, y as i32, z as i32), *i)
+      } )
+  }
+
+  /// Returns the index into `data` of the given position, or `None` if the
+  /// position is out of bounds.
+  fn index( &self, (x,y,z): (i32, i32, i32) ) -> Option< usize > {
+    let (x_size, y_size, z_size) = self.size;
+
+    if x < 0 || y < 0 || z < 0
+        || x as u32 >= x_size || y as u32 >= y_size || z as u32 >= z_size {
+      None
+    } else {
+      let (x_size, y_size) = (x_size as usize, y_size as usize);
+      Some( x as usize + x_size * ( y as usize + y_size * z as usize ) )
+    }
+  }
+}
+
+// The inherent accessors take precedence, so the trait forwards to them. The
+// model conversions are the defaults of the trait.
+impl VoxelStorage for DenseGrid {
+  fn new( size: (u32, u32, u32) ) -> DenseGrid {
+    DenseGrid::new( size )
+  }
+
+  fn size( &self ) -> (u32, u32, u32) {
+    self.size
+  }
+
+  fn get( &self, pos: (i32, i32, i32) ) -> Option< u8 > {
+    DenseGrid::get( self, pos )
+  }
+
+  fn set( &mut self, pos: (i32, i32, i32), palette_index: u8 ) -> bool {
+    DenseGrid::set( self, pos, palette_index )
+  }
+
+  fn voxels( &self ) -> Box< dyn Iterator< Item = ((i32, i32, i32), u8) > + '_ > {
+    Box::new( self.iter( ) )
+  }
+}
@@ -0,0 +1,110 @@
+//! Random-access voxel storage.
+//! 
+//! The voxels of a [`Model`] are stored as a flat list, which makes looking up
+//! a voxel by its coordinate expensive. The structures in this module offer
+//! (cheap) random access, and convert losslessly to and from models.
+//! 
+//! Three storage backends share the [`VoxelStorage`] interface:
+//! * [`DenseGrid`] - Stores every position. Fastest, but its memory usage is
+//!   proportional to the volume of the model.
+//! * [`SparseGrid`] - Stores only non-empty voxels in a hash map. Suitable for
+//!   large, mostly-empty models.
+//! * [`Octree`] - Stores uniform regions as a single node. Suitable for large
+//!   models with big solid or empty regions.
+//! 
+//! Positions are signed, such that neighbours of voxels on the boundary can be
+//! queried without special care; positions outside the bounds simply contain
+//! no voxel.
+//! 
+//! # Example: Count voxels without a voxel above them
+//! 
+//! ```
+//! use vox_parser::data::custom::Model;
+//! use vox_parser::storage::DenseGrid;
+//! 
+//! let model = Model { size: (2, 2, 2), xyzi: vec![ (0,0,0,1), (0,0,1,1), (1,1,0,1) ] };
+//! let grid = DenseGrid::from_model( &model );
+//! 
+//! let num_top =
+//!   grid.iter( )
+//!     .filter( |((x,y,z),_)| grid.get( (*x, *y, *z + 1) ).unwrap_or( 0 ) == 0 )
+//!     .count( );
+//! assert_eq!( num_top, 2 );
+//! ```
+
+
+mod dense;
+mod octree;
+mod sparse;
+
+pub use self::dense::DenseGrid;
+pub use self::octree::Octree;
+pub use self::sparse::SparseGrid;
+
+// Local imports
+use crate::data::custom::Model;
+
+
+/// Common interface of the voxel storage backends.
+/// 
+/// Every storage has fixed bounds, given by its size `(x_size, y_size,
+/// z_size)`. Each position within these bounds stores a palette index, where
+/// palette index 0 represents empty space.
+pub trait VoxelStorage {
+  /// Constructs an empty storage of the given size.
+  fn new( size: (u32, u32, u32) ) -> Self where Self : Sized;
+
+  /// Returns the size of the storage `(x_size, y_size, z_size)`.
+  fn size( &self ) -> (u32, u32, u32);
+
+  /// Returns the palette index at the given position, or `None` if the
+  /// position is out of bounds. Empty positions have palette index 0.
+  fn get( &self, pos: (i32, i32, i32) ) -> Option< u8 >;
+
+  /// Assigns the palette index at the given position. Palette index 0 clears
+  /// the voxel.
+  /// 
+  /// Returns `false` if the position is out of bounds, in which case the
+  /// storage is unchanged.
+  fn set( &mut self, pos: (i32, i32, i32), palette_index: u8 ) -> bool;
+
+  /// Iterates over all non-empty voxels. Every element is a position and its
+  /// palette index. The order depends on the backend.
+  fn voxels( &self ) -> Box< dyn Iterator< Item = ((i32, i32, i32), u8) > + '_ >;
+
+  /// Returns `true` iff the position is within the bounds of the storage.
+  fn contains( &self, (x,y,z): (i32, i32, i32) ) -> bool {
+    let (x_size, y_size, z_size) = self.size( );
+    x >= 0 && y >= 0 && z >= 0
+      && ( x as u32 ) < x_size && ( y as u32 ) < y_size && ( z as u32 ) < z_size
+  }
+
+  /// Constructs a storage with the size and voxels of the model.
+  /// 
+  /// Voxels outside the bounds of the model are ignored. When the model
+  /// contains multiple voxels at the same position, the last one is kept.
+  fn from_model( model: &Model ) -> Self where Self : Sized {
+    let mut storage = Self::new( model.size );
+    for (x,y,z,i) in &model.xyzi {
+      storage.set( (*x as i32, *y as i32, *z as i32), *i );
+    }
+    storage
+  }
+
+  /// Converts the storage back into a model of the same size. The voxels are
+  /// stored in x/y/z order (i.e., sorted by _z_, then _y_, then _x_), such
+  /// that all backends produce identical models.
+  /// 
+  /// Note that models cannot contain voxels at coordinates above 255. Such
+  /// voxels (which only exist in storages larger than 256) are omitted.
+  fn to_model( &self ) -> Model {
+    let mut xyzi: Vec< (u8,u8,u8,u8) > =
+      self.voxels( )
+        .filter( |((x,y,z),_)| *x < 256 && *y < 256 && *z < 256 )
+        .map( |((x,y,z),i)| (x as u8, y as u8, z as u8, i) )
+        .collect( );
+    xyzi.sort_by_key( |(x,y,z,_)| (*z, *y, *x) );
+
+    Model { size: self.size( ), xyzi }
+  }
+}
@@ -0,0 +1,168 @@
+//! Octree voxel storage, which compresses uniform regions.
+
+
+// Local imports
+use crate::storage::VoxelStorage;
+
+
+/// A 3D grid of palette indices, backed by an octree.
+///
+/// The octree recursively subdivides a cube into 8 octants, until the octant
+/// is uniform (i.e., all its positions have the same palette index). Large
+/// solid or empty regions are thus stored as a single node. Voxels are
+/// iterated in depth-first order over the octants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Octree {
+  size  : (u32, u32, u32),
+  /// Side length of the cube covered by the root node. Always a power of two.
+  width : u32,
+  root  : Node
+}
+
+/// Internal. A node within the octree, which covers a cube.
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+  /// The entire cube contains the same palette index.
+  Uniform( u8 ),
+  /// The cube is split into 8 octants. The octant index has its bits set for
+  /// the upper half along x (bit 0), y (bit 1), and z (bit 2).
+  Branch( Box< [Node; 8] > )
+}
+
+impl Octree {
+  /// Returns the number of nodes in the tree. This is a measure of the memory
+  /// usage of the tree.
+  pub fn num_nodes( &self ) -> usize {
+    self.root.num_nodes( )
+  }
+}
+
+impl VoxelStorage for Octree {
+  fn new( size: (u32, u32, u32) ) -> Octree {
+    let width = size.0.max( size.1 ).max( size.2 ).max( 1 ).next_power_of_two( );
+    Octree { size, width, root: Node::Uniform( 0 ) }
+  }
+
+  fn size( &self ) -> (u32, u32, u32) {
+    self.size
+  }
+
+  fn get( &self, pos: (i32, i32, i32) ) -> Option< u8 > {
+    if !self.contains( pos ) {
+      return None;
+    }
+
+    let (x, y, z) = (pos.0 as u32, pos.1 as u32, pos.2 as u32);
+    let mut node = &self.root;
+    let mut width = self.width;
+
+    loop {
+      match node {
+        Node::Uniform( i ) => { return Some( *i ); },
+        Node::Branch( children ) => {
+          width /= 2;
+          node = &children[ octant( width, (x, y, z) ) ];
+        }
+      }
+    }
+  }
+
+  fn set( &mut self, pos: (i32, i32, i32), palette_index: u8 ) -> bool {
+    if self.contains( pos ) {
+      let pos = (pos.0 as u32, pos.1 as u32, pos.2 as u32);
+      self.root.set( self.width, pos, palette_index );
+      true
+    } else {
+      false
+    }
+  }
+
+  fn voxels( &self ) -> Box< dyn Iterator< Item = ((i32, i32, i32), u8) > + '_ > {
+    let mut dst = Vec::new( );
+    self.root.collect( self.size, self.width, (0, 0, 0), &mut dst );
+    Box::new( dst.into_iter( ) )
+  }
+}
+
+impl Node {
+  /// Assigns the palette index at the position, which is relative to the cube
+  /// of this node. Octants that become uniform are merged.
+  fn set( &mut self, width: u32, pos: (u32, u32, u32), palette_index: u8 ) {
+    if width == 1 {
+      *self = Node::Uniform( palette_index );
+      return;
+    }
+
+    if let Node::Uniform( i ) = *self {
+      if i == palette_index {
+        return;
+      }
+      *self = Node::Branch( Box::new( std::array::from_fn( |_| Node::Uniform( i ) ) ) );
+    }
+
+    if let Node::Branch( children ) = self {
+      let half = width / 2;
+      let (x, y, z) = pos;
+      children[ octant( half, pos ) ].set( half, (x % half, y % half, z % half), palette_index );
+
+      // Merge the octants if they all became the same
+      if let Node::Uniform( first ) = children[ 0 ] {
+        if children.iter( ).all( |c| *c == Node::Uniform( first ) ) {
+          *self = Node::Uniform( first );
+        }
+      }
+    }
+  }
+
+  /// Appends all non-empty voxels within the bounds `size` to `dst`. `min` is
+  /// the lowest corner of the cube of this node.
+  fn collect(
+      &self,
+      size: (u32, u32, u32),
+      width: u32,
+      min: (u32, u32, u32),
+      dst: &mut Vec< ((i32, i32, i32), u8) > ) {
+
+    match self {
+      Node::Uniform( 0 ) => { },
+      Node::Uniform( i ) => {
+        // Clip the cube to the bounds, as the root cube may exceed them
+        for z in min.2..( min.2 + width ).min( size.2 ) {
+          for y in min.1..( min.1 + width ).min( size.1 ) {
+            for x in min.0..( min.0 + width ).min( size.0 ) {
+              dst.push( ((x as i32, y as i32, z as i32), *i) );
+            }
+          }
+        }
+      },
+      Node::Branch( children ) => {
+        let half = width / 2;
+        for (i, c) in children.iter( ).enumerate( ) {
+          let child_min =
+            ( min.0 + half * ( i as u32 & 1 )
+            , min.1 + half * ( ( i as u32 >> 1 ) & 1 )
+            , min.2 + half * ( ( i as u32 >> 2 ) & 1 )
+            );
+          c.collect( size, half, child_min, dst );
+        }
+      }
+    }
+  }
+
+  fn num_nodes( &self ) -> usize {
+    match self {
+      Node::Uniform( _ ) => 1,
+      Node::Branch( children ) => 1 + children.iter( ).map( Node::num_nodes ).sum::< usize >( )
+    }
+  }
+}
+
+/// Returns the index of the octant containing the position, where `half` is
+/// the side length of the octants. The position is relative to the parent
+/// cube.
+fn octant( half: u32, (x, y, z): (u32, u32, u32) ) -> usize {
+  let x_bit = ( ( x / half ) & 1 ) as usize;
+  let y_bit = ( ( y / half ) & 1 ) as usize;
+  let z_bit = ( ( z / half ) & 1 ) as usize;
+  x_bit | ( y_bit << 1 ) | ( z_bit << 2 )
+}
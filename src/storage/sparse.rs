@@ -0,0 +1,66 @@
+//! Sparse voxel grid, which stores only non-empty voxels.
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::storage::VoxelStorage;
+
+
+/// A sparse 3D grid of palette indices, backed by a hash map.
+///
+/// Only non-empty voxels are stored. Memory usage is thus proportional to the
+/// number of voxels, regardless of the size of the grid. Voxels are iterated
+/// in no particular order.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SparseGrid {
+  size   : (u32, u32, u32),
+  voxels : HashMap< (i32, i32, i32), u8 >
+}
+
+impl SparseGrid {
+  /// Returns the number of non-empty voxels.
+  pub fn len( &self ) -> usize {
+    self.voxels.len( )
+  }
+
+  /// Returns `true` iff the grid contains no voxels.
+  pub fn is_empty( &self ) -> bool {
+    self.voxels.is_empty( )
+  }
+}
+
+impl VoxelStorage for SparseGrid {
+  fn new( size: (u32, u32, u32) ) -> SparseGrid {
+    SparseGrid { size, voxels: HashMap::new( ) }
+  }
+
+  fn size( &self ) -> (u32, u32, u32) {
+    self.size
+  }
+
+  fn get( &self, pos: (i32, i32, i32) ) -> Option< u8 > {
+    if self.contains( pos ) {
+      Some( self.voxels.get( &pos ).copied( ).unwrap_or( 0 ) )
+    } else {
+      None
+    }
+  }
+
+  fn set( &mut self, pos: (i32, i32, i32), palette_index: u8 ) -> bool {
+    if !self.contains( pos ) {
+      false
+    } else {
+      if palette_index == 0 {
+        self.voxels.remove( &pos );
+      } else {
+        self.voxels.insert( pos, palette_index );
+      }
+      true
+    }
+  }
+
+  fn voxels( &self ) -> Box< dyn Iterator< Item = ((i32, i32, i32), u8) > + '_ > {
+    Box::new( self.voxels.iter( ).map( |(pos, i)| (*pos, *i) ) )
+  }
+}
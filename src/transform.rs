@@ -0,0 +1,172 @@
+//! Rotating and mirroring the voxel data of models.
+//!
+//! Engines without support for rotated instances require the rotations of the
+//! scene graph to be applied to the models themselves. These methods "bake"
+//! the rotations into the models, while preserving the world position of
+//! every voxel.
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use crate::data::spec::MatRowCols;
+use crate::data::custom::{Model, NodeType, SceneNode, VoxScene, mirror_offset};
+
+
+impl Model {
+  /// Rotates (or mirrors) the voxels of the model in place.
+  ///
+  /// The axes of [`Model::size`] are permuted accordingly, and the voxels are
+  /// remapped into the new bounds. So, the voxels remain within
+  /// `(0,0,0)`-`size`.
+  pub fn transform( &mut self, rotation: MatRowCols ) {
+    let (x_size, y_size, z_size) =
+      rotation.apply_to( (self.size.0 as i32, self.size.1 as i32, self.size.2 as i32) );
+    let size = (x_size.unsigned_abs( ), y_size.unsigned_abs( ), z_size.unsigned_abs( ));
+    let (cx, cy, cz) = mirror_shift( rotation, size );
+
+    for v in self.xyzi.iter_mut( ) {
+      let (x, y, z) = rotation.apply_to( (v.0 as i32, v.1 as i32, v.2 as i32) );
+      *v = ( ( x + cx ) as u8, ( y + cy ) as u8, ( z + cz ) as u8, v.3 );
+    }
+
+    self.size = size;
+  }
+}
+
+impl SceneNode {
+  /// Pushes the rotation of this node into the models, and resets the
+  /// rotation of this node (and all its descendants) to the identity.
+  ///
+  /// Rotations of groups are pushed down to their children. A rotated model is
+  /// appended to `models`, as the original model may be placed elsewhere in the
+  /// scene. The translations are adjusted such that every voxel remains at the
+  /// same world position.
+  ///
+  /// Note that the original models may become unused. Consider
+  /// [`VoxScene::bake_rotations`], which also removes those models.
+  pub fn bake_rotation( &mut self, models: &mut Vec< Model > ) {
+    bake( self, models, &mut HashMap::new( ) );
+  }
+}
+
+impl VoxScene {
+  /// Pushes all rotations in the scene graph into the models. (See
+  /// [`SceneNode::bake_rotation`])
+  ///
+  /// Instances of the same model with the same rotation share the rotated
+  /// model. Models that are no longer placed in the scene are removed.
+  pub fn bake_rotations( &mut self ) {
+    bake( &mut self.graph, &mut self.models, &mut HashMap::new( ) );
+    self.remove_unused_models( );
+  }
+
+  /// Removes all models that are not placed by the scene graph. Model indices
+  /// in the scene graph are updated accordingly.
+  pub fn remove_unused_models( &mut self ) {
+    let mut is_used = vec![ false; self.models.len( ) ];
+    mark_used( &self.graph, &mut is_used );
+
+    let mut new_ids = Vec::with_capacity( self.models.len( ) );
+    let mut next_id = 0;
+    for used in &is_used {
+      new_ids.push( next_id );
+      if *used {
+        next_id += 1;
+      }
+    }
+
+    let mut i = 0;
+    self.models.retain( |_| { i += 1; is_used[ i - 1 ] } );
+    renumber( &mut self.graph, &new_ids );
+  }
+}
+
+/// Bakes the rotation of the node into its models. (See
+/// [`SceneNode::bake_rotation`])
+///
+/// `cache` maps a model with a rotation to the index of its rotated model.
+fn bake(
+    node: &mut SceneNode,
+    models: &mut Vec< Model >,
+    cache: &mut HashMap< (u32, MatRowCols), u32 > ) {
+
+  let rotation = node.rotation;
+  node.rotation = MatRowCols::identity( );
+
+  match &mut node.node_type {
+    NodeType::Group( children ) =>
+      for c in children {
+        c.rotation    = rotation.compose( &c.rotation );
+        c.translation = rotation.apply_to( c.translation );
+        bake( c, models, cache );
+      },
+    NodeType::Shape( model_id ) => {
+      if rotation.is_identity( ) {
+        return;
+      }
+
+      let (px, py, pz) = models[ *model_id as usize ].pivot( );
+
+      let new_id =
+        *cache.entry( (*model_id, rotation) ).or_insert_with( || {
+          let mut m = models[ *model_id as usize ].clone( );
+          m.transform( rotation );
+          models.push( m );
+          ( models.len( ) - 1 ) as u32
+        } );
+      let new_model = &models[ new_id as usize ];
+
+      // The original maps voxel `v` to world voxel `R(v-p) + t + n`, with `n`
+      // the mirror offset. The rotated model contains voxel `Rv + c` instead,
+      // with `c` its mirror shift, which maps to `Rv + c - p' + t'`. So, solve
+      // for the new translation `t'`.
+      let (rx, ry, rz) = rotation.apply_to( (px, py, pz) );
+      let (nx, ny, nz) = mirror_offset( rotation );
+      let (cx, cy, cz) = mirror_shift( rotation, new_model.size );
+      let (qx, qy, qz) = new_model.pivot( );
+      let (tx, ty, tz) = node.translation;
+
+      node.translation =
+        ( tx - rx + nx - cx + qx
+        , ty - ry + ny - cy + qy
+        , tz - rz + nz - cz + qz
+        );
+      *model_id = new_id;
+    }
+  }
+}
+
+/// Returns the shift which moves rotated voxel coordinates back into the
+/// (rotated) bounds `size`. Mirrored axes map coordinates `0..n` onto
+/// `-(n-1)..0`, which are shifted by `n-1`.
+fn mirror_shift( rotation: MatRowCols, (x_size, y_size, z_size): (u32,u32,u32) ) -> (i32,i32,i32) {
+  let (nx, ny, nz) = mirror_offset( rotation );
+  ( -nx * ( x_size as i32 - 1 ), -ny * ( y_size as i32 - 1 ), -nz * ( z_size as i32 - 1 ) )
+}
+
+/// Marks all models referenced by the scene graph as used.
+fn mark_used( node: &SceneNode, is_used: &mut [bool] ) {
+  match &node.node_type {
+    NodeType::Group( children ) =>
+      for c in children {
+        mark_used( c, is_used );
+      },
+    NodeType::Shape( model_id ) => {
+      is_used[ *model_id as usize ] = true;
+    }
+  }
+}
+
+/// Replaces every model index in the scene graph by its new index.
+fn renumber( node: &mut SceneNode, new_ids: &[u32] ) {
+  match &mut node.node_type {
+    NodeType::Group( children ) =>
+      for c in children {
+        renumber( c, new_ids );
+      },
+    NodeType::Shape( model_id ) => {
+      *model_id = new_ids[ *model_id as usize ];
+    }
+  }
+}
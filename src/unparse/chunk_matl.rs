@@ -2,7 +2,7 @@
 // Stdlib imports
 use std::collections::HashMap;
 // Local imports
-use crate::data::spec::{Matl, MatlType};
+use crate::data::spec::{Matl, MatlMediaType, MatlType};
 use crate::unparse::helpers::{le_u32};
 use crate::unparse::special::{dict};
 
@@ -11,7 +11,9 @@ use crate::unparse::special::{dict};
 pub fn chunk_matl< 'a >( dst: &mut Vec< u8 >, m: &Matl ) {
   le_u32( dst, m.id as u32 );
 
-  let mut properties = HashMap::new( );
+  // Known properties take precedence over unknown properties with their keys
+  let mut properties: HashMap< &str, String > =
+    m.unknown_props.iter( ).map( |(k, v)| (k.as_str( ), v.clone( )) ).collect( );
 
   let prop_type_str =
     match m.prop_type {
@@ -36,6 +38,19 @@ pub fn chunk_matl< 'a >( dst: &mut Vec< u8 >, m: &Matl ) {
   prop_f32( &mut properties,  "_ldr",     m.prop_ldr );
   prop_f32( &mut properties,  "_metal",   m.prop_metal );
   prop_bool( &mut properties, "_plastic", m.prop_plastic );
+  prop_f32( &mut properties,  "_sp",      m.prop_sp );
+  prop_f32( &mut properties,  "_g",       m.prop_g );
+
+  if let Some( media_type ) = m.prop_media_type {
+    let media_type_str =
+      match media_type {
+        MatlMediaType::Absorb  => "_absorb",
+        MatlMediaType::Scatter => "_scatter",
+        MatlMediaType::Emit    => "_emit",
+        MatlMediaType::Sss     => "_sss"
+      };
+    properties.insert( "_media_type", media_type_str.to_string( ) );
+  }
 
   dict( dst, &properties );
 }
@@ -0,0 +1,58 @@
+//! Valid and invalid scenes built with the `SceneBuilder`.
+
+
+// Local imports
+use vox_parser::builder::{BuildError, NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{Material, MaterialType, NodeType};
+
+
+#[test]
+fn valid( ) {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (2,1,0,1), (5,5,5,0) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,1) ] );
+  builder
+    .add( NodeBuilder::shape( a ).name( "a" ).layer( "First" ) )
+    .add( NodeBuilder::group( vec![ NodeBuilder::shape( b ).layer( "Second" ) ] ).translation( (1, 2, 3) ) )
+    .hide_layer( "Second", true );
+  let scene = builder.build( ).unwrap( );
+
+  // Empty voxels are omitted, and do not grow the model
+  assert_eq!( scene.models[ a as usize ].size, (3, 2, 1) );
+  assert_eq!( scene.models[ a as usize ].xyzi.len( ), 2 );
+  assert_eq!( scene.palette[ 0 ].rgba, (255, 0, 0, 255) );
+  // Layers are created when first referenced, which is by `hide_layer` here
+  assert_eq!( scene.layers.iter( ).map( |l| (l.name.as_str( ), l.is_hidden) ).collect::< Vec< _ > >( ), vec![ ("Second", true), ("First", false) ] );
+
+  match &scene.graph.node_type {
+    NodeType::Group( children ) => {
+      assert_eq!( children.len( ), 2 );
+      assert_eq!( children[ 0 ].name.as_deref( ), Some( "a" ) );
+      assert_eq!( children[ 0 ].layer_id, Some( 1 ) );
+      assert_eq!( children[ 1 ].translation, (1, 2, 3) );
+    },
+    t => panic!( "Not a group: {:?}", t )
+  }
+  assert_eq!( scene.instances( ).len( ), 2 );
+}
+
+#[test]
+fn zero_palette_index( ) {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 0, (255, 0, 0, 255) );
+  assert_eq!( builder.build( ).err( ), Some( BuildError::ZeroPaletteIndex ) );
+
+  let mut builder = SceneBuilder::new( );
+  let material = Material::new( (0, 0, 0, 255), MaterialType::Diffuse );
+  builder.material( 0, material );
+  assert_eq!( builder.build( ).err( ), Some( BuildError::ZeroPaletteIndex ) );
+}
+
+#[test]
+fn unknown_model( ) {
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_voxels( vec![ (0,0,0,1) ] );
+  builder.add( NodeBuilder::group( vec![ NodeBuilder::shape( a ), NodeBuilder::shape( a + 1 ) ] ) );
+  assert_eq!( builder.build( ).err( ), Some( BuildError::UnknownModel( a + 1 ) ) );
+}
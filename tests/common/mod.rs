@@ -0,0 +1,22 @@
+//! Helpers shared by the integration tests.
+
+
+// Local imports
+use vox_parser::data::custom::VoxScene;
+use vox_parser::palette::Rgba;
+
+
+/// Returns every voxel of the scene in world space with its color, sorted by
+/// position. This compares scenes regardless of how they split their models.
+pub fn world_voxels( scene: &VoxScene ) -> Vec< ((i32, i32, i32), Rgba) > {
+  let mut voxels = Vec::new( );
+  for inst in scene.instances( ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    for (x, y, z, i) in &model.xyzi {
+      let pos = inst.voxel_to_world( model, (*x as i32, *y as i32, *z as i32) );
+      voxels.push( (pos, scene.palette[ *i as usize - 1 ].rgba) );
+    }
+  }
+  voxels.sort( );
+  voxels
+}
@@ -0,0 +1,91 @@
+//! The container, nodes, and materials of exported binary glTF files.
+
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{MaterialType, MetalMaterial, VoxScene};
+use vox_parser::formats::gltf::{self, GltfOptions};
+
+
+/// A scene with a named group of two models, and a hidden model.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 255) );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,2) ] );
+  builder
+    .add( NodeBuilder::group( vec![ NodeBuilder::shape( a ).name( "a \"quoted\"\nname" ), NodeBuilder::shape( a ).translation( (5, 0, 0) ) ] ).name( "group" ) )
+    .add( NodeBuilder::shape( b ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  builder.build( ).unwrap( )
+}
+
+/// Reads a little-endian `u32` at the offset.
+fn u32_at( bytes: &[u8], offset: usize ) -> u32 {
+  u32::from_le_bytes( [bytes[ offset ], bytes[ offset + 1 ], bytes[ offset + 2 ], bytes[ offset + 3 ]] )
+}
+
+/// Splits a `.glb` file into its JSON and binary chunks, while checking the
+/// container.
+fn chunks( glb: &[u8] ) -> (String, Vec< u8 >) {
+  assert_eq!( &glb[ 0..4 ], b"glTF" );
+  assert_eq!( u32_at( glb, 4 ), 2 );
+  assert_eq!( u32_at( glb, 8 ) as usize, glb.len( ) );
+
+  let json_len = u32_at( glb, 12 ) as usize;
+  assert_eq!( &glb[ 16..20 ], b"JSON" );
+  assert_eq!( json_len % 4, 0 );
+  let json = String::from_utf8( glb[ 20..20 + json_len ].to_vec( ) ).unwrap( );
+
+  let rest = &glb[ 20 + json_len.. ];
+  if rest.is_empty( ) {
+    return (json, Vec::new( ));
+  }
+  let bin_len = u32_at( rest, 0 ) as usize;
+  assert_eq!( &rest[ 4..8 ], b"BIN\0" );
+  assert_eq!( bin_len % 4, 0 );
+  assert_eq!( rest.len( ), 8 + bin_len );
+  (json, rest[ 8.. ].to_vec( ))
+}
+
+#[test]
+fn container( ) {
+  let (json, bin) = chunks( &gltf::export( &scene( ), &GltfOptions::default( ) ) );
+  assert!( json.starts_with( "{\"asset\":{\"version\":\"2.0\"" ) );
+  assert!( json.contains( &format!( "\"buffers\":[{{\"byteLength\":{}}}]", bin.len( ) ) ) );
+
+  // Both shape nodes share a single mesh, and the hidden node has none
+  assert_eq!( json.matches( "\"mesh\":0" ).count( ), 2 );
+  assert!( !json.contains( "\"mesh\":1" ) );
+  assert_eq!( json.matches( "\"name\":\"palette_" ).count( ), 2 );
+
+  // Empty scenes have no binary chunk
+  let (json, bin) = chunks( &gltf::export( &SceneBuilder::new( ).build( ).unwrap( ), &GltfOptions::default( ) ) );
+  assert!( bin.is_empty( ) );
+  assert!( !json.contains( "\"buffers\"" ) );
+}
+
+#[test]
+fn node_names( ) {
+  let (json, _) = chunks( &gltf::export( &scene( ), &GltfOptions::default( ) ) );
+  assert!( json.contains( "{\"name\":\"group\"," ) );
+  assert!( json.contains( "{\"name\":\"a \\\"quoted\\\"\\u000aname\"," ) );
+  // Unnamed nodes have no name
+  assert_eq!( json.matches( "{\"matrix\":" ).count( ), 1 );
+}
+
+#[test]
+fn non_finite( ) {
+  let mut scene = scene( );
+  scene.palette[ 0 ].mat_type =
+    MaterialType::Metal(
+      MetalMaterial { prop_rough: f32::NAN, prop_ior: f32::INFINITY, prop_metal: 1.0, prop_spec: 0.0, prop_plastic: false }
+    );
+  let options = GltfOptions { scale: f32::INFINITY, ..GltfOptions::default( ) };
+
+  // JSON has no literals for these numbers
+  let (json, _) = chunks( &gltf::export( &scene, &options ) );
+  assert!( !json.contains( "NaN" ) && !json.contains( "inf" ) );
+  assert!( json.contains( "\"roughnessFactor\":0" ) );
+  assert!( json.contains( &format!( "\"ior\":{}", f32::MAX ) ) );
+}
@@ -0,0 +1,112 @@
+//! Columns, colors, and tiling of terrains imported from heightmaps.
+
+
+mod common;
+
+// Local imports
+use vox_parser::data::custom::VoxScene;
+use vox_parser::formats::heightmap::{self, HeightmapOptions};
+use vox_parser::formats::image::Image;
+use vox_parser::palette::Rgba;
+use common::world_voxels;
+
+
+/// An image with the given grey values, where `None` is transparent.
+fn grey( width: u32, height: u32, values: &[Option< u8 >] ) -> Image {
+  let pixels = values.iter( ).flat_map( |v| v.map_or( [0, 0, 0, 0], |v| [v, v, v, 255] ) ).collect( );
+  Image { width, height, pixels }
+}
+
+/// Returns the bottom and top of every column of voxels, by position.
+fn columns( scene: &VoxScene ) -> Vec< ((i32, i32), i32, i32) > {
+  let mut columns: Vec< ((i32, i32), i32, i32) > = Vec::new( );
+  for ((x, y, z), _) in world_voxels( scene ) {
+    match columns.last_mut( ) {
+      Some( (p, _, top) ) if *p == (x, y) && *top + 1 == z => *top = z,
+      _ => columns.push( ((x, y), z, z) )
+    }
+  }
+  columns
+}
+
+#[test]
+fn heights( ) {
+  // The top row is the far end, and the transparent pixel has no column
+  let image = grey( 3, 2, &[Some( 255 ), Some( 128 ), None, Some( 0 ), Some( 64 ), Some( 192 )] );
+  let options = HeightmapOptions { max_height: 4, ..HeightmapOptions::default( ) };
+  let scene = heightmap::import( &image, None, &options );
+  assert_eq!(
+    columns( &scene ),
+    vec![ ((0, 0), 0, 0), ((0, 1), 0, 4), ((1, 0), 0, 1), ((1, 1), 0, 2), ((2, 0), 0, 3) ]
+  );
+
+  // The image survives being stored as a PNG file
+  let image = Image::from_png( &image.to_png( ).unwrap( ) ).unwrap( );
+  assert_eq!( world_voxels( &heightmap::import( &image, None, &options ) ), world_voxels( &scene ) );
+}
+
+#[test]
+fn hollow( ) {
+  // A peak in the middle of a flat plane
+  let mut values = vec![ Some( 0 ); 9 ];
+  values[ 4 ] = Some( 255 );
+  let image = grey( 3, 3, &values );
+  let options = HeightmapOptions { max_height: 10, solid: false, ..HeightmapOptions::default( ) };
+  let scene = heightmap::import( &image, None, &options );
+
+  // The peak only extends down to the tops of its neighbours
+  let peak = columns( &scene );
+  assert_eq!( peak.len( ), 9 );
+  assert!( peak.contains( &((1, 1), 1, 10) ) );
+  assert!( peak.iter( ).filter( |(p, _, _)| *p != (1, 1) ).all( |c| ( c.1, c.2 ) == (0, 0) ) );
+
+  // Columns at the edges of transparent pixels extend down to the bottom
+  values[ 1 ] = None;
+  let scene = heightmap::import( &grey( 3, 3, &values ), None, &options );
+  assert!( columns( &scene ).contains( &((1, 1), 0, 10) ) );
+}
+
+#[test]
+fn colors( ) {
+  let image = grey( 3, 1, &[Some( 0 ), Some( 85 ), Some( 255 )] );
+  let red: Rgba = (255, 0, 0, 255);
+  let green: Rgba = (0, 255, 0, 255);
+  let options = HeightmapOptions { max_height: 3, height_colors: vec![ red, green ], ..HeightmapOptions::default( ) };
+
+  // Without a color map, columns take the color of the band of their top
+  let voxels = world_voxels( &heightmap::import( &image, None, &options ) );
+  let expected = vec![
+    ((0, 0, 0), red), ((1, 0, 0), red), ((1, 0, 1), red),
+    ((2, 0, 0), green), ((2, 0, 1), green), ((2, 0, 2), green), ((2, 0, 3), green)
+  ];
+  assert_eq!( voxels, expected );
+
+  // Transparent pixels of the color map fall back to the bands, and opaque
+  // ones color the whole column
+  let blue: Rgba = (0, 0, 255, 128);
+  let mut pixels = vec![ 0; 8 ];
+  pixels.extend( &[blue.0, blue.1, blue.2, blue.3] );
+  let color_map = Image { width: 3, height: 1, pixels };
+  let voxels = world_voxels( &heightmap::import( &image, Some( &color_map ), &options ) );
+  assert_eq!( voxels[ ..3 ], expected[ ..3 ] );
+  assert!( voxels[ 3.. ].iter( ).all( |(_, c)| *c == (0, 0, 255, 255) ) );
+}
+
+#[test]
+fn tiling( ) {
+  // A ramp which is too long for a single model
+  let values: Vec< Option< u8 > > = ( 0..300 ).map( |x| Some( ( x * 255 / 299 ) as u8 ) ).collect( );
+  let image = grey( 300, 1, &values );
+  let options = HeightmapOptions { max_height: 299, ..HeightmapOptions::default( ) };
+  let scene = heightmap::import( &image, None, &options );
+
+  assert!( scene.models.len( ) > 1 );
+  assert!( scene.models.iter( ).all( |m| m.size.0 <= 256 && m.size.1 <= 256 && m.size.2 <= 256 ) );
+  let columns = columns( &scene );
+  assert_eq!( columns.len( ), 300 );
+  assert!( columns.iter( ).enumerate( ).all( |(x, c)| c.0 == (x as i32, 0) && c.1 == 0 ) );
+
+  // The tiles survive a round trip through a file
+  let back: VoxScene = vox_parser::parse::file_custom( &vox_parser::unparse::file_custom( &scene ) ).unwrap( );
+  assert_eq!( world_voxels( &back ), world_voxels( &scene ) );
+}
@@ -0,0 +1,68 @@
+//! Round trips and malformed input for PNG images.
+
+
+// Local imports
+use vox_parser::formats::{ExportError, ImportError};
+use vox_parser::formats::image::Image;
+
+
+/// An image of 3 by 2 pixels, with varying colors and transparency.
+fn image( ) -> Image {
+  let pixels = ( 0..24u8 ).map( |i| i.wrapping_mul( 37 ) ).collect( );
+  Image { width: 3, height: 2, pixels }
+}
+
+/// Replaces the size in the header of an encoded image. (Its checksum is not
+/// verified)
+fn with_size( mut png: Vec< u8 >, width: u32, height: u32 ) -> Vec< u8 > {
+  png[ 16..20 ].copy_from_slice( &width.to_be_bytes( ) );
+  png[ 20..24 ].copy_from_slice( &height.to_be_bytes( ) );
+  png
+}
+
+#[test]
+fn round_trip( ) {
+  let image = image( );
+  assert_eq!( Image::from_png( &image.to_png( ).unwrap( ) ).unwrap( ), image );
+}
+
+#[test]
+fn truncated( ) {
+  let bytes = image( ).to_png( ).unwrap( );
+  // The final chunk is not needed to read the image
+  for len in 0..bytes.len( ) - 12 {
+    assert!( Image::from_png( &bytes[ ..len ] ).is_err( ), "length {}", len );
+  }
+}
+
+#[test]
+fn invalid_size( ) {
+  let png = image( ).to_png( ).unwrap( );
+  assert_eq!( Image::from_png( &with_size( png.clone( ), 0, 2 ) ).err( ), Some( ImportError::InvalidHeader ) );
+  assert_eq!( Image::from_png( &with_size( png.clone( ), 3, 0 ) ).err( ), Some( ImportError::InvalidHeader ) );
+  assert_eq!( Image::from_png( &with_size( png.clone( ), u32::MAX, u32::MAX ) ).err( ), Some( ImportError::InvalidHeader ) );
+
+  // More rows than the data contains
+  assert_eq!( Image::from_png( &with_size( png, 3, 1000 ) ).err( ), Some( ImportError::InvalidCompression ) );
+
+  // Far more data than the header describes
+  let large = Image { width: 1000, height: 1000, pixels: vec![ 0; 4_000_000 ] };
+  let bytes = with_size( large.to_png( ).unwrap( ), 1, 1 );
+  assert_eq!( Image::from_png( &bytes ).err( ), Some( ImportError::InvalidCompression ) );
+}
+
+#[test]
+fn invalid_image( ) {
+  // The pixels must match the size
+  let mut image = image( );
+  image.pixels.pop( );
+  assert_eq!( image.to_png( ).err( ), Some( ExportError::InvalidImage ) );
+  image.pixels.extend( &[0, 0] );
+  assert_eq!( image.to_png( ).err( ), Some( ExportError::InvalidImage ) );
+
+  // PNG cannot represent empty images
+  let empty = Image { width: 0, height: 2, pixels: Vec::new( ) };
+  assert_eq!( empty.to_png( ).err( ), Some( ExportError::InvalidImage ) );
+  let huge = Image { width: u32::MAX, height: u32::MAX, pixels: Vec::new( ) };
+  assert_eq!( huge.to_png( ).err( ), Some( ExportError::InvalidImage ) );
+}
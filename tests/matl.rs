@@ -0,0 +1,165 @@
+//! Round trips of `MATL` properties through custom materials.
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{Material, MaterialType, MediaMaterial, MediaType, VoxScene};
+use vox_parser::data::spec::{Chunk, Matl, MatlMediaType, MatlType};
+use vox_parser::{from_custom, parse, unparse};
+
+
+/// Materials with properties of other material types, unknown properties, and
+/// both specular keys. (Where `_sp` supersedes `_spec` of the blend material)
+fn materials( ) -> Vec< Matl > {
+  let mut diffuse = Matl::new( 1, MatlType::Diffuse );
+  diffuse.prop_rough   = Some( 0.5 );
+  diffuse.prop_ior     = Some( 0.3 );
+  diffuse.prop_spec    = Some( 0.4 );
+  diffuse.prop_weight  = Some( 0.8 );
+  diffuse.prop_plastic = true;
+  diffuse.unknown_props.insert( "_future".to_string( ), "x".to_string( ) );
+
+  // Older versions store the specular value as `_spec`
+  let mut metal = Matl::new( 2, MatlType::Metal );
+  metal.prop_rough = Some( 0.2 );
+  metal.prop_metal = Some( 0.9 );
+  metal.prop_spec  = Some( 0.6 );
+  metal.prop_att   = Some( 0.5 );
+  metal.prop_density = Some( 0.1 );
+
+  let mut blend = Matl::new( 3, MatlType::Blend );
+  blend.prop_sp    = Some( 0.7 );
+  blend.prop_spec  = Some( 0.2 );
+  blend.prop_alpha = Some( 0.5 );
+  blend.prop_emit  = Some( 0.3 );
+
+  let mut emit = Matl::new( 4, MatlType::Emit );
+  emit.prop_emit       = Some( 0.5 );
+  emit.prop_flux       = Some( 2 );
+  emit.prop_rough      = Some( 0.1 );
+  emit.prop_media_type = Some( MatlMediaType::Scatter );
+
+  vec![ diffuse, metal, blend, emit ]
+}
+
+/// Returns the properties of the material, as written to the file.
+fn properties( m: &Matl ) -> HashMap< String, String > {
+  let mut bytes = Vec::new( );
+  unparse::chunk_matl( &mut bytes, m );
+  let (_, props) = parse::dict( &bytes[ 4.. ] ).unwrap( );
+  props.into_iter( ).map( |(k, v)| (k.to_string( ), v.to_string( )) ).collect( )
+}
+
+/// A scene read from a file with the [`materials`].
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  let model = builder.add_voxels( vec![ (0,0,0,1) ] );
+  builder.add( NodeBuilder::shape( model ) );
+  let scene = builder.build( ).unwrap( );
+
+  let mut chunks = from_custom( &scene );
+  chunks.extend( materials( ).into_iter( ).map( Chunk::MATL ) );
+  parse::file_custom( &unparse::file_raw( &chunks ) ).unwrap( )
+}
+
+#[test]
+fn round_trip( ) {
+  let scene = scene( );
+  // `_sp` takes precedence over `_spec`
+  match scene.palette[ 1 ].mat_type {
+    MaterialType::Metal( m ) => assert_eq!( m.prop_spec, 0.6 ),
+    t => panic!( "Not a metal: {:?}", t )
+  }
+  match scene.palette[ 2 ].mat_type {
+    MaterialType::Blend( m ) => assert_eq!( m.prop_spec, 0.7 ),
+    t => panic!( "Not a blend: {:?}", t )
+  }
+
+  let chunks = from_custom( &scene );
+  for m in materials( ) {
+    let back =
+      chunks.iter( )
+        .find_map( |c| match c { Chunk::MATL( b ) if b.id == m.id => Some( b ), _ => None } )
+        .unwrap( );
+    // The superseded `_spec` of the blend material is dropped
+    let mut expected = properties( &m );
+    if m.id == 3 {
+      expected.remove( "_spec" );
+    }
+    assert_eq!( properties( back ), expected, "material {}", m.id );
+  }
+}
+
+#[test]
+fn specular( ) {
+  let mut scene = scene( );
+  assert_eq!( scene.palette[ 1 ].unknown_props( ).get( "_spec" ).map( |v| v.as_str( ) ), Some( "0.6" ) );
+  assert!( !scene.palette[ 2 ].unknown_props( ).contains_key( "_spec" ) );
+
+  // A changed specular value is written as `_sp`, without the stale `_spec`
+  if let MaterialType::Metal( m ) = &mut scene.palette[ 1 ].mat_type {
+    m.prop_spec = 0.25;
+  }
+  let chunks = from_custom( &scene );
+  let back =
+    chunks.iter( )
+      .find_map( |c| match c { Chunk::MATL( b ) if b.id == 2 => Some( b ), _ => None } )
+      .unwrap( );
+  let props = properties( back );
+  assert_eq!( props.get( "_sp" ).map( |v| v.as_str( ) ), Some( "0.25" ) );
+  assert!( !props.contains_key( "_spec" ) );
+}
+
+#[test]
+fn unknown_props( ) {
+  let mut material = Material::new( (1, 2, 3, 255), MaterialType::Diffuse );
+  assert!( material.unknown_props( ).is_empty( ) );
+  material.unknown_props_mut( ).insert( "_future".to_string( ), "x".to_string( ) );
+
+  let mut builder = SceneBuilder::new( );
+  builder.material( 5, material.clone( ) );
+  let model = builder.add_voxels( vec![ (0,0,0,5) ] );
+  builder.add( NodeBuilder::shape( model ) );
+  let scene = parse::file_custom( &unparse::file_custom( &builder.build( ).unwrap( ) ) ).unwrap( );
+  assert_eq!( scene.palette[ 4 ], material );
+}
+
+#[test]
+fn media( ) {
+  let media =
+    [MediaType::Absorb, MediaType::Scatter, MediaType::Emit, MediaType::Sss].iter( ).enumerate( )
+      .map( |(i, t)| {
+        let m = MediaMaterial { prop_media_type: *t, prop_density: 0.1 * i as f32, prop_g: -0.5, prop_ior: 0.3, prop_rough: 0.2 };
+        Material::new( (10, 20, 30, 255), MaterialType::Media( m ) )
+      } )
+      .collect::< Vec< _ > >( );
+
+  let mut builder = SceneBuilder::new( );
+  for (i, m) in media.iter( ).enumerate( ) {
+    builder.material( i as u8 + 1, m.clone( ) );
+  }
+  let model = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2), (2,0,0,3), (3,0,0,4) ] );
+  builder.add( NodeBuilder::shape( model ) );
+  let scene = parse::file_custom( &unparse::file_custom( &builder.build( ).unwrap( ) ) ).unwrap( );
+  assert_eq!( scene.palette[ ..4 ], media[ .. ] );
+
+  // The media type and every property is written, unless it is the default
+  let chunks = from_custom( &scene );
+  let matl =
+    |id: u8| chunks.iter( ).find_map( |c| match c { Chunk::MATL( b ) if b.id == id => Some( properties( b ) ), _ => None } ).unwrap( );
+  let absorb = matl( 1 );
+  assert_eq!( absorb.get( "_type" ).map( |v| v.as_str( ) ), Some( "_media" ) );
+  assert!( !absorb.contains_key( "_media_type" ) && !absorb.contains_key( "_d" ) );
+  assert_eq!( absorb.get( "_g" ).map( |v| v.as_str( ) ), Some( "-0.5" ) );
+  assert_eq!( matl( 4 ).get( "_media_type" ).map( |v| v.as_str( ) ), Some( "_sss" ) );
+
+  // Media without properties read as absorbing media with default values
+  let mut chunks = from_custom( &scene );
+  chunks.retain( |c| !matches!( c, Chunk::MATL( _ ) ) );
+  chunks.push( Chunk::MATL( Matl::new( 1, MatlType::Media ) ) );
+  let scene = parse::file_custom( &unparse::file_raw( &chunks ) ).unwrap( );
+  let m = MediaMaterial { prop_media_type: MediaType::Absorb, prop_density: 0.0, prop_g: 0.0, prop_ior: 0.0, prop_rough: 0.0 };
+  assert_eq!( scene.palette[ 0 ].mat_type, MaterialType::Media( m ) );
+}
@@ -0,0 +1,257 @@
+//! Quads, culling, and attributes of the generated meshes.
+
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::Model;
+use vox_parser::mesh::{self, Mesh};
+
+
+/// A solid box of the given size, with a single palette index.
+fn solid( (x_size, y_size, z_size): (u8, u8, u8), palette_index: u8 ) -> Model {
+  let mut xyzi = Vec::new( );
+  for z in 0..z_size {
+    for y in 0..y_size {
+      for x in 0..x_size {
+        xyzi.push( (x, y, z, palette_index) );
+      }
+    }
+  }
+  Model { size: (x_size as u32, y_size as u32, z_size as u32), xyzi }
+}
+
+/// Returns the total area of the triangles of the mesh.
+fn area( mesh: &Mesh ) -> f32 {
+  mesh.indices.chunks( 3 )
+    .map( |t| {
+      let [a, b, c] = [0, 1, 2].map( |i| mesh.positions[ t[ i ] as usize ] );
+      let (u, v) = ( [b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]] );
+      let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+      ( n[0] * n[0] + n[1] * n[1] + n[2] * n[2] ).sqrt( ) / 2.0
+    } )
+    .sum( )
+}
+
+/// Returns `true` iff every triangle is counter-clockwise when viewed from the
+/// side its normals point to.
+fn faces_outward( mesh: &Mesh ) -> bool {
+  mesh.indices.chunks( 3 ).all( |t| {
+    let [a, b, c] = [0, 1, 2].map( |i| mesh.positions[ t[ i ] as usize ] );
+    let (u, v) = ( [b[0] - a[0], b[1] - a[1], b[2] - a[2]], [c[0] - a[0], c[1] - a[1], c[2] - a[2]] );
+    let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    let normal = mesh.normals[ t[ 0 ] as usize ];
+    n[0] * normal[0] + n[1] * normal[1] + n[2] * normal[2] > 0.0
+  } )
+}
+
+#[test]
+fn greedy_quads( ) {
+  // Every side of a box becomes a single quad
+  let model = solid( (4, 3, 2), 1 );
+  let mesh = mesh::greedy( &model );
+  assert_eq!( mesh.num_quads( ), 6 );
+  assert_eq!( mesh.num_vertices( ), 24 );
+  assert_eq!( area( &mesh ), 2.0 * ( 4.0 * 3.0 + 4.0 * 2.0 + 3.0 * 2.0 ) );
+  assert!( faces_outward( &mesh ) );
+
+  // Whereas every visible voxel face is a quad of its own
+  let mesh = mesh::faces( &model );
+  assert_eq!( mesh.num_quads( ), 2 * ( 12 + 8 + 6 ) );
+  assert_eq!( area( &mesh ), 2.0 * ( 4.0 * 3.0 + 4.0 * 2.0 + 3.0 * 2.0 ) );
+  assert!( faces_outward( &mesh ) );
+
+  // Empty models have no faces
+  assert_eq!( mesh::greedy( &Model { size: (3, 3, 3), xyzi: Vec::new( ) } ).num_quads( ), 0 );
+}
+
+#[test]
+fn greedy_colors( ) {
+  // Faces of different palette indices are not merged
+  let mut model = solid( (4, 1, 1), 1 );
+  model.xyzi[ 2 ].3 = 2;
+  let mesh = mesh::greedy( &model );
+  // 2 ends, and 4 sides split in 3 parts
+  assert_eq!( mesh.num_quads( ), 2 + 4 * 3 );
+  assert_eq!( area( &mesh ), 18.0 );
+  for palette_index in 1..=2 {
+    let n = mesh.palette_indices.iter( ).filter( |i| **i == palette_index ).count( );
+    assert_eq!( n, 4 * if palette_index == 1 { 2 + 4 * 2 } else { 4 } );
+  }
+}
+
+#[test]
+fn greedy_instances( ) {
+  // Two boxes touching at a face hide that face of each other
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_model( solid( (2, 2, 2), 1 ) );
+  builder
+    .add( NodeBuilder::shape( a ).translation( (1, 1, 1) ) )
+    .add( NodeBuilder::shape( a ).translation( (3, 1, 1) ) )
+    .add( NodeBuilder::shape( a ).translation( (1, 3, 1) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  let scene = builder.build( ).unwrap( );
+
+  let meshes = mesh::greedy_instances( &scene );
+  assert_eq!( meshes.len( ), 3 );
+  assert_eq!( meshes[ 0 ].num_quads( ), 5 );
+  assert_eq!( meshes[ 1 ].num_quads( ), 5 );
+  assert_eq!( area( &meshes[ 0 ] ), 20.0 );
+  // Hidden instances cover nothing, but are meshed themselves
+  assert_eq!( meshes[ 2 ].num_quads( ), 5 );
+
+  // Positions that are occluded from outside hide the faces in front of them
+  let mesh = mesh::greedy_occluded( &solid( (2, 2, 2), 1 ), |(_, _, z)| z < 0 );
+  assert_eq!( mesh.num_quads( ), 5 );
+  assert!( mesh.normals.iter( ).all( |n| n[ 2 ] >= 0.0 ) );
+}
+
+/// Returns the ambient occlusion of the two vertices on the diagonal of every
+/// quad (which both triangles share), and of the two other vertices.
+fn diagonals( mesh: &Mesh ) -> Vec< ([f32; 2], [f32; 2]) > {
+  mesh.indices.chunks( 6 )
+    .map( |q| {
+      let shared: Vec< u32 > = q[ ..3 ].iter( ).copied( ).filter( |i| q[ 3.. ].contains( i ) ).collect( );
+      let mut other: Vec< u32 > = q.iter( ).copied( ).filter( |i| !shared.contains( i ) ).collect( );
+      other.dedup( );
+      let ao = |i: u32| mesh.ambient_occlusion[ i as usize ];
+      ( [ao( shared[ 0 ] ), ao( shared[ 1 ] )], [ao( other[ 0 ] ), ao( other[ 1 ] )] )
+    } )
+    .collect( )
+}
+
+#[test]
+fn ambient_occlusion( ) {
+  // A floor of 3 voxels, with a voxel on one end. The voxel occludes the
+  // floor next to it.
+  let model = Model { size: (3, 1, 2), xyzi: vec![ (0,0,0,1), (1,0,0,1), (2,0,0,1), (0,0,1,1) ] };
+
+  // Occlusion does not split quads, so the floor is a single quad
+  let mesh = mesh::greedy( &model );
+  assert_eq!( mesh.num_quads( ), 10 );
+  let floor: Vec< usize > =
+    ( 0..mesh.num_vertices( ) ).filter( |i| mesh.normals[ *i ] == [0.0, 0.0, 1.0] && mesh.positions[ *i ][ 2 ] == 1.0 ).collect( );
+  assert_eq!( floor.len( ), 4 );
+  for i in floor {
+    let expected = if mesh.positions[ i ][ 0 ] == 1.0 { 2.0 / 3.0 } else { 1.0 };
+    assert_eq!( mesh.ambient_occlusion[ i ], expected );
+  }
+
+  // Unless the occlusion must be exact
+  let mesh = mesh::greedy_ao( &model );
+  assert_eq!( mesh.num_quads( ), 11 );
+  assert_eq!( mesh::greedy_occluded_ao( &model, |_| false ).num_quads( ), 11 );
+  assert_eq!( mesh::faces( &model ).num_quads( ), 18 );
+
+  // Surrounded corners are fully occluded
+  let model = Model { size: (3, 3, 2), xyzi: vec![ (1,1,0,1), (0,1,1,1), (1,0,1,1) ] };
+  let mesh = mesh::faces( &model );
+  let corner =
+    ( 0..mesh.num_vertices( ) )
+      .find( |i| mesh.normals[ *i ] == [0.0, 0.0, 1.0] && mesh.positions[ *i ] == [1.0, 1.0, 1.0] )
+      .unwrap( );
+  assert_eq!( mesh.ambient_occlusion[ corner ], 0.0 );
+}
+
+#[test]
+fn ambient_occlusion_flip( ) {
+  // A voxel diagonally above the corner of another occludes one corner of its
+  // top face, which is always on the diagonal of the quad
+  for &occluder in &[(0,0,1), (2,0,1), (2,2,1), (0,2,1)] {
+    let model = Model { size: (3, 3, 2), xyzi: vec![ (1,1,0,1), (occluder.0, occluder.1, occluder.2, 1) ] };
+    let mesh = mesh::faces( &model );
+    let top =
+      mesh.indices.chunks( 6 )
+        .position( |q| q.iter( ).all( |i| mesh.positions[ *i as usize ][ 2 ] == 1.0 && mesh.normals[ *i as usize ][ 2 ] == 1.0 ) )
+        .unwrap( );
+    let (mut shared, _) = diagonals( &mesh )[ top ];
+    shared.sort_by( |a, b| a.partial_cmp( b ).unwrap( ) );
+    assert_eq!( shared, [2.0 / 3.0, 1.0], "{:?}", occluder );
+  }
+
+  // Every quad is split along its darkest diagonal
+  let model = Model { size: (3, 3, 3), xyzi: vec![ (1,1,1,1), (0,0,2,1), (2,1,0,1), (1,2,2,1), (0,1,1,1), (2,2,2,1) ] };
+  for mesh in &[mesh::faces( &model ), mesh::greedy_ao( &model ), mesh::greedy( &model )] {
+    let diagonals = diagonals( mesh );
+    assert!( diagonals.iter( ).all( |(shared, other)| shared[ 0 ] + shared[ 1 ] <= other[ 0 ] + other[ 1 ] ) );
+    // Which is not arbitrary, as some quads have a darker diagonal
+    assert!( diagonals.iter( ).any( |(shared, other)| shared[ 0 ] + shared[ 1 ] < other[ 0 ] + other[ 1 ] ) );
+    assert!( faces_outward( mesh ) );
+  }
+}
+
+/// Returns `true` iff every edge of the mesh is traversed equally often in
+/// both directions. (Voxels that only touch at an edge share that edge of the
+/// surface, which then belongs to four triangles)
+fn is_closed( mesh: &Mesh ) -> bool {
+  let mut edges = std::collections::HashMap::new( );
+  for t in mesh.indices.chunks( 3 ) {
+    for k in 0..3 {
+      *edges.entry( (t[ k ], t[ ( k + 1 ) % 3 ]) ).or_insert( 0 ) += 1;
+    }
+  }
+  edges.iter( ).all( |((a, b), n)| edges.get( &(*b, *a) ) == Some( n ) )
+}
+
+#[test]
+fn surface_nets( ) {
+  // A single voxel becomes an octahedron around its center
+  let mesh = mesh::surface_nets( &Model { size: (1, 1, 1), xyzi: vec![ (0,0,0,5) ] }, 0 );
+  assert_eq!( mesh.num_vertices( ), 8 );
+  assert_eq!( mesh.num_quads( ), 6 );
+  assert!( is_closed( &mesh ) );
+  for (p, n) in mesh.positions.iter( ).zip( &mesh.normals ) {
+    // The normals point away from the voxel
+    assert!( ( 0..3 ).map( |a| ( p[ a ] - 0.5 ) * n[ a ] ).sum::< f32 >( ) > 0.0 );
+    assert!( ( ( 0..3 ).map( |a| n[ a ] * n[ a ] ).sum::< f32 >( ) - 1.0 ).abs( ) < 1e-5 );
+  }
+  assert!( mesh.ambient_occlusion.iter( ).all( |ao| *ao == 1.0 ) );
+
+  // Larger models remain closed when smoothed, and stay near their voxels
+  let mut model = solid( (5, 4, 3), 1 );
+  model.xyzi.retain( |(x, y, z, _)| ( x + y + z ) % 5 != 0 );
+  model.xyzi.push( (7, 7, 7, 2) );
+  model.size = (8, 8, 8);
+  for &iterations in &[0, 1, 4] {
+    let mesh = mesh::surface_nets( &model, iterations );
+    assert!( is_closed( &mesh ) );
+    assert!( mesh.positions.iter( ).flatten( ).all( |c| *c >= -0.5 && *c <= 8.5 ) );
+    assert_eq!( mesh.normals.len( ), mesh.num_vertices( ) );
+    assert!( mesh.palette_indices.iter( ).all( |i| *i == 1 || *i == 2 ) );
+  }
+
+  assert_eq!( mesh::surface_nets( &Model { size: (4, 4, 4), xyzi: Vec::new( ) }, 2 ).num_vertices( ), 0 );
+}
+
+#[test]
+fn surface_nets_colors( ) {
+  // Every vertex takes the most common color of its cell
+  let model = Model { size: (3, 1, 1), xyzi: vec![ (0,0,0,1), (1,0,0,1), (2,0,0,2) ] };
+  let mesh = mesh::surface_nets( &model, 0 );
+  for (p, i) in mesh.positions.iter( ).zip( &mesh.palette_indices ) {
+    // Ties go to the lowest palette index
+    let expected = if p[ 0 ] <= 2.0 { 1 } else { 2 };
+    assert_eq!( *i, expected, "{:?}", p );
+  }
+}
+
+#[test]
+fn surface_nets_scene( ) {
+  // Touching instances are merged into a single surface, in world space
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_voxels( vec![ (0,0,0,1) ] );
+  builder
+    .add( NodeBuilder::shape( a ).translation( (10, 0, 0) ) )
+    .add( NodeBuilder::shape( a ).translation( (11, 0, 0) ) )
+    .add( NodeBuilder::shape( a ).translation( (20, 0, 0) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  let scene = builder.build( ).unwrap( );
+
+  let mesh = mesh::surface_nets_scene( &scene, 0 );
+  let expected = mesh::surface_nets( &Model { size: (2, 1, 1), xyzi: vec![ (0,0,0,1), (1,0,0,1) ] }, 0 );
+  assert_eq!( mesh.num_vertices( ), expected.num_vertices( ) );
+  assert_eq!( mesh.num_quads( ), expected.num_quads( ) );
+  assert!( is_closed( &mesh ) );
+  assert!( mesh.positions.iter( ).all( |p| p[ 0 ] > 10.0 && p[ 0 ] < 12.0 ) );
+
+  assert_eq!( mesh::surface_nets_scene( &SceneBuilder::new( ).build( ).unwrap( ), 1 ).num_vertices( ), 0 );
+}
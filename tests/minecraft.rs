@@ -0,0 +1,174 @@
+//! Round trips and malformed input for Minecraft schematics and structures.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::VoxScene;
+use vox_parser::formats::{ExportError, ImportError};
+use vox_parser::formats::minecraft::{self, MinecraftOptions, NbtFormat};
+use vox_parser::palette::Rgba;
+use common::world_voxels;
+
+
+/// A scene with two models, one of them moved.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 255) );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,1), (1,2,3,2) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,2), (0,1,0,1) ] );
+  builder.add( NodeBuilder::shape( a ) );
+  builder.add( NodeBuilder::shape( b ).translation( (-10, 5, 20) ) );
+  builder.build( ).unwrap( )
+}
+
+/// Options which map both colors of [`scene`] to blocks, so they survive the
+/// round trip.
+fn options( format: NbtFormat ) -> MinecraftOptions {
+  let mut options = MinecraftOptions { format, ..MinecraftOptions::default( ) };
+  options.blocks.insert( "minecraft:red_wool".to_string( ), 1 );
+  options.blocks.insert( "minecraft:blue_wool".to_string( ), 2 );
+  options
+}
+
+/// Moves the voxels such that their lowest corner is at the origin.
+fn normalized( mut voxels: Vec< ((i32, i32, i32), Rgba) > ) -> Vec< ((i32, i32, i32), Rgba) > {
+  let min = |f: fn( &(i32, i32, i32) ) -> i32| voxels.iter( ).map( |(p, _)| f( p ) ).min( ).unwrap_or( 0 );
+  let (x0, y0, z0) = ( min( |p| p.0 ), min( |p| p.1 ), min( |p| p.2 ) );
+  for (p, _) in &mut voxels {
+    *p = ( p.0 - x0, p.1 - y0, p.2 - z0 );
+  }
+  voxels
+}
+
+/// Appends a named tag header to uncompressed NBT data.
+fn named( out: &mut Vec< u8 >, id: u8, name: &str ) {
+  out.push( id );
+  out.extend( &( name.len( ) as u16 ).to_be_bytes( ) );
+  out.extend( name.as_bytes( ) );
+}
+
+/// An uncompressed Sponge schematic of the given size and offset, with a
+/// single stone block at its lowest corner.
+fn schematic( size: (u16, u16, u16), offset: (i32, i32, i32) ) -> Vec< u8 > {
+  let mut out = Vec::new( );
+  named( &mut out, 10, "Schematic" );
+  for (name, v) in &[("Width", size.0), ("Height", size.1), ("Length", size.2)] {
+    named( &mut out, 2, name );
+    out.extend( &v.to_be_bytes( ) );
+  }
+  named( &mut out, 11, "Offset" );
+  for v in &[3, offset.0, offset.1, offset.2] {
+    out.extend( &v.to_be_bytes( ) );
+  }
+  named( &mut out, 10, "Palette" );
+  named( &mut out, 3, "minecraft:stone" );
+  out.extend( &0i32.to_be_bytes( ) );
+  out.push( 0 );
+  named( &mut out, 7, "BlockData" );
+  out.extend( &1i32.to_be_bytes( ) );
+  out.push( 0 );
+  out.push( 0 );
+  out
+}
+
+/// Exports and imports the scene. Mapped palette indices keep their default
+/// colors on import, so the palette of the scene is restored.
+fn round_trip_with( scene: &VoxScene, format: NbtFormat ) -> VoxScene {
+  let mut back = minecraft::import( &minecraft::export( scene, &options( format ) ).unwrap( ), &options( format ) ).unwrap( );
+  back.palette = scene.palette.clone( );
+  back
+}
+
+#[test]
+fn round_trip( ) {
+  let scene = scene( );
+  assert_eq!( world_voxels( &round_trip_with( &scene, NbtFormat::Sponge ) ), world_voxels( &scene ) );
+
+  // Structures have no offset, so only the relative positions survive
+  let back = round_trip_with( &scene, NbtFormat::Structure );
+  assert_eq!( normalized( world_voxels( &back ) ), normalized( world_voxels( &scene ) ) );
+}
+
+#[test]
+fn truncated( ) {
+  for &format in &[NbtFormat::Sponge, NbtFormat::Structure] {
+    let bytes = minecraft::export( &scene( ), &options( format ) ).unwrap( );
+    // The gzip trailer (a checksum and the length) is not needed to read the
+    // file
+    for len in 0..bytes.len( ) - 8 {
+      assert!( minecraft::import( &bytes[ ..len ], &options( format ) ).is_err( ), "length {}", len );
+    }
+  }
+}
+
+#[test]
+fn deeply_nested( ) {
+  let nested = |depth: usize| {
+    let mut out = Vec::new( );
+    named( &mut out, 9, "" );
+    for _ in 0..depth {
+      out.extend( &[9, 0, 0, 0, 1] );
+    }
+    out.extend( &[1, 0, 0, 0, 0] );
+    out
+  };
+
+  // Lists are not schematics, but they are read
+  let options = MinecraftOptions::default( );
+  assert_eq!( minecraft::import( &nested( 100 ), &options ).err( ), Some( ImportError::MissingField( "Width" ) ) );
+  assert_eq!( minecraft::import( &nested( 100_000 ), &options ).err( ), Some( ImportError::Nom( nom::error::ErrorKind::TooLarge ) ) );
+}
+
+#[test]
+fn oversized( ) {
+  let options = MinecraftOptions::default( );
+
+  // An array far longer than the file
+  let mut bytes = Vec::new( );
+  named( &mut bytes, 10, "" );
+  named( &mut bytes, 12, "a" );
+  bytes.extend( &i32::MAX.to_be_bytes( ) );
+  assert!( minecraft::import( &bytes, &options ).is_err( ) );
+
+  // The volume overflows 32 bits, but only one block is present
+  let scene = minecraft::import( &schematic( (u16::MAX, u16::MAX, u16::MAX), (0, 0, 0) ), &options ).unwrap( );
+  assert_eq!( world_voxels( &scene ).len( ), 1 );
+
+  // The schematic extends beyond the range of coordinates
+  let bytes = schematic( (2, 1, 1), (i32::MAX, 0, 0) );
+  assert_eq!( minecraft::import( &bytes, &options ).err( ), Some( ImportError::InvalidHeader ) );
+  let bytes = schematic( (1, 1, 2), (0, 0, i32::MAX) );
+  assert_eq!( minecraft::import( &bytes, &options ).err( ), Some( ImportError::InvalidHeader ) );
+}
+
+#[test]
+fn too_large( ) {
+  let sparse = |voxels: &[(i32, i32, i32)]| {
+    let mut builder = SceneBuilder::new( );
+    let a = builder.add_voxels( vec![ (0,0,0,1) ] );
+    for p in voxels {
+      builder.add( NodeBuilder::shape( a ).translation( *p ) );
+    }
+    builder.build( ).unwrap( )
+  };
+  let sponge = options( NbtFormat::Sponge );
+  let structure = options( NbtFormat::Structure );
+
+  // Schematics are at most 65535 blocks long
+  let scene = sparse( &[(0, 0, 0), (70_000, 0, 0)] );
+  assert_eq!( minecraft::export( &scene, &sponge ).err( ), Some( ExportError::TooLarge ) );
+  assert_eq!( world_voxels( &round_trip_with( &scene, NbtFormat::Structure ) ).len( ), 2 );
+
+  // And hold at most 2^31 - 1 blocks
+  let scene = sparse( &[(0, 0, 0), (50_000, 50_000, 1)] );
+  assert_eq!( minecraft::export( &scene, &sponge ).err( ), Some( ExportError::TooLarge ) );
+  assert!( minecraft::export( &scene, &structure ).is_ok( ) );
+
+  // Strings are at most 65535 bytes long
+  let mut options = structure.clone( );
+  options.blocks.insert( format!( "minecraft:{}", "a".repeat( 70_000 ) ), 1 );
+  options.blocks.remove( "minecraft:red_wool" );
+  assert_eq!( minecraft::export( &sparse( &[(0, 0, 0)] ), &options ).err( ), Some( ExportError::TooLarge ) );
+}
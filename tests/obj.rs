@@ -0,0 +1,102 @@
+//! Meshes and materials of exported Wavefront `.obj` files.
+
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::VoxScene;
+use vox_parser::formats::Pivot;
+use vox_parser::formats::image::Image;
+use vox_parser::formats::obj::{self, ObjMaterials, ObjOptions};
+
+
+/// A scene with a red voxel, a blue voxel moved away from it, and a hidden
+/// voxel.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 128) );
+  let red = builder.add_voxels( vec![ (0,0,0,1) ] );
+  let blue = builder.add_voxels( vec![ (0,0,0,2) ] );
+  builder
+    .add( NodeBuilder::shape( red ) )
+    .add( NodeBuilder::shape( blue ).translation( (3, 0, 0) ) )
+    .add( NodeBuilder::shape( red ).translation( (9, 9, 9) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  builder.build( ).unwrap( )
+}
+
+/// Reads the vertex positions and the faces of an `.obj` file. Every face
+/// vertex refers to its position by index (starting at 1).
+fn read( obj: &str ) -> (Vec< [f32; 3] >, Vec< Vec< usize > >) {
+  let mut positions = Vec::new( );
+  let mut faces = Vec::new( );
+  for line in obj.lines( ) {
+    let mut words = line.split_whitespace( );
+    match words.next( ) {
+      Some( "v" ) => {
+        let v: Vec< f32 > = words.map( |w| w.parse( ).unwrap( ) ).collect( );
+        positions.push( [v[ 0 ], v[ 1 ], v[ 2 ]] );
+      },
+      Some( "f" ) =>
+        faces.push( words.map( |w| w.split( '/' ).next( ).unwrap( ).parse( ).unwrap( ) ).collect( ) ),
+      _ => { }
+    }
+  }
+  (positions, faces)
+}
+
+#[test]
+fn mesh( ) {
+  let options = ObjOptions { scale: 2.0, pivot: Pivot::Corner, y_up: false, ..ObjOptions::default( ) };
+  let files = obj::export( &scene( ), &options );
+  let (positions, faces) = read( &files.obj );
+
+  // Two cubes of 6 quads, without the hidden instance
+  assert_eq!( files.obj.matches( "\no " ).count( ), 2 );
+  assert_eq!( faces.len( ), 2 * 6 * 2 );
+  assert!( faces.iter( ).flatten( ).all( |i| *i >= 1 && *i <= positions.len( ) ) );
+
+  // The voxels span from the corner to the far side of the blue voxel
+  let coords = |axis: usize| {
+    let mut c: Vec< i32 > = positions.iter( ).map( |p| p[ axis ] as i32 ).collect( );
+    c.sort( );
+    c.dedup( );
+    c
+  };
+  assert_eq!( coords( 0 ), vec![ 0, 2, 6, 8 ] );
+  assert_eq!( coords( 1 ), vec![ 0, 2 ] );
+  assert_eq!( coords( 2 ), vec![ 0, 2 ] );
+
+  // With y pointing up, the z coordinates become y coordinates
+  let options = ObjOptions { pivot: Pivot::Corner, ..ObjOptions::default( ) };
+  let (positions, _) = read( &obj::export( &scene( ), &options ).obj );
+  assert!( positions.iter( ).all( |p| p[ 1 ] >= 0.0 && p[ 1 ] <= 1.0 && p[ 2 ] <= 0.0 && p[ 2 ] >= -1.0 ) );
+}
+
+#[test]
+fn palette_texture( ) {
+  let scene = scene( );
+  let files = obj::export( &scene, &ObjOptions { name: "out".to_string( ), ..ObjOptions::default( ) } );
+  assert!( files.obj.starts_with( "mtllib out.mtl\n" ) );
+  assert!( files.mtl.contains( "map_Kd out.png" ) );
+
+  // The texture holds the palette, with one texture coordinate per entry
+  let texture = Image::from_png( &files.texture.unwrap( ) ).unwrap( );
+  assert_eq!( (texture.width, texture.height), (256, 1) );
+  assert_eq!( texture.pixel( 0, 0 ), (255, 0, 0, 255) );
+  assert_eq!( texture.pixel( 1, 0 ), (0, 0, 255, 128) );
+  assert_eq!( files.obj.lines( ).filter( |l| l.starts_with( "vt " ) ).count( ), 255 );
+}
+
+#[test]
+fn per_color( ) {
+  let options = ObjOptions { materials: ObjMaterials::PerColor, ..ObjOptions::default( ) };
+  let files = obj::export( &scene( ), &options );
+  assert!( files.texture.is_none( ) );
+
+  // Only the used palette indices have materials
+  assert_eq!( files.mtl.matches( "newmtl" ).count( ), 2 );
+  assert!( files.mtl.contains( "newmtl palette_1\nKd 1 0 0\n" ) );
+  assert!( files.mtl.contains( "newmtl palette_2\nKd 0 0 1\nKa 0 0 0\nd 0.5019608\n" ) );
+  assert!( files.obj.contains( "usemtl palette_1\n" ) && files.obj.contains( "usemtl palette_2\n" ) );
+  assert!( !files.obj.contains( "vt " ) );
+}
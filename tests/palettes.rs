@@ -0,0 +1,68 @@
+//! Round trips and malformed input for palette files.
+
+
+// Local imports
+use vox_parser::data::custom::Material;
+use vox_parser::formats::ImportError;
+use vox_parser::formats::palettes;
+use vox_parser::palette::Rgba;
+
+
+/// A palette of distinct colors. Opaque palettes survive formats without an
+/// alpha channel.
+fn palette( opaque: bool ) -> [Material; 255] {
+  let mut palette = palettes::import_hex( "" ).unwrap( );
+  for (i, m) in palette.iter_mut( ).enumerate( ) {
+    let i = i as u8;
+    m.rgba = ( i, i.wrapping_mul( 7 ), i.wrapping_mul( 13 ), if opaque { 255 } else { 255 - i } );
+  }
+  palette
+}
+
+fn colors( palette: &[Material; 255] ) -> Vec< Rgba > {
+  palette.iter( ).map( |m| m.rgba ).collect( )
+}
+
+#[test]
+fn round_trip( ) {
+  let translucent = palette( false );
+  let back = palettes::import_png( &palettes::export_png( &translucent ) ).unwrap( );
+  assert_eq!( colors( &back ), colors( &translucent ) );
+  let back = palettes::import_paint_net( &palettes::export_paint_net( &translucent ) ).unwrap( );
+  assert_eq!( colors( &back ), colors( &translucent ) );
+
+  let opaque = palette( true );
+  let back = palettes::import_gpl( &palettes::export_gpl( &opaque, "Test" ) ).unwrap( );
+  assert_eq!( colors( &back ), colors( &opaque ) );
+  let back = palettes::import_hex( &palettes::export_hex( &opaque ) ).unwrap( );
+  assert_eq!( colors( &back ), colors( &opaque ) );
+}
+
+#[test]
+fn signed_colors( ) {
+  // `from_str_radix` would read "+f" as 15
+  assert_eq!( palettes::import_hex( "+f+f+f\n" ).err( ), Some( ImportError::InvalidLine( 1 ) ) );
+  assert_eq!( palettes::import_paint_net( "; Comment\n+f+f+f+f\n" ).err( ), Some( ImportError::InvalidLine( 2 ) ) );
+  assert_eq!( palettes::import_hex( "ff00ff\n#-1-1-1\n" ).err( ), Some( ImportError::InvalidLine( 2 ) ) );
+}
+
+#[test]
+fn malformed( ) {
+  assert_eq!( palettes::import_gpl( "JASC-PAL\n0100\n" ).err( ), Some( ImportError::InvalidHeader ) );
+  assert_eq!( palettes::import_gpl( "GIMP Palette\n255 0\n" ).err( ), Some( ImportError::InvalidLine( 2 ) ) );
+  assert_eq!( palettes::import_hex( "ff00f\n" ).err( ), Some( ImportError::InvalidLine( 1 ) ) );
+
+  let bytes = palettes::export_png( &palette( false ) );
+  // The final chunk is not needed to read the image
+  for len in 0..bytes.len( ) - 12 {
+    assert!( palettes::import_png( &bytes[ ..len ] ).is_err( ), "length {}", len );
+  }
+}
+
+#[test]
+fn oversized( ) {
+  // Colors beyond 255 are ignored
+  let input: String = ( 0..1000 ).map( |i| format!( "{:06x}\n", i ) ).collect( );
+  let palette = palettes::import_hex( &input ).unwrap( );
+  assert_eq!( palette[ 254 ].rgba, (0, 0, 254, 255) );
+}
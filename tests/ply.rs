@@ -0,0 +1,147 @@
+//! Point clouds and meshes of exported `.ply` files, in both encodings.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::VoxScene;
+use vox_parser::data::spec::{Axis, MatRowCols};
+use vox_parser::formats::Pivot;
+use vox_parser::formats::ply::{self, PlyContent, PlyEncoding, PlyOptions};
+use vox_parser::palette::Rgba;
+use common::world_voxels;
+
+
+/// A scene with a model placed twice, once mirrored, and a hidden model.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 100) );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,2), (1,1,0,1) ] );
+  builder
+    .add( NodeBuilder::shape( a ) )
+    .add( NodeBuilder::shape( a ).translation( (-5, 2, 3) ).rotation( MatRowCols::mirror( Axis::X ) ) )
+    .add( NodeBuilder::shape( a ).translation( (20, 0, 0) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  builder.build( ).unwrap( )
+}
+
+/// A vertex read from a file.
+#[derive(Debug, PartialEq)]
+struct Vertex {
+  position : [f32; 3],
+  normal   : Option< [f32; 3] >,
+  rgba     : Rgba
+}
+
+/// Reads the vertices and faces of a file, as written by the exporter.
+fn read( bytes: &[u8] ) -> (Vec< Vertex >, Vec< [u32; 3] >) {
+  let end = bytes.windows( 11 ).position( |w| w == b"end_header\n" ).unwrap( ) + 11;
+  let header = std::str::from_utf8( &bytes[ ..end ] ).unwrap( );
+  let count = |element: &str| {
+    header.lines( )
+      .find_map( |l| l.strip_prefix( &format!( "element {} ", element ) ) )
+      .map_or( 0, |n| n.parse( ).unwrap( ) )
+  };
+  let (num_vertices, num_faces) = ( count( "vertex" ), count( "face" ) );
+  let has_normals = header.contains( "property float nx\n" );
+
+  let mut vertices = Vec::new( );
+  let mut faces = Vec::new( );
+
+  if header.contains( "format ascii 1.0\n" ) {
+    let mut lines = std::str::from_utf8( &bytes[ end.. ] ).unwrap( ).lines( );
+    for _ in 0..num_vertices {
+      let v: Vec< f32 > = lines.next( ).unwrap( ).split( ' ' ).map( |w| w.parse( ).unwrap( ) ).collect( );
+      let (normal, c) = if has_normals { (Some( [v[ 3 ], v[ 4 ], v[ 5 ]] ), &v[ 6.. ]) } else { (None, &v[ 3.. ]) };
+      vertices.push( Vertex { position: [v[ 0 ], v[ 1 ], v[ 2 ]], normal, rgba: (c[ 0 ] as u8, c[ 1 ] as u8, c[ 2 ] as u8, c[ 3 ] as u8) } );
+    }
+    for _ in 0..num_faces {
+      let f: Vec< u32 > = lines.next( ).unwrap( ).split( ' ' ).map( |w| w.parse( ).unwrap( ) ).collect( );
+      assert_eq!( f[ 0 ], 3 );
+      faces.push( [f[ 1 ], f[ 2 ], f[ 3 ]] );
+    }
+    assert_eq!( lines.next( ), None );
+  } else {
+    let mut rest = &bytes[ end.. ];
+    for _ in 0..num_vertices {
+      let position = [0, 1, 2].map( |k| take_f32( &rest[ 4 * k.. ] ) );
+      rest = &rest[ 12.. ];
+      let normal = if has_normals { Some( [0, 1, 2].map( |k| take_f32( &rest[ 4 * k.. ] ) ) ) } else { None };
+      if has_normals {
+        rest = &rest[ 12.. ];
+      }
+      let c = &rest[ ..4 ];
+      vertices.push( Vertex { position, normal, rgba: (c[ 0 ], c[ 1 ], c[ 2 ], c[ 3 ]) } );
+      rest = &rest[ 4.. ];
+    }
+    for _ in 0..num_faces {
+      assert_eq!( rest[ 0 ], 3 );
+      let i = |k: usize| u32::from_le_bytes( [rest[ 1 + 4 * k ], rest[ 2 + 4 * k ], rest[ 3 + 4 * k ], rest[ 4 + 4 * k ]] );
+      faces.push( [i( 0 ), i( 1 ), i( 2 )] );
+      rest = &rest[ 13.. ];
+    }
+    assert!( rest.is_empty( ) );
+  }
+
+  (vertices, faces)
+}
+
+/// Reads a little-endian `f32` at the start of the bytes.
+fn take_f32( bytes: &[u8] ) -> f32 {
+  f32::from_le_bytes( [bytes[ 0 ], bytes[ 1 ], bytes[ 2 ], bytes[ 3 ]] )
+}
+
+fn options( encoding: PlyEncoding, content: PlyContent ) -> PlyOptions {
+  PlyOptions { encoding, content, ..PlyOptions::default( ) }
+}
+
+#[test]
+fn points( ) {
+  let scene = scene( );
+  let (vertices, faces) = read( &ply::export( &scene, &options( PlyEncoding::Ascii, PlyContent::Points ) ) );
+  assert!( faces.is_empty( ) );
+
+  // A vertex at the center of every visible voxel, with its color
+  let mut points: Vec< ((i32, i32, i32), Rgba) > =
+    vertices.iter( )
+      .map( |v| ( ( ( v.position[ 0 ] - 0.5 ) as i32, ( v.position[ 1 ] - 0.5 ) as i32, ( v.position[ 2 ] - 0.5 ) as i32 ), v.rgba ) )
+      .collect( );
+  points.sort( );
+  let mut expected = world_voxels( &scene );
+  expected.retain( |((x, _, _), _)| *x < 10 );
+  assert_eq!( points, expected );
+  assert!( vertices.iter( ).all( |v| v.normal.is_none( ) ) );
+}
+
+#[test]
+fn encodings( ) {
+  // Both encodings contain the same values
+  for &content in &[PlyContent::Points, PlyContent::Mesh] {
+    for &y_up in &[false, true] {
+      let ascii = PlyOptions { y_up, scale: 0.25, pivot: Pivot::Center, ..options( PlyEncoding::Ascii, content ) };
+      let binary = PlyOptions { encoding: PlyEncoding::BinaryLittleEndian, ..ascii.clone( ) };
+      assert_eq!( read( &ply::export( &scene( ), &ascii ) ), read( &ply::export( &scene( ), &binary ) ) );
+    }
+  }
+}
+
+#[test]
+fn mesh( ) {
+  let (vertices, faces) = read( &ply::export( &scene( ), &options( PlyEncoding::BinaryLittleEndian, PlyContent::Mesh ) ) );
+  assert!( faces.iter( ).flatten( ).all( |i| ( *i as usize ) < vertices.len( ) ) );
+
+  // Triangles face outward, also for the mirrored instance
+  for [a, b, c] in &faces {
+    let [a, b, c] = [a, b, c].map( |i| &vertices[ *i as usize ] );
+    let (u, v) = ( [0, 1, 2].map( |k| b.position[ k ] - a.position[ k ] ), [0, 1, 2].map( |k| c.position[ k ] - a.position[ k ] ) );
+    let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    let normal = a.normal.unwrap( );
+    assert!( n[ 0 ] * normal[ 0 ] + n[ 1 ] * normal[ 1 ] + n[ 2 ] * normal[ 2 ] > 0.0 );
+    assert_eq!( (a.rgba, b.rgba), (c.rgba, c.rgba) );
+  }
+
+  // Adjacent voxels differ in color, so none of the 14 visible faces of the
+  // model are merged. Each of the two visible instances has them all.
+  assert_eq!( faces.len( ), 2 * 2 * 14 );
+}
@@ -0,0 +1,86 @@
+//! Round trips and malformed input for Qubicle `.qb` files.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::VoxScene;
+use vox_parser::formats::ImportError;
+use vox_parser::formats::qb::{self, QbOptions};
+use common::world_voxels;
+
+
+/// A scene with two named models, one of them moved.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  builder.color( 1, (255, 0, 0, 255) ).color( 2, (0, 0, 255, 255) );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (1,0,0,1), (1,2,3,2) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,2), (0,1,0,1) ] );
+  builder.add( NodeBuilder::shape( a ).name( "first" ) );
+  builder.add( NodeBuilder::shape( b ).name( "second" ).translation( (-10, 5, 20) ) );
+  builder.build( ).unwrap( )
+}
+
+/// A file header with a single matrix of the given size and position, without
+/// any voxel data.
+fn header( compressed: bool, size: (u32, u32, u32), position: (i32, i32, i32) ) -> Vec< u8 > {
+  let mut out = Vec::new( );
+  for v in &[0x0000_0101, 0, 0, compressed as u32, 0, 1] {
+    out.extend( &u32::to_le_bytes( *v ) );
+  }
+  out.push( 1 );
+  out.push( b'm' );
+  for v in &[size.0, size.1, size.2] {
+    out.extend( &v.to_le_bytes( ) );
+  }
+  for v in &[position.0, position.1, position.2] {
+    out.extend( &v.to_le_bytes( ) );
+  }
+  out
+}
+
+#[test]
+fn round_trip( ) {
+  let scene = scene( );
+  for &compressed in &[false, true] {
+    let options = QbOptions { compressed, ..QbOptions::default( ) };
+    let back = qb::import( &qb::export( &scene, &options ) ).unwrap( );
+    assert_eq!( world_voxels( &back ), world_voxels( &scene ) );
+  }
+}
+
+#[test]
+fn truncated( ) {
+  for &compressed in &[false, true] {
+    let options = QbOptions { compressed, ..QbOptions::default( ) };
+    let bytes = qb::export( &scene( ), &options );
+    for len in 0..bytes.len( ) {
+      assert!( qb::import( &bytes[ ..len ] ).is_err( ), "length {}", len );
+    }
+  }
+}
+
+#[test]
+fn oversized( ) {
+  // The voxel count overflows 32 bits
+  let bytes = header( false, (0x10000, 0x10000, 1), (0, 0, 0) );
+  assert_eq!( qb::import( &bytes ).err( ), Some( ImportError::InvalidHeader ) );
+
+  // Far more voxels than the file contains
+  let bytes = header( false, (100, 100, 100), (0, 0, 0) );
+  assert!( qb::import( &bytes ).is_err( ) );
+
+  // The matrix extends beyond the range of coordinates
+  let bytes = header( false, (2, 1, 1), (i32::MAX, 0, 0) );
+  assert_eq!( qb::import( &bytes ).err( ), Some( ImportError::InvalidHeader ) );
+}
+
+#[test]
+fn run_beyond_slice( ) {
+  let mut bytes = header( true, (2, 2, 1), (0, 0, 0) );
+  for v in &[2, u32::MAX, 0xFF00_00FF, 6] {
+    bytes.extend( &u32::to_le_bytes( *v ) );
+  }
+  assert_eq!( qb::import( &bytes ).err( ), Some( ImportError::InvalidCompression ) );
+}
@@ -0,0 +1,95 @@
+//! Exact and reduced palettes of the color quantizer, with reserved and locked
+//! entries.
+
+
+// Local imports
+use vox_parser::palette::{self, QuantizeOptions, Rgba};
+
+
+/// A red and a blue cluster of slightly varying colors, 100 of each.
+fn clusters( ) -> Vec< Rgba > {
+  let mut colors: Vec< Rgba > = ( 0..100 ).map( |i| (200 + i % 10, i / 10, 0, 255) ).collect( );
+  colors.extend( ( 0..100 ).map( |i| (0, i % 10, 200 + i / 10, 255) ) );
+  colors
+}
+
+/// Returns the largest difference in any channel.
+fn distance( a: Rgba, b: Rgba ) -> u8 {
+  a.0.abs_diff( b.0 ).max( a.1.abs_diff( b.1 ) ).max( a.2.abs_diff( b.2 ) ).max( a.3.abs_diff( b.3 ) )
+}
+
+#[test]
+fn exact( ) {
+  // Few colors are kept as they are, in the order of their values
+  let colors = vec![ (9, 9, 9, 255), (1, 2, 3, 4), (9, 9, 9, 255), (0, 0, 255, 255) ];
+  let q = palette::quantize( colors.clone( ) );
+  assert_eq!( q.entries, vec![ (1, (0, 0, 255, 255)), (2, (1, 2, 3, 4)), (3, (9, 9, 9, 255)) ] );
+  assert_eq!( q.colors, vec![ (0, 0, 255, 255), (1, 2, 3, 4), (9, 9, 9, 255) ] );
+  for c in colors {
+    assert_eq!( q.colors[ q.index_of( c ).unwrap( ) as usize - 1 ], c );
+  }
+  assert_eq!( q.index_of( (5, 5, 5, 5) ), None );
+
+  // Which fills the palette exactly
+  let colors: Vec< Rgba > = ( 0..255 ).map( |i| (i, 0, 0, 255) ).collect( );
+  let q = palette::quantize( colors.clone( ) );
+  assert_eq!( q.colors, colors );
+}
+
+#[test]
+fn reserved_and_locked( ) {
+  let options = QuantizeOptions { reserved: vec![ 1, 3 ], locked: vec![ (2, (9, 9, 9, 255)) ], ..QuantizeOptions::default( ) };
+  let q = palette::quantize_with( vec![ (9, 9, 9, 255), (1, 1, 1, 255), (2, 2, 2, 255) ], &options );
+
+  // Reserved entries are transparent black, and equal colors use the locked
+  // entry
+  assert_eq!( q.entries, vec![ (2, (9, 9, 9, 255)), (4, (1, 1, 1, 255)), (5, (2, 2, 2, 255)) ] );
+  assert_eq!( q.colors[ ..3 ], [(0, 0, 0, 0), (9, 9, 9, 255), (0, 0, 0, 0)] );
+  assert_eq!( q.index_of( (9, 9, 9, 255) ), Some( 2 ) );
+
+  // Unused locked entries are still part of the palette
+  let q = palette::quantize_with( Vec::new( ), &options );
+  assert_eq!( q.entries, vec![ (2, (9, 9, 9, 255)) ] );
+
+  // Without any entries, no colors are mapped
+  let options = QuantizeOptions { reserved: ( 1..=255 ).collect( ), ..QuantizeOptions::default( ) };
+  let q = palette::quantize_with( vec![ (1, 1, 1, 255) ], &options );
+  assert!( q.entries.is_empty( ) && q.colors.is_empty( ) );
+  assert_eq!( q.index_of( (1, 1, 1, 255) ), None );
+}
+
+#[test]
+fn reduced( ) {
+  // Every color of a grid is mapped to a palette color, which is closer on
+  // average than the spacing of the grid
+  let colors: Vec< Rgba > = ( 0..1000u32 ).map( |i| ( ( i % 10 * 25 ) as u8, ( i / 10 % 10 * 25 ) as u8, ( i / 100 * 25 ) as u8, 255 ) ).collect( );
+  let q = palette::quantize( colors.clone( ) );
+  assert_eq!( q.entries.len( ), 255 );
+  let total: u32 = colors.iter( ).map( |c| distance( q.colors[ q.index_of( *c ).unwrap( ) as usize - 1 ], *c ) as u32 ).sum( );
+  assert!( total < 25 * 1000 );
+  let mut distinct = q.colors.clone( );
+  distinct.sort( );
+  distinct.dedup( );
+  assert_eq!( distinct.len( ), 255 );
+
+  // Two free entries hold the averages of the clusters
+  let options = QuantizeOptions { reserved: ( 3..=255 ).collect( ), ..QuantizeOptions::default( ) };
+  let q = palette::quantize_with( clusters( ), &options );
+  let (red, blue) = ( q.index_of( clusters( )[ 0 ] ).unwrap( ), q.index_of( clusters( )[ 100 ] ).unwrap( ) );
+  assert_ne!( red, blue );
+  assert!( clusters( )[ ..100 ].iter( ).all( |c| q.index_of( *c ) == Some( red ) ) );
+  assert!( clusters( )[ 100.. ].iter( ).all( |c| q.index_of( *c ) == Some( blue ) ) );
+  assert!( distance( q.colors[ red as usize - 1 ], (205, 5, 0, 255) ) <= 2 );
+  assert!( distance( q.colors[ blue as usize - 1 ], (0, 5, 205, 255) ) <= 2 );
+}
+
+#[test]
+fn reduced_locked( ) {
+  // The blue cluster uses the locked entry, which keeps its color
+  let options = QuantizeOptions { reserved: ( 3..=255 ).collect( ), locked: vec![ (2, (0, 0, 255, 255)) ], ..QuantizeOptions::default( ) };
+  let q = palette::quantize_with( clusters( ), &options );
+  assert_eq!( q.entries.len( ), 2 );
+  assert_eq!( q.colors[ 1 ], (0, 0, 255, 255) );
+  assert!( clusters( )[ ..100 ].iter( ).all( |c| q.index_of( *c ) == Some( 1 ) ) );
+  assert!( clusters( )[ 100.. ].iter( ).all( |c| q.index_of( *c ) == Some( 2 ) ) );
+}
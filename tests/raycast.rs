@@ -0,0 +1,106 @@
+//! Ray casts against models and scenes, which hit or miss.
+
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::Model;
+use vox_parser::data::spec::MatRowCols;
+use vox_parser::raycast::{self, Ray};
+
+
+/// A 4x4x4 model with a single voxel at `(1,2,0)`, and one at `(3,3,3)`.
+fn model( ) -> Model {
+  Model { size: (4, 4, 4), xyzi: vec![ (1,2,0,5), (3,3,3,6) ] }
+}
+
+fn ray( origin: (f32, f32, f32), direction: (f32, f32, f32) ) -> Ray {
+  Ray { origin, direction }
+}
+
+#[test]
+fn model_hit( ) {
+  // Enters the bounds through the top, and descends to the voxel
+  let hit = raycast::model( &model( ), &ray( (1.5, 2.5, 10.0), (0.0, 0.0, -2.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.voxel, (1,2,0) );
+  assert_eq!( hit.local_voxel, (1,2,0) );
+  assert_eq!( hit.normal, (0,0,1) );
+  assert_eq!( hit.palette_index, 5 );
+  assert_eq!( hit.instance, None );
+  // Distances are in multiples of the direction
+  assert_eq!( hit.distance, 4.5 );
+
+  // Diagonally, through the side
+  let hit = raycast::model( &model( ), &ray( (-1.0, 0.5, 3.5), (1.0, 0.75, 0.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.voxel, (3,3,3) );
+  assert_eq!( hit.normal, (-1,0,0) );
+}
+
+#[test]
+fn model_miss( ) {
+  let model = model( );
+  // Passes beside the voxels, points away, or never enters the bounds
+  assert!( raycast::model( &model, &ray( (0.5, 0.5, 10.0), (0.0, 0.0, -1.0) ), f32::INFINITY ).is_none( ) );
+  assert!( raycast::model( &model, &ray( (1.5, 2.5, 10.0), (0.0, 0.0, 1.0) ), f32::INFINITY ).is_none( ) );
+  assert!( raycast::model( &model, &ray( (10.0, 2.5, 0.5), (0.0, 1.0, 0.0) ), f32::INFINITY ).is_none( ) );
+
+  // The voxel is beyond the maximum distance
+  assert!( raycast::model( &model, &ray( (1.5, 2.5, 10.0), (0.0, 0.0, -1.0) ), 8.9 ).is_none( ) );
+  assert!( raycast::model( &model, &ray( (1.5, 2.5, 10.0), (0.0, 0.0, -1.0) ), 9.1 ).is_some( ) );
+}
+
+#[test]
+fn inside( ) {
+  // Rays starting inside a voxel hit it immediately, without a normal
+  let hit = raycast::model( &model( ), &ray( (1.5, 2.5, 0.5), (1.0, 0.0, 0.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.voxel, (1,2,0) );
+  assert_eq!( hit.normal, (0,0,0) );
+  assert_eq!( hit.distance, 0.0 );
+}
+
+#[test]
+fn degenerate( ) {
+  let model = model( );
+  assert!( raycast::model( &model, &ray( (1.5, 2.5, 0.5), (0.0, 0.0, 0.0) ), f32::INFINITY ).is_none( ) );
+  assert!( raycast::model( &model, &ray( (1.5, 2.5, 10.0), (0.0, f32::NAN, -1.0) ), f32::INFINITY ).is_none( ) );
+  assert!( raycast::model( &model, &ray( (1.5, f32::INFINITY, 10.0), (0.0, 0.0, -1.0) ), f32::INFINITY ).is_none( ) );
+  // Too small to ever cross a voxel boundary
+  assert!( raycast::model( &model, &ray( (0.5, 0.5, 3.5), (1e-30, 0.0, 0.0) ), f32::INFINITY ).is_none( ) );
+}
+
+#[test]
+fn scene( ) {
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_voxels( vec![ (0,0,0,1) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,2), (2,0,0,2) ] );
+  builder
+    .add( NodeBuilder::shape( a ).translation( (0, 0, -5) ) )
+    .add( NodeBuilder::shape( b ).translation( (10, 0, 0) ).rotation( MatRowCols::TwoOneThree( true, false, false ) ) )
+    .add( NodeBuilder::shape( a ).translation( (9, 1, 5) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  let scene = builder.build( ).unwrap( );
+
+  let instances = scene.instances( );
+  let world = instances[ 1 ].voxel_to_world( &scene.models[ b as usize ], (2,0,0) );
+  let hidden = instances[ 2 ].voxel_to_world( &scene.models[ a as usize ], (0,0,0) );
+  assert_eq!( (hidden.0, hidden.1), (world.0, world.1) );
+  let center = (world.0 as f32 + 0.5, world.1 as f32 + 0.5, world.2 as f32 + 0.5);
+
+  // The hit is mapped back into world space. The voxel on the hidden layer,
+  // which is in front of it, is ignored.
+  let hit = raycast::scene( &scene, &ray( (center.0, center.1, 20.0), (0.0, 0.0, -1.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.voxel, world );
+  assert_eq!( hit.local_voxel, (2,0,0) );
+  assert_eq!( hit.normal, (0,0,1) );
+  assert_eq!( hit.palette_index, 2 );
+  assert_eq!( hit.instance, Some( 1 ) );
+
+  // The rotation turns the model onto the y axis
+  let hit = raycast::scene( &scene, &ray( (-20.0, center.1, center.2), (1.0, 0.0, 0.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.voxel, world );
+  assert_eq!( hit.normal, (-1,0,0) );
+
+  // Instances other than the rotated one are hit too, but only ahead of the ray
+  let hit = raycast::scene( &scene, &ray( (0.5, 0.5, -20.0), (0.0, 0.0, 1.0) ), f32::INFINITY ).unwrap( );
+  assert_eq!( hit.instance, Some( 0 ) );
+  assert!( raycast::scene( &scene, &ray( (0.5, 0.5, -20.0), (0.0, 0.0, -1.0) ), f32::INFINITY ).is_none( ) );
+}
@@ -0,0 +1,161 @@
+//! Permuting, merging, compacting, sorting, and matching the palette of a
+//! scene, which remaps the voxels of all models.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{MaterialType, MetalMaterial, VoxScene};
+use vox_parser::palette::PaletteOrder;
+use common::world_voxels;
+
+
+/// A scene whose palette has distinct colors, with two models that use the
+/// palette indices 3, 5, and 200.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  for i in 1..=255u8 {
+    builder.color( i, ( i, 255 - i, i.wrapping_mul( 7 ), 255 ) );
+  }
+  let a = builder.add_voxels( vec![ (0,0,0,200), (1,0,0,3) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,5), (0,0,1,3) ] );
+  builder.add( NodeBuilder::shape( a ) ).add( NodeBuilder::shape( b ).translation( (5, 0, 0) ) );
+  builder.build( ).unwrap( )
+}
+
+/// Returns the palette indices of all voxels, model after model.
+fn indices( scene: &VoxScene ) -> Vec< u8 > {
+  scene.models.iter( ).flat_map( |m| m.xyzi.iter( ).map( |v| v.3 ) ).collect( )
+}
+
+/// Checks that the new palette holds the same materials, in any order.
+fn assert_same_materials( a: &VoxScene, b: &VoxScene ) {
+  let colors = |s: &VoxScene| {
+    let mut c: Vec< _ > = s.palette.iter( ).map( |m| m.rgba ).collect( );
+    c.sort( );
+    c
+  };
+  assert_eq!( colors( a ), colors( b ) );
+}
+
+#[test]
+fn permute( ) {
+  let original = scene( );
+  let mut scene = scene( );
+  // Reverses the palette
+  let mut permutation = [0; 255];
+  for (i, p) in permutation.iter_mut( ).enumerate( ) {
+    *p = 255 - i as u8;
+  }
+  scene.permute_palette( &permutation );
+
+  assert_eq!( indices( &scene ), vec![ 56, 253, 251, 253 ] );
+  assert_eq!( scene.palette[ 252 ], original.palette[ 2 ] );
+  assert_eq!( world_voxels( &scene ), world_voxels( &original ) );
+  assert_same_materials( &scene, &original );
+}
+
+#[test]
+#[should_panic( expected = "Not a permutation of the palette" )]
+fn permute_invalid( ) {
+  let mut permutation = [1; 255];
+  permutation[ 1 ] = 2;
+  scene( ).permute_palette( &permutation );
+}
+
+#[test]
+fn swap_and_merge( ) {
+  let original = scene( );
+  let mut scene = scene( );
+  scene.swap_palette_entries( 3, 200 );
+  assert_eq!( indices( &scene ), vec![ 3, 200, 5, 200 ] );
+  assert_eq!( world_voxels( &scene ), world_voxels( &original ) );
+
+  // Merged entries keep their material, but are no longer used
+  scene.merge_palette_entries( 200, 5 );
+  assert_eq!( indices( &scene ), vec![ 3, 5, 5, 5 ] );
+  assert_eq!( scene.palette[ 199 ], original.palette[ 2 ] );
+  assert_eq!( scene.palette_usage( )[ 5 ], 3 );
+}
+
+#[test]
+fn merge_identical( ) {
+  let mut merged = scene( );
+  merged.palette[ 6 ] = merged.palette[ 2 ].clone( );
+  merged.palette[ 199 ] = merged.palette[ 2 ].clone( );
+  merged.palette[ 4 ] = merged.palette[ 1 ].clone( );
+  let expected = world_voxels( &merged );
+
+  // Voxels use the lowest index of their material
+  assert_eq!( merged.merge_identical_materials( ), 3 );
+  assert_eq!( indices( &merged ), vec![ 3, 3, 2, 3 ] );
+  assert_eq!( world_voxels( &merged ), expected );
+
+  // Materials of another type are not identical
+  let mut scene = scene( );
+  scene.palette[ 4 ] = scene.palette[ 2 ].clone( );
+  scene.palette[ 4 ].mat_type =
+    MaterialType::Metal( MetalMaterial { prop_rough: 0.1, prop_ior: 0.3, prop_metal: 1.0, prop_spec: 0.5, prop_plastic: false } );
+  assert_eq!( scene.merge_identical_materials( ), 0 );
+}
+
+#[test]
+fn compact( ) {
+  let original = scene( );
+  let mut scene = scene( );
+  assert_eq!( scene.compact_palette( ), 3 );
+
+  // The used entries keep their order, and so do the unused ones
+  assert_eq!( indices( &scene ), vec![ 3, 1, 2, 1 ] );
+  assert_eq!( scene.palette[ 3 ], original.palette[ 0 ] );
+  assert_eq!( scene.palette[ 4 ], original.palette[ 1 ] );
+  assert_eq!( scene.palette[ 5 ], original.palette[ 3 ] );
+  assert_eq!( world_voxels( &scene ), world_voxels( &original ) );
+  assert_same_materials( &scene, &original );
+}
+
+#[test]
+fn sort( ) {
+  let original = scene( );
+  let mut gray = scene( );
+  gray.palette[ 199 ].rgba = (50, 50, 50, 255);
+  gray.palette[ 2 ].rgba = (10, 10, 10, 255);
+
+  // Grays come first, from dark to light
+  let mut scene = gray.clone( );
+  scene.sort_palette( PaletteOrder::Hue );
+  assert_eq!( (scene.palette[ 0 ].rgba, scene.palette[ 1 ].rgba), ((10, 10, 10, 255), (50, 50, 50, 255)) );
+  let sorted = indices( &scene );
+  assert_eq!( (sorted[ 0 ], sorted[ 1 ], sorted[ 3 ]), (2, 1, 1) );
+  assert_eq!( world_voxels( &scene ), world_voxels( &gray ) );
+
+  let mut scene = original.clone( );
+  scene.sort_palette( PaletteOrder::Luminance );
+  assert_eq!( world_voxels( &scene ), world_voxels( &original ) );
+  assert_same_materials( &scene, &original );
+  // Green is perceived as lighter than red or blue
+  assert_eq!( scene.palette[ 254 ].rgba, (1, 254, 7, 255) );
+}
+
+#[test]
+fn match_other( ) {
+  let original = scene( );
+  let mut palette = scene( ).palette;
+  // Index 3 has another type, but index 10 has an identical material
+  palette[ 2 ].mat_type =
+    MaterialType::Metal( MetalMaterial { prop_rough: 0.1, prop_ior: 0.3, prop_metal: 1.0, prop_spec: 0.5, prop_plastic: false } );
+  palette[ 9 ] = original.palette[ 2 ].clone( );
+  // Other colors are slightly off, or gone
+  palette[ 4 ].rgba.0 += 1;
+  palette[ 199 ].rgba = (0, 0, 0, 255);
+
+  let mut scene = scene( );
+  scene.match_palette( &palette );
+  assert_eq!( scene.palette, palette );
+  let matched = indices( &scene );
+  assert_eq!( matched[ 1.. ], [10, 5, 10] );
+
+  // The color of index 200 is gone, so its voxel takes a neighbouring color
+  assert!( matched[ 0 ] == 199 || matched[ 0 ] == 201 );
+}
@@ -0,0 +1,96 @@
+//! The group of 48 rotation/mirror transformations.
+
+
+// Stdlib imports
+use std::collections::HashSet;
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::VoxScene;
+use vox_parser::data::spec::{Axis, MatRowCols};
+
+
+const V: (i32,i32,i32) = (1, 2, 3);
+
+#[test]
+fn all( ) {
+  let all = MatRowCols::all( );
+  assert_eq!( all.iter( ).collect::< HashSet< _ > >( ).len( ), 48 );
+  assert_eq!( all.iter( ).filter( |m| m.is_rotation( ) ).count( ), 24 );
+  assert_eq!( all.iter( ).filter( |m| m.is_mirror( ) ).count( ), 24 );
+  assert_eq!( all.iter( ).filter( |m| m.is_identity( ) ).collect::< Vec< _ > >( ), vec![ &MatRowCols::identity( ) ] );
+
+  for m in &all {
+    assert_eq!( MatRowCols::from_matrix( m.matrix( ) ), Some( *m ) );
+    assert!( m.determinant( ) == 1 || m.determinant( ) == -1 );
+  }
+}
+
+#[test]
+fn compose( ) {
+  let all = MatRowCols::all( );
+  let id = MatRowCols::identity( );
+
+  for a in &all {
+    assert_eq!( a.compose( &id ), *a );
+    assert_eq!( id.compose( a ), *a );
+    assert_eq!( a.compose( &a.inverse( ) ), id );
+    assert_eq!( a.inverse( ).compose( a ), id );
+    assert_eq!( a.inverse( ).inverse( ), *a );
+    assert_eq!( a.inverse( ).apply_to( a.apply_to( V ) ), V );
+
+    for b in &all {
+      let ab = a.compose( b );
+      assert_eq!( ab.apply_to( V ), a.apply_to( b.apply_to( V ) ) );
+      assert_eq!( ab.determinant( ), a.determinant( ) * b.determinant( ) );
+      assert_eq!( ab.inverse( ), b.inverse( ).compose( &a.inverse( ) ) );
+
+      // Composition is closed over the group, and each product is unique
+      let products: HashSet< _ > = all.iter( ).map( |c| ab.compose( c ) ).collect( );
+      assert_eq!( products.len( ), 48 );
+    }
+  }
+}
+
+#[test]
+fn constructions( ) {
+  for &axis in &[Axis::X, Axis::Y, Axis::Z] {
+    let mirror = MatRowCols::mirror( axis );
+    assert!( mirror.is_mirror( ) );
+    assert_eq!( mirror.compose( &mirror ), MatRowCols::identity( ) );
+
+    let turn = MatRowCols::from_axis_turns( axis, 1 );
+    assert!( turn.is_rotation( ) );
+    assert_eq!( MatRowCols::from_axis_turns( axis, 0 ), MatRowCols::identity( ) );
+    assert_eq!( MatRowCols::from_axis_turns( axis, 4 ), MatRowCols::identity( ) );
+    assert_eq!( MatRowCols::from_axis_turns( axis, -1 ), turn.inverse( ) );
+    assert_eq!( MatRowCols::from_axis_turns( axis, 2 ), turn.compose( &turn ) );
+  }
+
+  assert_eq!( MatRowCols::mirror( Axis::Y ).apply_to( V ), (1, -2, 3) );
+  // Counter-clockwise by the right-hand rule
+  assert_eq!( MatRowCols::from_axis_turns( Axis::Z, 1 ).apply_to( (1,0,0) ), (0,1,0) );
+  assert_eq!( MatRowCols::from_axis_turns( Axis::X, 1 ).apply_to( (0,1,0) ), (0,0,1) );
+  assert_eq!( MatRowCols::from_axis_turns( Axis::Y, 1 ).apply_to( (0,0,1) ), (1,0,0) );
+
+  // Not a rotation/mirror matrix
+  assert_eq!( MatRowCols::from_matrix( [1,0,0, 1,0,0, 0,0,1] ), None );
+  assert_eq!( MatRowCols::from_matrix( [2,0,0, 0,1,0, 0,0,1] ), None );
+  assert_eq!( MatRowCols::from_matrix( [1,1,0, 0,1,0, 0,0,1] ), None );
+}
+
+#[test]
+fn file_round_trip( ) {
+  // Every transformation survives its encoding as a byte in the file
+  let mut builder = SceneBuilder::new( );
+  let model = builder.add_voxels( vec![ (0,0,0,1) ] );
+  for m in &MatRowCols::all( ) {
+    builder.add( NodeBuilder::shape( model ).rotation( *m ) );
+  }
+  let scene = builder.build( ).unwrap( );
+
+  let bytes = vox_parser::unparse::file_custom( &scene );
+  let back: VoxScene = vox_parser::parse::file_custom( &bytes ).unwrap( );
+  let rotations = |s: &VoxScene| s.instances( ).iter( ).map( |i| i.rotation ).collect::< Vec< _ > >( );
+  assert_eq!( rotations( &back ), MatRowCols::all( ).to_vec( ) );
+  assert_eq!( rotations( &back ), rotations( &scene ) );
+}
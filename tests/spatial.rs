@@ -0,0 +1,114 @@
+//! Bounding boxes, and voxel queries by world position.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{Model, VoxScene};
+use vox_parser::data::spec::MatRowCols;
+use vox_parser::spatial::Aabb;
+use common::world_voxels;
+
+
+fn aabb( min: (i32,i32,i32), max: (i32,i32,i32) ) -> Aabb {
+  Aabb { min, max }
+}
+
+/// A scene with a mirrored and rotated model, and an overlapping model.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_voxels( vec![ (0,0,0,1), (3,1,0,2), (1,0,2,3) ] );
+  let b = builder.add_voxels( vec![ (0,0,0,4) ] );
+  builder
+    .add( NodeBuilder::shape( a ).translation( (5, -3, 2) ).rotation( MatRowCols::ThreeOneTwo( true, false, true ) ) )
+    .add( NodeBuilder::shape( b ).translation( (-7, 0, 0) ) )
+    .add( NodeBuilder::shape( b ).translation( (-7, 0, 0) ) );
+  builder.build( ).unwrap( )
+}
+
+#[test]
+fn boxes( ) {
+  let a = aabb( (0,0,0), (4,3,2) );
+  assert_eq!( a, Aabb::from_size( (4, 3, 2) ) );
+  assert_eq!( a.size( ), (4, 3, 2) );
+  assert!( a.contains( (0,0,0) ) && a.contains( (3,2,1) ) );
+  assert!( !a.contains( (4,0,0) ) && !a.contains( (0,0,-1) ) );
+
+  // Boxes touching at a face share no voxels
+  let b = aabb( (4,0,0), (6,3,2) );
+  assert!( !a.intersects( &b ) );
+  assert_eq!( a.union( &b ), aabb( (0,0,0), (6,3,2) ) );
+
+  let c = aabb( (2,-1,1), (9,1,5) );
+  assert_eq!( a.intersection( &c ), Some( aabb( (2,0,1), (4,1,2) ) ) );
+  assert_eq!( a.intersection( &c ), c.intersection( &a ) );
+
+  // Inverted boxes are empty, and have no size
+  let empty = aabb( (3,0,0), (1,1,1) );
+  assert!( empty.is_empty( ) );
+  assert_eq!( empty.size( ), (0, 1, 1) );
+  assert!( !empty.intersects( &a ) );
+}
+
+#[test]
+fn enclosing( ) {
+  assert_eq!( Aabb::enclosing( Vec::new( ) ), None );
+  assert_eq!( Aabb::enclosing( vec![ (1,2,3) ] ), Some( aabb( (1,2,3), (2,3,4) ) ) );
+  assert_eq!( Aabb::enclosing( vec![ (1,2,3), (-1,5,0) ] ), Some( aabb( (-1,2,0), (2,6,4) ) ) );
+
+  // The tight bounds of a model may be smaller than its size
+  let model = Model { size: (10, 10, 10), xyzi: vec![ (2,3,4,1), (5,3,4,1) ] };
+  assert_eq!( model.bounds( ), Some( aabb( (2,3,4), (6,4,5) ) ) );
+  assert_eq!( Model { size: (10, 10, 10), xyzi: Vec::new( ) }.bounds( ), None );
+}
+
+#[test]
+fn scene_bounds( ) {
+  let scene = scene( );
+  let voxels = world_voxels( &scene );
+  assert_eq!( scene.bounds( ), Aabb::enclosing( voxels.iter( ).map( |(p, _)| *p ) ) );
+
+  // Instance bounds contain all of their voxels, and match the model size
+  let instances = scene.instances( );
+  for (inst, bounds) in instances.iter( ).zip( scene.instance_bounds( ) ) {
+    let model = &scene.models[ inst.model_id as usize ];
+    let (x, y, z) = model.size;
+    let (a, b, c) = bounds.size( );
+    let mut size = [a, b, c];
+    size.sort( );
+    let mut expected = [x, y, z];
+    expected.sort( );
+    assert_eq!( size, expected );
+
+    for (vx, vy, vz, _) in &model.xyzi {
+      assert!( bounds.contains( inst.voxel_to_world( model, (*vx as i32, *vy as i32, *vz as i32) ) ) );
+    }
+  }
+}
+
+#[test]
+fn voxels_in( ) {
+  let scene = scene( );
+  let everything = aabb( (-100,-100,-100), (100,100,100) );
+
+  // Every voxel is found, and the positions match the scene graph
+  let found = scene.voxels_in( &everything );
+  let mut positions: Vec< _ > = found.iter( ).map( |v| v.position ).collect( );
+  positions.sort( );
+  assert_eq!( positions, world_voxels( &scene ).iter( ).map( |(p, _)| *p ).collect::< Vec< _ > >( ) );
+
+  // Each voxel is only found in its own position
+  for v in &found {
+    let region = aabb( v.position, ( v.position.0 + 1, v.position.1 + 1, v.position.2 + 1 ) );
+    assert!( scene.voxels_in( &region ).contains( v ) );
+    assert!( !scene.voxels_in( &aabb( ( v.position.0 + 1, v.position.1, v.position.2 ), everything.max ) ).contains( v ) );
+  }
+
+  // Overlapping instances report the first one
+  let first = scene.voxel_at( (-7,0,0) ).unwrap( );
+  assert_eq!( (first.instance, first.local, first.palette_index), (1, (0,0,0), 4) );
+  assert_eq!( scene.voxels_in( &aabb( (-7,0,0), (-6,1,1) ) ).len( ), 2 );
+  assert_eq!( scene.voxel_at( (-8,0,0) ), None );
+  assert!( scene.voxels_in( &aabb( (50,50,50), (60,60,60) ) ).is_empty( ) );
+}
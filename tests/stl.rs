@@ -0,0 +1,149 @@
+//! Watertight solids of exported `.stl` files, in both encodings.
+
+
+// Stdlib imports
+use std::collections::HashMap;
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{Model, VoxScene};
+use vox_parser::formats::stl::{self, StlEncoding, StlOptions};
+
+
+/// A triangle with its normal.
+type Triangle = ([f32; 3], [[f32; 3]; 3]);
+
+/// A 3x3x3 cube with an enclosed cavity at its center. The center of the top
+/// face has a different color.
+fn hollow( ) -> Model {
+  let mut xyzi = Vec::new( );
+  for z in 0..3 {
+    for y in 0..3 {
+      for x in 0..3 {
+        if (x, y, z) != (1, 1, 1) {
+          xyzi.push( (x, y, z, if (x, y, z) == (1, 1, 2) { 2 } else { 1 }) );
+        }
+      }
+    }
+  }
+  Model { size: (3, 3, 3), xyzi }
+}
+
+/// A scene with two touching voxels, on different layers, and a hidden voxel.
+fn scene( ) -> VoxScene {
+  let mut builder = SceneBuilder::new( );
+  let a = builder.add_voxels( vec![ (0,0,0,1) ] );
+  builder
+    .add( NodeBuilder::shape( a ).layer( "First" ) )
+    .add( NodeBuilder::shape( a ).translation( (1, 0, 0) ).layer( "Second" ) )
+    .add( NodeBuilder::shape( a ).translation( (0, 0, 1) ).layer( "Hidden" ) )
+    .hide_layer( "Hidden", true );
+  builder.build( ).unwrap( )
+}
+
+/// Reads the triangles of a file in either encoding.
+fn read( bytes: &[u8] ) -> Vec< Triangle > {
+  if bytes.starts_with( b"solid " ) {
+    let text = std::str::from_utf8( bytes ).unwrap( );
+    assert!( text.ends_with( "endsolid vox\n" ) );
+    let floats = |line: &str, prefix: &str| -> [f32; 3] {
+      let v: Vec< f32 > = line.trim( ).strip_prefix( prefix ).unwrap( ).split( ' ' ).map( |w| w.parse( ).unwrap( ) ).collect( );
+      [v[ 0 ], v[ 1 ], v[ 2 ]]
+    };
+    let lines: Vec< &str > = text.lines( ).collect( );
+    lines[ 1..lines.len( ) - 1 ].chunks( 7 )
+      .map( |c| ( floats( c[ 0 ], "facet normal " ), [2, 3, 4].map( |i| floats( c[ i ], "vertex " ) ) ) )
+      .collect( )
+  } else {
+    let count = u32::from_le_bytes( [bytes[ 80 ], bytes[ 81 ], bytes[ 82 ], bytes[ 83 ]] ) as usize;
+    assert_eq!( bytes.len( ), 84 + 50 * count );
+    bytes[ 84.. ].chunks( 50 )
+      .map( |t| {
+        let v = |i: usize| f32::from_le_bytes( [t[ 4 * i ], t[ 4 * i + 1 ], t[ 4 * i + 2 ], t[ 4 * i + 3 ]] );
+        ( [v( 0 ), v( 1 ), v( 2 )], [1, 2, 3].map( |k| [v( 3 * k ), v( 3 * k + 1 ), v( 3 * k + 2 )] ) )
+      } )
+      .collect( )
+  }
+}
+
+/// Checks that every edge is traversed equally often in both directions, and
+/// that every triangle is counter-clockwise around its outward normal.
+fn assert_watertight( triangles: &[Triangle] ) {
+  let key = |p: [f32; 3]| p.map( |c| ( c * 1000.0 ).round( ) as i64 );
+  let mut edges: HashMap< ([i64; 3], [i64; 3]), i32 > = HashMap::new( );
+
+  for (normal, [a, b, c]) in triangles {
+    let (u, v) = ( [0, 1, 2].map( |k| b[ k ] - a[ k ] ), [0, 1, 2].map( |k| c[ k ] - a[ k ] ) );
+    let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+    assert!( n[ 0 ] * normal[ 0 ] + n[ 1 ] * normal[ 1 ] + n[ 2 ] * normal[ 2 ] > 0.0 );
+
+    for (p, q) in &[(a, b), (b, c), (c, a)] {
+      *edges.entry( (key( **p ), key( **q )) ).or_insert( 0 ) += 1;
+      *edges.entry( (key( **q ), key( **p )) ).or_insert( 0 ) -= 1;
+    }
+  }
+  assert!( edges.values( ).all( |n| *n == 0 ) );
+}
+
+#[test]
+fn encodings( ) {
+  for &exterior_only in &[false, true] {
+    let ascii = StlOptions { encoding: StlEncoding::Ascii, voxel_size: 0.5, exterior_only, ..StlOptions::default( ) };
+    let binary = StlOptions { encoding: StlEncoding::Binary, ..ascii.clone( ) };
+    let triangles = read( &stl::export_model( &hollow( ), &binary ) );
+    assert_eq!( read( &stl::export_model( &hollow( ), &ascii ) ), triangles );
+    assert_watertight( &triangles );
+  }
+
+  // The header of binary files must not look like an ASCII file
+  assert!( !stl::export_model( &hollow( ), &StlOptions::default( ) ).starts_with( b"solid" ) );
+}
+
+#[test]
+fn cavities( ) {
+  // 9 faces on every side of the cube, of 2 triangles each
+  let options = StlOptions::default( );
+  assert_eq!( read( &stl::export_model( &hollow( ), &options ) ).len( ), 6 * 9 * 2 );
+
+  // Plus the faces around the cavity
+  let options = StlOptions { exterior_only: false, ..StlOptions::default( ) };
+  let triangles = read( &stl::export_model( &hollow( ), &options ) );
+  assert_eq!( triangles.len( ), ( 6 * 9 + 6 ) * 2 );
+  // Whose normals point into the cavity
+  let inner = triangles.iter( ).filter( |(_, corners)| corners.iter( ).flatten( ).all( |c| *c >= 1.0 && *c <= 2.0 ) );
+  for (normal, corners) in inner {
+    let center = [0, 1, 2].map( |a| corners.iter( ).map( |c| c[ a ] ).sum::< f32 >( ) / 3.0 - 1.5 );
+    assert!( center[ 0 ] * normal[ 0 ] + center[ 1 ] * normal[ 1 ] + center[ 2 ] * normal[ 2 ] < 0.0 );
+  }
+}
+
+#[test]
+fn scale_and_pivot( ) {
+  let options = StlOptions { voxel_size: 2.5, ..StlOptions::default( ) };
+  let triangles = read( &stl::export_model( &hollow( ), &options ) );
+  // The solid is on the build plate, at its lowest corner
+  for axis in 0..3 {
+    let coords = triangles.iter( ).flat_map( |(_, c)| c.iter( ).map( move |p| p[ axis ] ) );
+    let (min, max) = coords.fold( (f32::MAX, f32::MIN), |(a, b), c| (a.min( c ), b.max( c )) );
+    assert_eq!( (min, max), (0.0, 7.5) );
+  }
+}
+
+#[test]
+fn filters( ) {
+  // Touching instances are merged, and hidden ones are omitted
+  let options = StlOptions::default( );
+  let triangles = read( &stl::export( &scene( ), &options ) );
+  assert_eq!( triangles.len( ), 10 * 2 );
+  assert_watertight( &triangles );
+
+  let options = StlOptions { layers: Some( vec![ 1 ] ), ..StlOptions::default( ) };
+  assert_eq!( read( &stl::export( &scene( ), &options ) ).len( ), 6 * 2 );
+  let options = StlOptions { layers: Some( Vec::new( ) ), ..StlOptions::default( ) };
+  assert!( read( &stl::export( &scene( ), &options ) ).is_empty( ) );
+
+  // Without its top center, the cavity of the hollow cube is exterior
+  let options = StlOptions { palette_indices: Some( vec![ 1 ] ), ..StlOptions::default( ) };
+  let triangles = read( &stl::export_model( &hollow( ), &options ) );
+  assert_eq!( triangles.len( ), ( 6 * 9 - 1 + 5 + 4 ) * 2 );
+  assert_watertight( &triangles );
+}
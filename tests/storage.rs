@@ -0,0 +1,129 @@
+//! Random access and model conversions of the voxel storages.
+
+
+// Local imports
+use vox_parser::data::custom::Model;
+use vox_parser::storage::{DenseGrid, Octree, SparseGrid, VoxelStorage};
+
+
+/// A model whose voxels are not in x/y/z order, with an empty corner.
+fn model( ) -> Model {
+  Model { size: (3, 2, 2), xyzi: vec![ (2,1,1,4), (0,0,0,1), (1,0,0,2), (0,1,1,3) ] }
+}
+
+#[test]
+fn dense_round_trip( ) {
+  let grid = DenseGrid::from_model( &model( ) );
+  assert_eq!( grid.size( ), (3, 2, 2) );
+
+  // Models come back in x/y/z order
+  let back = grid.to_model( );
+  assert_eq!( back.size, (3, 2, 2) );
+  assert_eq!( back.xyzi, vec![ (0,0,0,1), (1,0,0,2), (0,1,1,3), (2,1,1,4) ] );
+  assert_eq!( grid.iter( ).map( |(_, i)| i ).collect::< Vec< _ > >( ), vec![ 1, 2, 3, 4 ] );
+}
+
+#[test]
+fn dense_access( ) {
+  let mut grid = DenseGrid::from_model( &model( ) );
+  assert_eq!( grid.get( (1,0,0) ), Some( 2 ) );
+  assert_eq!( grid.get( (1,1,0) ), Some( 0 ) );
+
+  // Positions outside the bounds hold nothing, and cannot be set
+  for pos in &[(-1,0,0), (3,0,0), (0,2,0), (0,0,2), (i32::MIN,0,0)] {
+    assert!( !grid.contains( *pos ) );
+    assert_eq!( grid.get( *pos ), None );
+    assert!( !grid.set( *pos, 5 ) );
+  }
+
+  // Palette index 0 clears the voxel
+  assert!( grid.set( (1,0,0), 0 ) );
+  assert!( grid.set( (1,1,0), 7 ) );
+  assert_eq!( grid.to_model( ).xyzi, vec![ (0,0,0,1), (1,1,0,7), (0,1,1,3), (2,1,1,4) ] );
+}
+
+#[test]
+fn dense_large( ) {
+  // Voxels beyond coordinate 255 cannot be stored in models
+  let mut grid = DenseGrid::new( (300, 1, 1) );
+  grid.set( (10,0,0), 1 );
+  grid.set( (299,0,0), 2 );
+  assert_eq!( grid.to_model( ).xyzi, vec![ (10,0,0,1) ] );
+}
+
+#[test]
+fn backends_agree( ) {
+  let dense = DenseGrid::from_model( &model( ) );
+  let sparse = < SparseGrid as VoxelStorage >::from_model( &model( ) );
+  let octree = < Octree as VoxelStorage >::from_model( &model( ) );
+  assert_eq!( sparse.len( ), 4 );
+  for back in &[sparse.to_model( ), octree.to_model( )] {
+    assert_eq!( back.size, (3, 2, 2) );
+    assert_eq!( back.xyzi, dense.to_model( ).xyzi );
+  }
+
+  for z in -1..3 {
+    for y in -1..3 {
+      for x in -1..4 {
+        assert_eq!( sparse.get( (x,y,z) ), dense.get( (x,y,z) ) );
+        assert_eq!( octree.get( (x,y,z) ), dense.get( (x,y,z) ) );
+        assert_eq!( VoxelStorage::contains( &sparse, (x,y,z) ), dense.contains( (x,y,z) ) );
+        assert_eq!( VoxelStorage::contains( &octree, (x,y,z) ), dense.contains( (x,y,z) ) );
+      }
+    }
+  }
+}
+
+#[test]
+fn sparse_access( ) {
+  let mut grid = < SparseGrid as VoxelStorage >::new( (3, 2, 2) );
+  assert!( grid.is_empty( ) );
+  assert!( grid.set( (2,1,1), 5 ) );
+  assert!( !grid.set( (3,0,0), 5 ) );
+  assert_eq!( grid.get( (2,1,1) ), Some( 5 ) );
+  assert_eq!( grid.get( (0,0,0) ), Some( 0 ) );
+  assert_eq!( grid.get( (-1,0,0) ), None );
+
+  // Clearing a voxel removes it from the map
+  assert!( grid.set( (2,1,1), 0 ) );
+  assert!( grid.is_empty( ) );
+}
+
+#[test]
+fn octree_collapses( ) {
+  let mut tree = < Octree as VoxelStorage >::new( (4, 4, 4) );
+  assert_eq!( tree.num_nodes( ), 1 );
+
+  // A single voxel splits every level down to that voxel
+  assert!( tree.set( (1,2,3), 7 ) );
+  assert_eq!( tree.num_nodes( ), 1 + 8 + 8 );
+  assert_eq!( tree.get( (1,2,3) ), Some( 7 ) );
+  assert_eq!( tree.get( (1,2,2) ), Some( 0 ) );
+
+  // Filling the whole cube collapses it back into a single node
+  for z in 0..4 {
+    for y in 0..4 {
+      for x in 0..4 {
+        tree.set( (x,y,z), 7 );
+      }
+    }
+  }
+  assert_eq!( tree.num_nodes( ), 1 );
+  assert_eq!( tree.voxels( ).count( ), 64 );
+
+  // As does clearing it again
+  tree.set( (0,0,0), 0 );
+  assert_eq!( tree.num_nodes( ), 1 + 8 + 8 );
+  tree.set( (0,0,0), 7 );
+  assert_eq!( tree.num_nodes( ), 1 );
+}
+
+#[test]
+fn octree_bounds( ) {
+  // The tree covers a power-of-two cube, but only the size is in bounds
+  let mut tree = < Octree as VoxelStorage >::new( (3, 1, 2) );
+  assert!( !tree.set( (3,0,0), 1 ) );
+  assert!( !tree.set( (0,1,0), 1 ) );
+  assert_eq!( tree.get( (3,3,3) ), None );
+  assert_eq!( tree.num_nodes( ), 1 );
+}
@@ -0,0 +1,99 @@
+//! Rotated models, and scenes with their rotations baked into the models.
+
+
+mod common;
+
+// Local imports
+use vox_parser::builder::{NodeBuilder, SceneBuilder};
+use vox_parser::data::custom::{Model, NodeType, SceneNode, VoxScene};
+use vox_parser::data::spec::{Axis, MatRowCols};
+use common::world_voxels;
+
+
+/// An asymmetric model, such that every transformation changes it.
+fn model( ) -> Model {
+  Model { size: (4, 3, 2), xyzi: vec![ (0,0,0,1), (3,0,0,2), (0,2,0,3), (0,0,1,4), (2,1,1,5) ] }
+}
+
+/// Returns `true` iff no node in the graph is rotated.
+fn is_unrotated( node: &SceneNode ) -> bool {
+  node.rotation.is_identity( ) &&
+    match &node.node_type {
+      NodeType::Group( children ) => children.iter( ).all( is_unrotated ),
+      NodeType::Shape( _ ) => true
+    }
+}
+
+#[test]
+fn transform( ) {
+  let mut m = model( );
+  m.transform( MatRowCols::from_axis_turns( Axis::Z, 1 ) );
+  assert_eq!( m.size, (3, 4, 2) );
+  // `(x,y)` becomes `(-y,x)`, which is shifted back into the bounds
+  assert_eq!( m.xyzi, vec![ (2,0,0,1), (2,3,0,2), (0,0,0,3), (2,0,1,4), (1,2,1,5) ] );
+
+  let mut m = model( );
+  m.transform( MatRowCols::mirror( Axis::X ) );
+  assert_eq!( m.size, (4, 3, 2) );
+  assert_eq!( m.xyzi, vec![ (3,0,0,1), (0,0,0,2), (3,2,0,3), (3,0,1,4), (1,1,1,5) ] );
+
+  for r in &MatRowCols::all( ) {
+    let mut m = model( );
+    m.transform( *r );
+    let (x, y, z) = m.size;
+    assert!( m.xyzi.iter( ).all( |v| ( v.0 as u32 ) < x && ( v.1 as u32 ) < y && ( v.2 as u32 ) < z ) );
+
+    // The inverse restores the model
+    m.transform( r.inverse( ) );
+    assert_eq!( m.size, model( ).size );
+    assert_eq!( m.xyzi, model( ).xyzi );
+  }
+}
+
+#[test]
+fn bake_rotations( ) {
+  for r in &MatRowCols::all( ) {
+    let mut builder = SceneBuilder::new( );
+    for i in 1..6 {
+      builder.color( i, (i * 40, 0, 0, 255) );
+    }
+    let a = builder.add_model( model( ) );
+    let b = builder.add_voxels( vec![ (0,0,0,1) ] );
+    builder
+      .add( NodeBuilder::group( vec![ NodeBuilder::shape( a ).translation( (3, -1, 2) ).rotation( MatRowCols::from_axis_turns( Axis::X, 1 ) ), NodeBuilder::shape( b ) ] ).translation( (-5, 7, 1) ).rotation( *r ) )
+      .add( NodeBuilder::shape( a ).rotation( *r ) )
+      .add( NodeBuilder::shape( a ).translation( (20, 0, 0) ).rotation( *r ) );
+    let scene: VoxScene = builder.build( ).unwrap( );
+
+    let mut baked = scene.clone( );
+    baked.bake_rotations( );
+    assert!( is_unrotated( &baked.graph ) );
+    assert_eq!( world_voxels( &baked ), world_voxels( &scene ), "{:?}", r );
+
+    // Instances with the same rotation share the rotated model, and unused
+    // models are removed
+    let ids = baked.instances( ).iter( ).map( |i| i.model_id ).collect::< Vec< _ > >( );
+    assert_eq!( ids[ 2 ], ids[ 3 ] );
+    let mut unique = ids.clone( );
+    unique.sort( );
+    unique.dedup( );
+    assert_eq!( unique, (0..baked.models.len( ) as u32).collect::< Vec< _ > >( ) );
+  }
+}
+
+#[test]
+fn bake_rotation( ) {
+  // Baking a single node keeps the original model, which may be used elsewhere
+  let mut models = vec![ model( ) ];
+  let mut builder = SceneBuilder::new( );
+  builder.add_model( model( ) );
+  builder.add( NodeBuilder::shape( 0 ).rotation( MatRowCols::mirror( Axis::Y ) ) );
+  let mut scene = builder.build( ).unwrap( );
+
+  let expected = world_voxels( &scene );
+  scene.graph.bake_rotation( &mut models );
+  assert_eq!( models.len( ), 2 );
+  scene.models = models;
+  assert!( is_unrotated( &scene.graph ) );
+  assert_eq!( world_voxels( &scene ), expected );
+}
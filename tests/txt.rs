@@ -0,0 +1,23 @@
+//! Malformed and extreme input for plain-text voxel lists.
+
+
+// Local imports
+use vox_parser::formats::ImportError;
+use vox_parser::formats::txt;
+
+
+#[test]
+fn signed_colors( ) {
+  // `from_str_radix` would read "+f" as 15
+  assert_eq!( txt::import( "0 0 0 +f+f+f\n" ).err( ), Some( ImportError::InvalidLine( 1 ) ) );
+  assert_eq!( txt::import( "# Comment\n0 0 0 -1-1-1\n" ).err( ), Some( ImportError::InvalidLine( 2 ) ) );
+}
+
+#[test]
+fn widely_spread( ) {
+  let input = format!( "{} 0 0 ff0000\n{} 0 0 0000ff\n", i32::MIN, i32::MAX );
+  let scene = txt::import( &input ).unwrap( );
+  let output = txt::export( &scene );
+  assert!( output.contains( &format!( "\n{} 0 0 ff0000\n", i32::MIN ) ) );
+  assert!( output.contains( &format!( "\n{} 0 0 0000ff\n", i32::MAX ) ) );
+}